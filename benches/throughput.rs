@@ -0,0 +1,48 @@
+// Encoding throughput of the whole machine on megabyte-sized messages
+//
+// Run with: cargo bench --bench throughput
+
+use std::time::{Duration, Instant};
+
+use enigma::{EnigmaBuilder, PlugboardConnection, Reflector, Rotor};
+
+const MEGABYTE: usize = 1024 * 1024;
+const RUNS: u32 = 5;
+
+fn main() {
+    let message: String = "DERFEINDGREIFTANDERKUESTEANWETTERBERICHTFOLGT"
+        .chars()
+        .cycle()
+        .take(MEGABYTE)
+        .collect();
+
+    let mut best = Duration::MAX;
+    for _ in 0..RUNS {
+        let mut enigma = EnigmaBuilder::init()
+            .rotor_left(Rotor::enigma_i_wehrmacht_i())
+            .rotor_middle(Rotor::enigma_i_wehrmacht_ii())
+            .rotor_right(Rotor::enigma_i_wehrmacht_iii())
+            .reflector(Reflector::b())
+            .plugboard_connections(vec![
+                PlugboardConnection::create("AQ").unwrap(),
+                PlugboardConnection::create("EZ").unwrap(),
+                PlugboardConnection::create("RT").unwrap(),
+            ])
+            .build()
+            .unwrap();
+
+        let start = Instant::now();
+        let result = enigma
+            .encode("AAA".into(), "XYZ".into(), message.clone())
+            .unwrap();
+        best = best.min(start.elapsed());
+        assert_eq!(result.encoded_message.len(), MEGABYTE);
+    }
+
+    println!(
+        "encode 1 MiB: best of {} runs {:.1} ms, {:.2} MiB/s",
+        RUNS,
+        best.as_secs_f64() * 1000.0,
+        1.0 / best.as_secs_f64()
+    );
+}
@@ -0,0 +1,181 @@
+use std::convert::TryFrom;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use crate::enigma::SUPPORTED_ALPHABET;
+use crate::error::Error;
+
+/// Letter of the supported alphabet - checked once when created, so the parts of the
+/// machine can use it as an index without failing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Letter(u8);
+
+impl Letter {
+    pub fn new(c: char) -> Result<Letter, Error> {
+        if !c.is_ascii_uppercase() {
+            return Err(Error::UnsupportedCharacter {
+                character: c,
+                index: 0,
+            });
+        }
+        Ok(Letter(c as u8 - b'A'))
+    }
+
+    // `i` must be lower than the alphabet length
+    pub(crate) fn from_index(i: u8) -> Letter {
+        debug_assert!((i as usize) < SUPPORTED_ALPHABET.len());
+        Letter(i)
+    }
+
+    /// Position in the alphabet, 0 for 'A'.
+    pub fn index(self) -> u8 {
+        self.0
+    }
+
+    pub fn to_char(self) -> char {
+        (b'A' + self.0) as char
+    }
+}
+
+impl TryFrom<char> for Letter {
+    type Error = Error;
+
+    fn try_from(c: char) -> Result<Self, Self::Error> {
+        Letter::new(c)
+    }
+}
+
+impl FromStr for Letter {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Error::check_length("Letter", s, 1)?;
+        Letter::new(s.chars().next().unwrap())
+    }
+}
+
+impl From<Letter> for char {
+    fn from(letter: Letter) -> Self {
+        letter.to_char()
+    }
+}
+
+impl Display for Letter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_char())
+    }
+}
+
+/// Wiring of a rotor, reflector or entry disk - the letters wired to 'A', 'B', 'C' and so on,
+/// each letter of the alphabet exactly once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Wiring([u8; 26]);
+
+impl Wiring {
+    pub fn new(letters: &str) -> Result<Wiring, Error> {
+        Error::check_length("Wiring", letters, SUPPORTED_ALPHABET.len())?;
+        Error::check_alphabet(letters)?;
+        let mut wiring = [0; 26];
+        let mut used = [false; 26];
+        for (index, c) in letters.chars().enumerate() {
+            let wired = Letter::new(c)?.index();
+            if used[wired as usize] {
+                return Err(Error::RepeatedLetter { letter: c, index });
+            }
+            used[wired as usize] = true;
+            wiring[index] = wired;
+        }
+        Ok(Wiring(wiring))
+    }
+
+    // letter (as index) wired to each letter
+    pub(crate) fn forward(&self) -> [u8; 26] {
+        self.0
+    }
+
+    pub(crate) fn inverse(&self) -> [u8; 26] {
+        let mut inverse = [0; 26];
+        for (i, wired) in self.0.iter().enumerate() {
+            inverse[*wired as usize] = i as u8;
+        }
+        inverse
+    }
+}
+
+impl FromStr for Wiring {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Wiring::new(s)
+    }
+}
+
+impl Display for Wiring {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for i in self.0.iter() {
+            write!(f, "{}", Letter(*i))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn letter_is_validated() {
+        assert_eq!(Letter::new('C').map(Letter::index), Ok(2));
+        assert_eq!(
+            Letter::try_from('c'),
+            Err(Error::UnsupportedCharacter {
+                character: 'c',
+                index: 0
+            })
+        );
+        assert_eq!(char::from(Letter::from_index(25)), 'Z');
+        assert_eq!("Q".parse::<Letter>().map(Letter::to_char), Ok('Q'));
+        assert_eq!(
+            "QV".parse::<Letter>(),
+            Err(Error::InvalidLength {
+                subject: "Letter",
+                expected: 1,
+                actual: 2
+            })
+        );
+    }
+
+    #[test]
+    fn wiring_round_trips() {
+        let wiring: Wiring = "EKMFLGDQVZNTOWYHXUSPAIBRCJ".parse().unwrap();
+        assert_eq!(wiring.to_string(), "EKMFLGDQVZNTOWYHXUSPAIBRCJ");
+        assert_eq!(wiring.forward()[0], 4);
+        assert_eq!(wiring.inverse()[4], 0);
+    }
+
+    #[test]
+    fn error_on_invalid_wiring() {
+        assert_eq!(
+            Wiring::new("ABC"),
+            Err(Error::InvalidLength {
+                subject: "Wiring",
+                expected: 26,
+                actual: 3
+            })
+        );
+        assert_eq!(
+            Wiring::new("EKMFLGDQVZNTOWYHXUSPAIBRCj"),
+            Err(Error::UnsupportedCharacter {
+                character: 'j',
+                index: 25
+            })
+        );
+        assert_eq!(
+            Wiring::new("EKMFLGDQVZNTOWYHXUSPAIBRCE"),
+            Err(Error::RepeatedLetter {
+                letter: 'E',
+                index: 25
+            })
+        );
+    }
+}
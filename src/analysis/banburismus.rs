@@ -0,0 +1,695 @@
+// Banburismus
+//
+// Two messages enciphered with keys which differ only in the right (or middle) rotor position
+// are "in depth" when slid against each other by the difference of the positions - the same
+// letters are then enciphered by the same machine state and plaintext repeats show through as
+// ciphertext repeats (about 1 in 17 for German text against 1 in 26 for random letters).
+//
+// Message keys are not known, only their indicators - every indicator letter stands for an
+// unknown key letter (the same indicator letter on the same place always means the same key
+// letter). Comparing messages whose indicators differ on a single place tells how far apart are
+// the key letters behind the indicator letters, and the links form chains which can be placed on
+// the alphabet. A link can only exist when the turnover notch of the wheel was not passed between
+// both positions, so only wheels with notches outside of the chained distances remain possible.
+
+use std::fmt::Display;
+
+use crate::analysis::{index, letter, RotorFactory};
+use crate::error::Error;
+
+const LETTERS: u8 = 26;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RepeatStatistics {
+    // chance that two letters enciphered at the same machine state are the same
+    pub in_depth_repeat_rate: f64,
+    // chance that two letters enciphered at different machine states are the same
+    pub random_repeat_rate: f64,
+}
+
+impl Default for RepeatStatistics {
+    fn default() -> Self {
+        // values used for German naval traffic
+        RepeatStatistics {
+            in_depth_repeat_rate: 1.0 / 17.0,
+            random_repeat_rate: 1.0 / 26.0,
+        }
+    }
+}
+
+impl RepeatStatistics {
+    pub fn repeat_decibans(&self) -> f64 {
+        10.0 * (self.in_depth_repeat_rate / self.random_repeat_rate).log10()
+    }
+
+    pub fn non_repeat_decibans(&self) -> f64 {
+        10.0 * ((1.0 - self.in_depth_repeat_rate) / (1.0 - self.random_repeat_rate)).log10()
+    }
+
+    /// Rates of 0 or 1 give infinite scores, but only for letters which were (or were not)
+    /// repeated - letters which were not seen add nothing, so the score is never NaN.
+    pub fn decibans(&self, repeats: usize, overlap: usize) -> f64 {
+        let score = |count: usize, decibans: f64| {
+            if count == 0 {
+                0.0
+            } else {
+                count as f64 * decibans
+            }
+        };
+        score(repeats, self.repeat_decibans())
+            + score(overlap - repeats, self.non_repeat_decibans())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Message {
+    indicator: [u8; 3],
+    text: Vec<u8>,
+}
+
+impl Message {
+    pub fn new(indicator: &str, text: &str) -> Result<Message, Error> {
+        Error::check_length("Indicator", indicator, 3)?;
+        Error::check_alphabet(indicator)?;
+        Error::check_alphabet(text)?;
+        let mut message = Message {
+            indicator: [0; 3],
+            text: text.chars().map(index).collect(),
+        };
+        for (i, c) in indicator.chars().enumerate() {
+            message.indicator[i] = index(c);
+        }
+        Ok(message)
+    }
+
+    pub fn indicator(&self) -> String {
+        self.indicator.iter().map(|i| letter(*i)).collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Alignment {
+    // letter `i` of the second message is set against the letter `i + offset` of the first one
+    pub offset: isize,
+    pub overlap: usize,
+    pub repeats: usize,
+    pub decibans: f64,
+}
+
+pub fn align(
+    first: &Message,
+    second: &Message,
+    offset: isize,
+    statistics: &RepeatStatistics,
+) -> Alignment {
+    let mut overlap = 0;
+    let mut repeats = 0;
+    for (j, c) in second.text.iter().enumerate() {
+        let i = j as isize + offset;
+        if i < 0 || i as usize >= first.text.len() {
+            continue;
+        }
+        overlap += 1;
+        if first.text[i as usize] == *c {
+            repeats += 1;
+        }
+    }
+    Alignment {
+        offset,
+        overlap,
+        repeats,
+        decibans: statistics.decibans(repeats, overlap),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Slot {
+    Middle,
+    Right,
+}
+
+impl Slot {
+    fn indicator_index(&self) -> usize {
+        match self {
+            Slot::Middle => 1,
+            Slot::Right => 2,
+        }
+    }
+
+    // keypresses needed to move the wheel in the slot by one position
+    fn keypresses_per_step(&self) -> isize {
+        match self {
+            Slot::Middle => LETTERS as isize,
+            Slot::Right => 1,
+        }
+    }
+}
+
+impl Display for Slot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Slot::Middle => f.write_str("Middle"),
+            Slot::Right => f.write_str("Right"),
+        }
+    }
+}
+
+/// Key letter behind `to_label` is `distance` positions after the key letter behind `from_label`
+/// and the wheel in `slot` has no turnover notch on the way.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Link {
+    pub slot: Slot,
+    pub from_message: usize,
+    pub to_message: usize,
+    pub from_label: char,
+    pub to_label: char,
+    pub distance: u8,
+    pub alignment: Alignment,
+}
+
+impl Display for Link {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} wheel: message #{} ({}) against message #{} ({}) at offset {}: {} repeats in {} letters, {:+.1} db => '{}' is {} after '{}'",
+            self.slot,
+            self.from_message,
+            self.from_label,
+            self.to_message,
+            self.to_label,
+            self.alignment.offset.abs(),
+            self.alignment.repeats,
+            self.alignment.overlap,
+            self.alignment.decibans,
+            self.to_label,
+            self.distance,
+            self.from_label
+        )
+    }
+}
+
+/// Indicator letters connected by links, placed relatively to the first of them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EvidenceChain {
+    pub slot: Slot,
+    // indicator letter and its distance from the first letter of the chain
+    pub positions: Vec<(char, u8)>,
+    pub links: Vec<Link>,
+    // links which disagree with the positions established by other links
+    pub conflicts: Vec<Link>,
+}
+
+impl EvidenceChain {
+    fn position_of(&self, label: char) -> Option<u8> {
+        self.positions
+            .iter()
+            .find(|(l, _)| *l == label)
+            .map(|(_, p)| *p)
+    }
+
+    /// Absolute positions of the first letter of the chain, for which the indicator letters
+    /// can be the encoding of the key letters: Enigma never encodes a letter to itself and
+    /// the encoding is reciprocal.
+    pub fn placements(&self) -> Vec<u8> {
+        (0..LETTERS)
+            .filter(|start| {
+                self.positions.iter().all(|(label, relative)| {
+                    let absolute = letter((relative + start) % LETTERS);
+                    if absolute == *label {
+                        return false;
+                    }
+                    match self.position_of(absolute) {
+                        Some(other) => letter((other + start) % LETTERS) == *label,
+                        None => true,
+                    }
+                })
+            })
+            .collect()
+    }
+
+    /// Positions (relative to the first letter of the chain) on which the wheel notch
+    /// can't be, as links would be broken by the turnover.
+    pub fn notch_free_positions(&self) -> [bool; LETTERS as usize] {
+        let mut free = [false; LETTERS as usize];
+        for link in &self.links {
+            let from = self.position_of(link.from_label).unwrap();
+            for d in 0..link.distance {
+                free[((from + d) % LETTERS) as usize] = true;
+            }
+        }
+        free
+    }
+
+    /// Placements of the chain which are possible for a wheel with given turnover positions.
+    pub fn placements_for_turnover(&self, turnover_offsets: &[u8]) -> Vec<u8> {
+        let free = self.notch_free_positions();
+        self.placements()
+            .into_iter()
+            .filter(|start| {
+                turnover_offsets
+                    .iter()
+                    .all(|notch| !free[((notch + LETTERS - start) % LETTERS) as usize])
+            })
+            .collect()
+    }
+}
+
+impl Display for EvidenceChain {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut alphabet = vec!['-'; LETTERS as usize];
+        for (label, position) in &self.positions {
+            alphabet[*position as usize] = *label;
+        }
+        writeln!(
+            f,
+            "{} wheel chain: {}",
+            self.slot,
+            alphabet.iter().collect::<String>()
+        )?;
+        for link in &self.links {
+            writeln!(f, "  {}", link)?;
+        }
+        for link in &self.conflicts {
+            writeln!(f, "  CONFLICT {}", link)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WheelCandidate {
+    pub name: &'static str,
+    pub slot: Slot,
+    // for each chain of the slot: positions of its first letter which fit the wheel notches
+    pub placements: Vec<Vec<u8>>,
+}
+
+impl Display for WheelCandidate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let placements: Vec<String> = self
+            .placements
+            .iter()
+            .enumerate()
+            .map(|(i, p)| {
+                format!(
+                    "chain #{} starts at {}",
+                    i,
+                    p.iter().map(|s| letter(*s)).collect::<String>()
+                )
+            })
+            .collect();
+        write!(
+            f,
+            "{} wheel may be {}: {}",
+            self.slot,
+            self.name,
+            placements.join(", ")
+        )
+    }
+}
+
+pub struct Report {
+    pub links: Vec<Link>,
+    pub chains: Vec<EvidenceChain>,
+    pub wheel_candidates: Vec<WheelCandidate>,
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for chain in &self.chains {
+            write!(f, "{}", chain)?;
+        }
+        for candidate in &self.wheel_candidates {
+            writeln!(f, "{}", candidate)?;
+        }
+        Ok(())
+    }
+}
+
+pub struct Banburismus {
+    statistics: RepeatStatistics,
+    // minimal score for the alignment to be accepted as a link
+    threshold: f64,
+    min_overlap: usize,
+}
+
+impl Banburismus {
+    pub fn new(statistics: RepeatStatistics) -> Banburismus {
+        Banburismus {
+            statistics,
+            threshold: 10.0,
+            min_overlap: 30,
+        }
+    }
+
+    pub fn threshold(mut self, decibans: f64) -> Self {
+        self.threshold = decibans;
+        self
+    }
+
+    pub fn min_overlap(mut self, min_overlap: usize) -> Self {
+        self.min_overlap = min_overlap;
+        self
+    }
+
+    /// Best alignment for every pair of messages with indicators differing only in the right
+    /// letter (and only in the middle letter), if it scores above the threshold.
+    pub fn links(&self, messages: &[Message]) -> Vec<Link> {
+        let mut links = vec![];
+        for a in 0..messages.len() {
+            for b in a + 1..messages.len() {
+                let slot = match Banburismus::differing_slot(&messages[a], &messages[b]) {
+                    Some(slot) => slot,
+                    None => continue,
+                };
+                if let Some(link) = self.link(messages, a, b, slot) {
+                    links.push(link);
+                }
+            }
+        }
+        links
+    }
+
+    fn differing_slot(a: &Message, b: &Message) -> Option<Slot> {
+        let same: Vec<bool> = (0..3).map(|i| a.indicator[i] == b.indicator[i]).collect();
+        match (same[0], same[1], same[2]) {
+            (true, true, false) => Some(Slot::Right),
+            (true, false, true) => Some(Slot::Middle),
+            _ => None,
+        }
+    }
+
+    fn link(&self, messages: &[Message], a: usize, b: usize, slot: Slot) -> Option<Link> {
+        let step = slot.keypresses_per_step();
+        let best = (1..LETTERS as isize)
+            .flat_map(|distance| vec![distance * step, -distance * step])
+            .map(|offset| align(&messages[a], &messages[b], offset, &self.statistics))
+            .filter(|alignment| alignment.overlap >= self.min_overlap)
+            .max_by(|x, y| x.decibans.total_cmp(&y.decibans))?;
+        if best.decibans < self.threshold {
+            return None;
+        }
+
+        // positive offset - key of the second message is after the key of the first one
+        let (from, to) = if best.offset > 0 { (a, b) } else { (b, a) };
+        let i = slot.indicator_index();
+        Some(Link {
+            slot,
+            from_message: from,
+            to_message: to,
+            from_label: letter(messages[from].indicator[i]),
+            to_label: letter(messages[to].indicator[i]),
+            distance: (best.offset.abs() / step) as u8,
+            alignment: best,
+        })
+    }
+
+    pub fn chains(links: &[Link]) -> Vec<EvidenceChain> {
+        let mut chains: Vec<EvidenceChain> = vec![];
+        for slot in [Slot::Right, Slot::Middle].iter() {
+            let mut remaining: Vec<&Link> = links.iter().filter(|l| l.slot == *slot).collect();
+            while !remaining.is_empty() {
+                let mut chain = EvidenceChain {
+                    slot: *slot,
+                    positions: vec![(remaining[0].from_label, 0)],
+                    links: vec![],
+                    conflicts: vec![],
+                };
+                // keep attaching links touching letters already in the chain
+                while let Some(i) = remaining.iter().position(|l| {
+                    chain.position_of(l.from_label).is_some()
+                        || chain.position_of(l.to_label).is_some()
+                }) {
+                    let link = remaining.remove(i);
+                    match (
+                        chain.position_of(link.from_label),
+                        chain.position_of(link.to_label),
+                    ) {
+                        (Some(from), Some(to)) => {
+                            if (from + link.distance) % LETTERS == to {
+                                chain.links.push(link.clone());
+                            } else {
+                                chain.conflicts.push(link.clone());
+                            }
+                        }
+                        (Some(from), None) => {
+                            let to = (from + link.distance) % LETTERS;
+                            chain.positions.push((link.to_label, to));
+                            chain.links.push(link.clone());
+                        }
+                        (None, Some(to)) => {
+                            let from = (to + LETTERS - link.distance) % LETTERS;
+                            chain.positions.push((link.from_label, from));
+                            chain.links.push(link.clone());
+                        }
+                        (None, None) => unreachable!(),
+                    }
+                }
+                chains.push(chain);
+            }
+        }
+        chains
+    }
+
+    pub fn wheel_candidates(
+        chains: &[EvidenceChain],
+        wheels: &[(&'static str, RotorFactory)],
+    ) -> Vec<WheelCandidate> {
+        let mut candidates = vec![];
+        for slot in [Slot::Right, Slot::Middle].iter() {
+            let slot_chains: Vec<&EvidenceChain> =
+                chains.iter().filter(|c| c.slot == *slot).collect();
+            if slot_chains.is_empty() {
+                continue;
+            }
+            for (name, rotor) in wheels {
+                let rotor = rotor();
+                let placements: Vec<Vec<u8>> = slot_chains
+                    .iter()
+                    .map(|c| c.placements_for_turnover(rotor.turnover_offsets()))
+                    .collect();
+                if placements.iter().all(|p| !p.is_empty()) {
+                    candidates.push(WheelCandidate {
+                        name,
+                        slot: *slot,
+                        placements,
+                    });
+                }
+            }
+        }
+        candidates
+    }
+
+    pub fn analyse(&self, messages: &[Message], wheels: &[(&'static str, RotorFactory)]) -> Report {
+        let links = self.links(messages);
+        let chains = Banburismus::chains(&links);
+        let wheel_candidates = Banburismus::wheel_candidates(&chains, wheels);
+        Report {
+            links,
+            chains,
+            wheel_candidates,
+        }
+    }
+}
+
+impl Default for Banburismus {
+    fn default() -> Self {
+        Banburismus::new(RepeatStatistics::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::{RotorOrder, ENIGMA_I_ROTORS};
+    use crate::reflector::Reflector;
+
+    const PLAINTEXTS: [&str; 4] = [
+        "THEWEATHERREPORTFORTHENORTHERNSEAAREAISASFOLLOWSWINDFROMTHEWESTATFORCESIXVISIBILITYGOOD\
+        SEASTATEROUGHNOCHANGEEXPECTEDFORTHENEXTTWELVEHOURSCONVOYSHOULDKEEPTOTHEPLANNEDROUTEAND\
+        REPORTPOSITIONATNOONTHEWEATHERWILLTURNWORSEINTHEEVENING",
+        "ALLUNITSARETOREPORTTHEIRPOSITIONANDFUELSTATEATTHEENDOFTHEWATCHTHEFLOTILLAWILLASSEMBLE\
+        ATTHEPOINTGIVENINTHEORDERSOFYESTERDAYANDWAITFORFURTHERINSTRUCTIONSFROMTHECOMMANDER\
+        THEREISNOCHANGETOTHEPLANOFTHEOPERATIONFORTHEWEEK",
+        "ENEMYDESTROYERSWERESEENINTHEMORNINGHEADINGTOWARDSTHECOASTATHIGHSPEEDTHEIRNUMBERIS\
+        ESTIMATEDATFOURTHEYWEREFOLLOWEDBYACRUISERANDTWOTRANSPORTSHIPSALLBOATSINTHEAREA\
+        SHOULDTAKECAREANDREPORTANYFURTHERCONTACTWITHOUTDELAY",
+        "SUPPLYSHIPWILLARRIVEATTHEHARBOURTOMORROWATDAWNTHECREWSHOULDPREPARETOLOADFUELAND\
+        FOODFORTHREEWEEKSATSEATHECAPTAINWILLREPORTTOTHEHEADQUARTERSATNOONTOGETTHENEWORDERS\
+        ANDTHEKEYSFORTHENEXTMONTHTHEWEATHERISEXPECTEDTOBEGOOD",
+    ];
+
+    // enciphers the plaintexts using keys which are the encoding of the indicators at the
+    // basic position of the day, as the Kriegsmarine did
+    fn messages(indicators: &[&str]) -> Vec<Message> {
+        let rotor_order =
+            RotorOrder::new(ENIGMA_I_ROTORS[0], ENIGMA_I_ROTORS[1], ENIGMA_I_ROTORS[2]);
+        let mut enigma = rotor_order.scrambler(Reflector::b);
+        indicators
+            .iter()
+            .zip(PLAINTEXTS.iter())
+            .map(|(indicator, plaintext)| {
+                enigma.change_rotor_setting("AHC").unwrap();
+                let key = enigma.encode_for_current_rotor_setting(indicator).unwrap();
+                enigma.change_rotor_setting(&key).unwrap();
+                let text = enigma.encode_for_current_rotor_setting(plaintext).unwrap();
+                Message::new(indicator, &text).unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn decibans_for_default_statistics() {
+        let statistics = RepeatStatistics::default();
+        assert!((statistics.repeat_decibans() - 1.845).abs() < 0.001);
+        assert!((statistics.non_repeat_decibans() + 0.093).abs() < 0.001);
+        assert!((statistics.decibans(10, 100) - 10.09).abs() < 0.01);
+    }
+
+    #[test]
+    fn decibans_for_certain_repeats() {
+        let statistics = RepeatStatistics {
+            in_depth_repeat_rate: 0.0,
+            random_repeat_rate: 1.0 / 26.0,
+        };
+        assert_eq!(
+            statistics.decibans(0, 10),
+            10.0 * statistics.non_repeat_decibans()
+        );
+        assert_eq!(statistics.decibans(1, 10), f64::NEG_INFINITY);
+
+        // no links, as the messages can never be in depth
+        let messages = messages(&["QWE", "QWR"]);
+        assert!(Banburismus::new(statistics).links(&messages).is_empty());
+    }
+
+    #[test]
+    fn alignment_counts_repeats_in_overlap() {
+        let statistics = RepeatStatistics::default();
+        let first = Message::new("AAA", "ABCDEFGH").unwrap();
+        let second = Message::new("AAB", "CDXF").unwrap();
+        let alignment = align(&first, &second, 2, &statistics);
+        assert_eq!(alignment.overlap, 4);
+        assert_eq!(alignment.repeats, 3);
+
+        let alignment = align(&first, &second, -2, &statistics);
+        assert_eq!(alignment.overlap, 2);
+        assert_eq!(alignment.repeats, 0);
+    }
+
+    #[test]
+    fn error_on_invalid_message() {
+        assert_eq!(
+            Message::new("AA", "ABC"),
+            Err(Error::InvalidLength {
+                subject: "Indicator",
+                expected: 3,
+                actual: 2
+            })
+        );
+        assert_eq!(
+            Message::new("AAA", "AB C"),
+            Err(Error::UnsupportedCharacter {
+                character: ' ',
+                index: 2
+            })
+        );
+    }
+
+    #[test]
+    fn links_messages_in_depth_on_right_wheel() {
+        let messages = messages(&["QWE", "QWR", "QWT"]);
+        let links = Banburismus::default().links(&messages);
+
+        assert_eq!(links.len(), 3);
+        for link in &links {
+            assert_eq!(link.slot, Slot::Right);
+            assert!(link.alignment.decibans >= 10.0);
+        }
+
+        let chains = Banburismus::chains(&links);
+        assert_eq!(chains.len(), 1);
+        assert!(chains[0].conflicts.is_empty());
+        assert_eq!(chains[0].positions.len(), 3);
+    }
+
+    #[test]
+    fn chain_is_placed_where_key_letters_really_are() {
+        let messages = messages(&["QWE", "QWR", "QWT"]);
+        let report = Banburismus::default().analyse(&messages, &ENIGMA_I_ROTORS);
+
+        // real key letters behind the indicator letters
+        let rotor_order =
+            RotorOrder::new(ENIGMA_I_ROTORS[0], ENIGMA_I_ROTORS[1], ENIGMA_I_ROTORS[2]);
+        let mut enigma = rotor_order.scrambler(Reflector::b);
+        enigma.change_rotor_setting("AHC").unwrap();
+        let chain = &report.chains[0];
+        let first_label = chain.positions[0].0;
+        let key = enigma
+            .encode_for_current_rotor_setting(&format!("QW{}", first_label))
+            .unwrap();
+        let start = index(key.chars().nth(2).unwrap());
+
+        assert!(chain.placements().contains(&start));
+        let right_wheel = report
+            .wheel_candidates
+            .iter()
+            .find(|c| c.slot == Slot::Right && c.name == "III")
+            .unwrap();
+        assert!(right_wheel.placements[0].contains(&start));
+    }
+
+    #[test]
+    fn chain_forbids_notch_between_linked_positions() {
+        let link = |from_label, to_label, distance| Link {
+            slot: Slot::Right,
+            from_message: 0,
+            to_message: 1,
+            from_label,
+            to_label,
+            distance,
+            alignment: Alignment {
+                offset: distance as isize,
+                overlap: 100,
+                repeats: 10,
+                decibans: 17.0,
+            },
+        };
+        let chains = Banburismus::chains(&[link('K', 'P', 3), link('P', 'W', 2)]);
+        assert_eq!(chains.len(), 1);
+        assert_eq!(chains[0].positions, vec![('K', 0), ('P', 3), ('W', 5)]);
+
+        let free = chains[0].notch_free_positions();
+        assert_eq!(&free[0..6], &[true, true, true, true, true, false]);
+
+        // chain starting at 'B' puts the letters on B, E, G
+        assert!(chains[0].placements().contains(&1));
+        // wheel with notch on 'C' would break the links
+        assert!(!chains[0].placements_for_turnover(&[2]).contains(&1));
+        // wheel with notch on 'G' is fine
+        assert!(chains[0].placements_for_turnover(&[6]).contains(&1));
+    }
+
+    #[test]
+    fn conflicting_links_are_reported() {
+        let link = |from_label, to_label, distance| Link {
+            slot: Slot::Right,
+            from_message: 0,
+            to_message: 1,
+            from_label,
+            to_label,
+            distance,
+            alignment: Alignment {
+                offset: distance as isize,
+                overlap: 100,
+                repeats: 10,
+                decibans: 17.0,
+            },
+        };
+        let chains =
+            Banburismus::chains(&[link('K', 'P', 3), link('P', 'W', 2), link('K', 'W', 7)]);
+        assert_eq!(chains[0].links.len(), 2);
+        assert_eq!(chains[0].conflicts.len(), 1);
+        assert!(chains[0].to_string().contains("CONFLICT"));
+    }
+}
@@ -0,0 +1,388 @@
+// Number of distinct keys of a model, derived from the wheels in the model registry. Counted
+// are the settings which change the encoding:
+// - ring of the left rotor only shifts its wiring like its position does, so ring and
+//   position of the left rotor (and of the greek wheel of M4) count once,
+// - reflector position counts only for models where it can be turned,
+// - UKW-D has one pair fixed by construction ('J' and 'Y' on the German notation), so the
+//   other 24 contacts can be wired in 23 * 21 * ... * 1 ways,
+// - Uhr is plugged instead of the plugboard cables with its 20 numbered plugs, every
+//   assignment of letters to the plugs and each of its 40 settings counts as different.
+
+use std::fmt::Display;
+
+use crate::error::Error;
+use crate::model::{find, Model, WheelSpec};
+use crate::plugboard::HISTORICAL_MAX_CABLES;
+
+const UHR_PLUGS: u64 = 20;
+const UHR_SETTINGS: u64 = 40;
+
+/// Counts the keys of a model, by default with all its wheels and as many cables as were
+/// issued.
+pub struct KeyspaceCalculator {
+    model: &'static Model,
+    rotors: Vec<&'static WheelSpec>,
+    cables: usize,
+    ukw_d: bool,
+    uhr: bool,
+}
+
+impl KeyspaceCalculator {
+    pub fn new(model: &'static Model) -> KeyspaceCalculator {
+        KeyspaceCalculator {
+            model,
+            rotors: model.rotors.iter().collect(),
+            cables: if model.plugboard {
+                HISTORICAL_MAX_CABLES
+            } else {
+                0
+            },
+            ukw_d: false,
+            uhr: false,
+        }
+    }
+
+    /// Only the rotors with these names are available, e.g. 'I' to 'V' of the Army.
+    pub fn rotors(mut self, names: &[&str]) -> Result<Self, Error> {
+        self.rotors = vec![];
+        for name in names {
+            let rotor = find(self.model.rotors, name)
+                .ok_or_else(|| Error::UnknownRotor(name.to_string()))?;
+            if !self.rotors.contains(&rotor) {
+                self.rotors.push(rotor);
+            }
+        }
+        Ok(self)
+    }
+
+    pub fn cables(mut self, cables: usize) -> Self {
+        self.cables = cables;
+        self
+    }
+
+    /// Rewirable reflector instead of the ones of the model (and without the greek wheel of
+    /// M4, which was used with UKW-D as a three rotor machine).
+    pub fn ukw_d(mut self) -> Self {
+        self.ukw_d = true;
+        self
+    }
+
+    /// Uhr connected instead of the plugboard cables, which needs 10 of them.
+    pub fn uhr(mut self) -> Self {
+        self.uhr = true;
+        self
+    }
+
+    pub fn calculate(&self) -> Result<Keyspace, Error> {
+        let model = self.model;
+        let n = self.rotors.len() as u64;
+        if n < 3 {
+            return Err(Error::UnsupportedKeyspace(format!(
+                "at least 3 rotors are needed, got {}",
+                n
+            )));
+        }
+        let max_cables = if model.plugboard { 13 } else { 0 };
+        if self.cables > max_cables {
+            return Err(Error::TooManyPlugboardPairs {
+                max: max_cables,
+                actual: self.cables,
+            });
+        }
+        if (self.ukw_d || self.uhr) && !model.plugboard {
+            return Err(Error::UnsupportedKeyspace(format!(
+                "UKW-D and Uhr were used only with the service machines, not with Enigma {}",
+                model.name
+            )));
+        }
+        if self.uhr && self.cables != UHR_PLUGS as usize / 2 {
+            return Err(Error::UnsupportedKeyspace(format!(
+                "Uhr replaces exactly {} cables, got {}",
+                UHR_PLUGS / 2,
+                self.cables
+            )));
+        }
+
+        let greek_wheel = !model.greek_wheels.is_empty() && !self.ukw_d;
+        let mut factors = vec![("rotor order", BigCount::from(n * (n - 1) * (n - 2)))];
+        if greek_wheel {
+            factors.push((
+                "greek wheel",
+                BigCount::from(model.greek_wheels.len() as u64),
+            ));
+        }
+        if self.ukw_d {
+            factors.push(("UKW-D wiring", BigCount::from(double_factorial(23))));
+        } else {
+            factors.push(("reflector", BigCount::from(model.reflectors.len() as u64)));
+        }
+        factors.push(("ring settings", BigCount::from(26 * 26)));
+        let wheels = if greek_wheel { 4 } else { 3 };
+        factors.push(("rotor positions", BigCount::from(26u64.pow(wheels))));
+        if model.settable_reflector && !self.ukw_d {
+            factors.push(("reflector position", BigCount::from(26)));
+        }
+        if self.uhr {
+            factors.push(("Uhr plugs", falling_factorial(26, UHR_PLUGS)));
+            factors.push(("Uhr settings", BigCount::from(UHR_SETTINGS)));
+        } else if model.plugboard {
+            factors.push(("plugboard", plugboard_connections(self.cables as u64)));
+        }
+        Ok(Keyspace { factors })
+    }
+}
+
+/// Number of keys, as the product of the independent parts of the setting.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keyspace {
+    pub factors: Vec<(&'static str, BigCount)>,
+}
+
+impl Keyspace {
+    pub fn total(&self) -> BigCount {
+        self.factors
+            .iter()
+            .fold(BigCount::from(1), |total, (_, factor)| total.mul(factor))
+    }
+}
+
+impl Display for Keyspace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (name, factor) in &self.factors {
+            writeln!(f, "{:<20}{:>40}", name, factor)?;
+        }
+        write!(f, "{:<20}{:>40}", "total", self.total())
+    }
+}
+
+// ways to connect `cables` pairs of 26 letters: 26! / ((26 - 2k)! * k! * 2^k)
+fn plugboard_connections(cables: u64) -> BigCount {
+    let mut count = falling_factorial(26, 2 * cables);
+    for k in 1..=cables {
+        count = count.div_small(2 * k);
+    }
+    count
+}
+
+// n * (n - 1) * ... * (n - k + 1)
+fn falling_factorial(n: u64, k: u64) -> BigCount {
+    (n - k + 1..=n).fold(BigCount::from(1), |count, i| count.mul(&BigCount::from(i)))
+}
+
+fn double_factorial(n: u64) -> u64 {
+    (1..=n).step_by(2).product()
+}
+
+/// Unsigned integer of any size - keyspaces with the Uhr do not fit in 128 bits.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BigCount {
+    // base 10^9, least significant first
+    digits: Vec<u64>,
+}
+
+const BASE: u64 = 1_000_000_000;
+
+impl BigCount {
+    pub fn mul(&self, other: &BigCount) -> BigCount {
+        let mut digits = vec![0; self.digits.len() + other.digits.len()];
+        for (i, a) in self.digits.iter().enumerate() {
+            let mut carry = 0;
+            for (j, b) in other.digits.iter().enumerate() {
+                let current = digits[i + j] + a * b + carry;
+                digits[i + j] = current % BASE;
+                carry = current / BASE;
+            }
+            digits[i + other.digits.len()] += carry;
+        }
+        BigCount::normalized(digits)
+    }
+
+    // exact only when `divisor` divides the number
+    fn div_small(&self, divisor: u64) -> BigCount {
+        let mut digits = self.digits.clone();
+        let mut remainder = 0;
+        for digit in digits.iter_mut().rev() {
+            let current = remainder * BASE + *digit;
+            *digit = current / divisor;
+            remainder = current % divisor;
+        }
+        debug_assert_eq!(remainder, 0);
+        BigCount::normalized(digits)
+    }
+
+    fn normalized(mut digits: Vec<u64>) -> BigCount {
+        while digits.len() > 1 && digits[digits.len() - 1] == 0 {
+            digits.pop();
+        }
+        BigCount { digits }
+    }
+
+    /// None if the number does not fit.
+    pub fn to_u128(&self) -> Option<u128> {
+        self.digits.iter().rev().try_fold(0u128, |value, digit| {
+            value
+                .checked_mul(BASE as u128)
+                .and_then(|v| v.checked_add(*digit as u128))
+        })
+    }
+}
+
+impl From<u64> for BigCount {
+    fn from(value: u64) -> Self {
+        BigCount::normalized(vec![value % BASE, value / BASE % BASE, value / BASE / BASE])
+    }
+}
+
+impl Display for BigCount {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut text = self.digits[self.digits.len() - 1].to_string();
+        for digit in self.digits.iter().rev().skip(1) {
+            text.push_str(&format!("{:09}", digit));
+        }
+        f.pad(&text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn model(name: &str) -> &'static Model {
+        Model::by_name(name).unwrap()
+    }
+
+    mod big_count {
+        use super::*;
+
+        #[test]
+        fn multiplies_beyond_u128() {
+            let big = BigCount::from(u64::MAX).mul(&BigCount::from(u64::MAX));
+            assert_eq!(big.to_u128(), Some(u64::MAX as u128 * u64::MAX as u128));
+            let bigger = big.mul(&BigCount::from(1000));
+            assert_eq!(bigger.to_u128(), None);
+            assert_eq!(
+                bigger.to_string(),
+                "340282366920938463426481119284349108225000"
+            );
+        }
+
+        #[test]
+        fn displays_zero_padded_digits() {
+            assert_eq!(BigCount::from(1_000_000_007).to_string(), "1000000007");
+            assert_eq!(BigCount::from(0).to_string(), "0");
+            assert_eq!(format!("{:>6}", BigCount::from(42)), "    42");
+        }
+    }
+
+    #[test]
+    fn plugboard_connections_for_each_number_of_cables() {
+        assert_eq!(plugboard_connections(0).to_u128(), Some(1));
+        assert_eq!(plugboard_connections(1).to_u128(), Some(325));
+        assert_eq!(
+            plugboard_connections(10).to_u128(),
+            Some(150_738_274_937_250)
+        );
+        // most connections with 11 cables, not 13
+        assert!(
+            plugboard_connections(11).to_u128().unwrap()
+                > plugboard_connections(13).to_u128().unwrap()
+        );
+    }
+
+    #[test]
+    fn enigma_i_with_army_wheels() {
+        let keyspace = KeyspaceCalculator::new(model("I")).calculate().unwrap();
+        let names: Vec<&str> = keyspace.factors.iter().map(|(name, _)| *name).collect();
+        assert_eq!(
+            names,
+            vec![
+                "rotor order",
+                "reflector",
+                "ring settings",
+                "rotor positions",
+                "plugboard"
+            ]
+        );
+        assert_eq!(
+            keyspace.total().to_u128(),
+            Some(60 * 3 * 676 * 17576 * 150_738_274_937_250)
+        );
+    }
+
+    #[test]
+    fn m4_with_greek_wheel() {
+        let keyspace = KeyspaceCalculator::new(model("M4"))
+            .cables(0)
+            .calculate()
+            .unwrap();
+        assert_eq!(
+            keyspace.total().to_u128(),
+            Some(336 * 2 * 2 * 676 * 26 * 17576)
+        );
+    }
+
+    #[test]
+    fn commercial_models_have_settable_reflector_and_no_plugboard() {
+        let keyspace = KeyspaceCalculator::new(model("K")).calculate().unwrap();
+        assert_eq!(keyspace.total().to_u128(), Some(6 * 676 * 17576 * 26));
+        assert_eq!(
+            KeyspaceCalculator::new(model("K")).cables(1).calculate(),
+            Err(Error::TooManyPlugboardPairs { max: 0, actual: 1 })
+        );
+    }
+
+    #[test]
+    fn available_rotors() {
+        let keyspace = KeyspaceCalculator::new(model("M3"))
+            .rotors(&["I", "II", "III"])
+            .unwrap()
+            .cables(0)
+            .calculate()
+            .unwrap();
+        assert_eq!(keyspace.factors[0].1.to_u128(), Some(6));
+        assert_eq!(
+            KeyspaceCalculator::new(model("I"))
+                .rotors(&["I", "VI"])
+                .err(),
+            Some(Error::UnknownRotor("VI".into()))
+        );
+        assert!(KeyspaceCalculator::new(model("I"))
+            .rotors(&["I", "II"])
+            .unwrap()
+            .calculate()
+            .is_err());
+    }
+
+    #[test]
+    fn ukw_d_and_uhr() {
+        let keyspace = KeyspaceCalculator::new(model("I"))
+            .ukw_d()
+            .uhr()
+            .calculate()
+            .unwrap();
+        assert_eq!(keyspace.factors[1].1.to_u128(), Some(316_234_143_225));
+        // 26! / 6!
+        let uhr_plugs: u128 = (7..=26).product();
+        assert_eq!(keyspace.factors[4].1.to_u128(), Some(uhr_plugs));
+        let expected = BigCount::from(60 * 676 * 17576 * 40)
+            .mul(&BigCount::from(316_234_143_225))
+            .mul(&falling_factorial(26, 20));
+        assert_eq!(keyspace.total(), expected);
+        assert!(KeyspaceCalculator::new(model("I"))
+            .cables(8)
+            .uhr()
+            .calculate()
+            .is_err());
+    }
+
+    #[test]
+    fn breakdown() {
+        let keyspace = KeyspaceCalculator::new(model("I"))
+            .cables(0)
+            .calculate()
+            .unwrap();
+        let text = keyspace.to_string();
+        assert!(text.starts_with("rotor order"));
+        assert!(text.ends_with(&format!("{}", 60 * 3 * 676 * 17576)));
+    }
+}
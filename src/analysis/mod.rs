@@ -0,0 +1,247 @@
+pub mod banburismus;
+pub mod keyspace;
+pub mod ngram;
+pub mod plugboard_solver;
+pub mod search;
+pub mod stats;
+pub mod stepping;
+pub mod zygalski;
+
+use std::fmt::Display;
+
+use crate::alphabet::Letter;
+use crate::enigma::Enigma;
+use crate::enigma_builder::EnigmaBuilder;
+use crate::error::Error;
+use crate::reflector::Reflector;
+use crate::rotors::rotor::Rotor;
+
+pub type RotorFactory = fn() -> Rotor;
+pub type ReflectorFactory = fn() -> Reflector;
+
+// wheels available in the box of Enigma I (I-III) extended by M3 (IV, V)
+pub const ENIGMA_I_ROTORS: [(&str, RotorFactory); 5] = [
+    ("I", Rotor::enigma_i_wehrmacht_i),
+    ("II", Rotor::enigma_i_wehrmacht_ii),
+    ("III", Rotor::enigma_i_wehrmacht_iii),
+    ("IV", Rotor::m3_wehrmacht_iv),
+    ("V", Rotor::m3_wehrmacht_v),
+];
+
+/// Walzenlage - which named wheel sits in the left, middle and right slot.
+#[derive(Clone, Copy)]
+pub struct RotorOrder {
+    names: [&'static str; 3],
+    rotors: [RotorFactory; 3],
+}
+
+impl RotorOrder {
+    pub fn new(
+        left: (&'static str, RotorFactory),
+        middle: (&'static str, RotorFactory),
+        right: (&'static str, RotorFactory),
+    ) -> RotorOrder {
+        RotorOrder {
+            names: [left.0, middle.0, right.0],
+            rotors: [left.1, middle.1, right.1],
+        }
+    }
+
+    /// All orders of three different wheels picked from `wheels`, e.g. 60 orders for 5 wheels.
+    pub fn all(wheels: &[(&'static str, RotorFactory)]) -> Vec<RotorOrder> {
+        let mut orders = vec![];
+        for l in 0..wheels.len() {
+            for m in 0..wheels.len() {
+                for r in 0..wheels.len() {
+                    if l != m && m != r && l != r {
+                        orders.push(RotorOrder::new(wheels[l], wheels[m], wheels[r]));
+                    }
+                }
+            }
+        }
+        orders
+    }
+
+    pub fn names(&self) -> [&'static str; 3] {
+        self.names
+    }
+
+    pub(crate) fn rotors(&self) -> [Rotor; 3] {
+        [(self.rotors[0])(), (self.rotors[1])(), (self.rotors[2])()]
+    }
+
+    // machine without plugboard connections, which is enough for most of the attacks,
+    // as plugboard does not change the cycle structure of encoding permutations; orders
+    // made by hand may repeat a wheel, so the builder is permissive
+    pub(crate) fn scrambler(&self, reflector: ReflectorFactory) -> Enigma {
+        let [left, middle, right] = self.rotors();
+        EnigmaBuilder::init()
+            .permissive()
+            .rotor_left(left)
+            .rotor_middle(middle)
+            .rotor_right(right)
+            .reflector(reflector())
+            .build()
+            .unwrap()
+    }
+}
+
+impl Display for RotorOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} {}", self.names[0], self.names[1], self.names[2])
+    }
+}
+
+impl std::fmt::Debug for RotorOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "RotorOrder({})", self)
+    }
+}
+
+/// Everything about the machine apart from the plugboard: wheels, rings and the position of
+/// the wheels before the first letter.
+#[derive(Clone, Debug)]
+pub struct RotorSetting {
+    rotor_order: RotorOrder,
+    reflector: ReflectorFactory,
+    ring_setting: String,
+    position: String,
+}
+
+impl RotorSetting {
+    pub fn new(
+        rotor_order: RotorOrder,
+        reflector: ReflectorFactory,
+        ring_setting: &str,
+        position: &str,
+    ) -> Result<RotorSetting, Error> {
+        Error::check_length("Ring setting", ring_setting, 3)?;
+        Error::check_alphabet(ring_setting)?;
+        Error::check_length("Rotor positions", position, 3)?;
+        Error::check_alphabet(position)?;
+        Ok(RotorSetting {
+            rotor_order,
+            reflector,
+            ring_setting: ring_setting.into(),
+            position: position.into(),
+        })
+    }
+
+    pub fn rotor_order(&self) -> RotorOrder {
+        self.rotor_order
+    }
+
+    pub fn ring_setting(&self) -> &str {
+        &self.ring_setting
+    }
+
+    pub fn position(&self) -> &str {
+        &self.position
+    }
+
+    // ring setting and position were validated, and the builder is permissive like the one
+    // of `RotorOrder`
+    pub(crate) fn scrambler(&self) -> Enigma {
+        let mut rings = self.ring_setting.chars();
+        let [left, middle, right] = self.rotor_order.rotors();
+        let mut enigma = EnigmaBuilder::init()
+            .permissive()
+            .rotor_left(left.with_ring_setting(rings.next().unwrap()).unwrap())
+            .rotor_middle(middle.with_ring_setting(rings.next().unwrap()).unwrap())
+            .rotor_right(right.with_ring_setting(rings.next().unwrap()).unwrap())
+            .reflector((self.reflector)())
+            .build()
+            .unwrap();
+        enigma.change_rotor_setting(&self.position).unwrap();
+        enigma
+    }
+
+    // permutations (as letter indexes) applied by the machine without plugboard to each of
+    // the next `length` letters
+    pub(crate) fn scrambler_permutations(&self, length: usize) -> Vec<[u8; 26]> {
+        let mut enigma = self.scrambler();
+        let mut permutations = Vec::with_capacity(length);
+        for _ in 0..length {
+            enigma.step();
+            let mut permutation = [0; 26];
+            for (i, p) in permutation.iter_mut().enumerate() {
+                *p = enigma
+                    .encode_without_stepping(Letter::from_index(i as u8))
+                    .index();
+            }
+            permutations.push(permutation);
+        }
+        permutations
+    }
+}
+
+pub(crate) fn letter(i: u8) -> char {
+    (b'A' + i) as char
+}
+
+pub(crate) fn index(c: char) -> u8 {
+    c as u8 - b'A'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn all_rotor_orders_for_five_wheels() {
+        let orders = RotorOrder::all(&ENIGMA_I_ROTORS);
+        assert_eq!(orders.len(), 60);
+        assert_eq!(orders[0].to_string(), "I II III");
+        assert_eq!(orders[59].to_string(), "V IV III");
+    }
+
+    #[test]
+    fn rotor_setting_with_repeated_wheel() {
+        let rotor_order =
+            RotorOrder::new(ENIGMA_I_ROTORS[0], ENIGMA_I_ROTORS[0], ENIGMA_I_ROTORS[1]);
+        let setting = RotorSetting::new(rotor_order, Reflector::b, "AAA", "AAA").unwrap();
+        let mut from_order = rotor_order.scrambler(Reflector::b);
+        from_order.change_rotor_setting("AAA").unwrap();
+        from_order.step();
+        assert_eq!(
+            setting.scrambler_permutations(1)[0][0],
+            from_order
+                .encode_without_stepping(Letter::from_index(0))
+                .index()
+        );
+    }
+
+    #[test]
+    fn error_on_invalid_rotor_setting() {
+        let rotor_order =
+            RotorOrder::new(ENIGMA_I_ROTORS[0], ENIGMA_I_ROTORS[1], ENIGMA_I_ROTORS[2]);
+        assert_eq!(
+            RotorSetting::new(rotor_order, Reflector::b, "AA", "AAA").err(),
+            Some(Error::InvalidLength {
+                subject: "Ring setting",
+                expected: 3,
+                actual: 2
+            })
+        );
+        assert_eq!(
+            RotorSetting::new(rotor_order, Reflector::b, "AAA", "AaA").err(),
+            Some(Error::UnsupportedCharacter {
+                character: 'a',
+                index: 1
+            })
+        );
+    }
+
+    #[test]
+    fn scrambler_permutations_are_involutions_without_fixed_points() {
+        let rotor_order =
+            RotorOrder::new(ENIGMA_I_ROTORS[3], ENIGMA_I_ROTORS[4], ENIGMA_I_ROTORS[0]);
+        let setting = RotorSetting::new(rotor_order, Reflector::c, "KDZ", "QEV").unwrap();
+        for permutation in setting.scrambler_permutations(30) {
+            for i in 0..26 {
+                assert_ne!(permutation[i], i as u8);
+                assert_eq!(permutation[permutation[i] as usize], i as u8);
+            }
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use crate::analysis::index;
+use crate::enigma::SUPPORTED_ALPHABET;
+use crate::error::Error;
+
+const LETTERS: usize = 26;
+
+// letter frequencies (in percent) of German texts
+const GERMAN_MONOGRAM_FREQUENCIES: [f64; LETTERS] = [
+    6.51, 1.89, 3.06, 5.08, 16.93, 1.66, 3.01, 4.76, 7.55, 0.27, 1.21, 3.44, 2.53, 9.78, 2.51,
+    0.79, 0.02, 7.00, 7.27, 6.15, 4.35, 0.67, 1.89, 0.03, 0.04, 1.13,
+];
+
+/// Log probabilities of letter n-grams used to tell how much a text looks like a language.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NgramModel {
+    n: usize,
+    // natural logarithms, indexed by the n-gram read as a number in base 26
+    log_probabilities: Vec<f64>,
+}
+
+impl NgramModel {
+    /// Counts n-grams (1 to 4 letters) of the letters found in `corpus`, other characters are
+    /// skipped. N-grams which never appeared get a probability lower than any seen one.
+    pub fn train(n: usize, corpus: &str) -> Result<NgramModel, Error> {
+        if n == 0 || n > 4 {
+            return Err(Error::NgramLengthOutOfRange(n));
+        }
+        let letters: Vec<u8> = corpus
+            .chars()
+            .flat_map(|c| c.to_uppercase())
+            .filter(|c| SUPPORTED_ALPHABET.contains(*c))
+            .map(index)
+            .collect();
+        if letters.len() < n {
+            return Err(Error::CorpusTooShort {
+                required: n,
+                actual: letters.len(),
+            });
+        }
+
+        let mut counts = vec![0usize; LETTERS.pow(n as u32)];
+        for ngram in letters.windows(n) {
+            counts[NgramModel::ngram_index(ngram)] += 1;
+        }
+        let total = (letters.len() - n + 1) as f64;
+        let floor = (0.01 / total).ln();
+        let log_probabilities = counts
+            .into_iter()
+            .map(|count| {
+                if count == 0 {
+                    floor
+                } else {
+                    (count as f64 / total).ln()
+                }
+            })
+            .collect();
+        Ok(NgramModel {
+            n,
+            log_probabilities,
+        })
+    }
+
+    pub fn german_monograms() -> NgramModel {
+        let total: f64 = GERMAN_MONOGRAM_FREQUENCIES.iter().sum();
+        NgramModel {
+            n: 1,
+            log_probabilities: GERMAN_MONOGRAM_FREQUENCIES
+                .iter()
+                .map(|f| (f / total).ln())
+                .collect(),
+        }
+    }
+
+    pub fn n(&self) -> usize {
+        self.n
+    }
+
+    /// Sum of the log probabilities of all n-grams in `text` (letters as indexes 0-25).
+    pub fn score(&self, text: &[u8]) -> f64 {
+        text.windows(self.n)
+            .map(|ngram| self.log_probabilities[NgramModel::ngram_index(ngram)])
+            .sum()
+    }
+
+    fn ngram_index(ngram: &[u8]) -> usize {
+        ngram
+            .iter()
+            .fold(0, |acc, letter| acc * LETTERS + *letter as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn indexes(text: &str) -> Vec<u8> {
+        text.chars().map(index).collect()
+    }
+
+    #[test]
+    fn german_text_scores_better_than_random_letters() {
+        let model = NgramModel::german_monograms();
+        assert!(
+            model.score(&indexes("DERFEINDGREIFTANDERKUESTEAN"))
+                > model.score(&indexes("QXJVZYPKQWXJVQZYKPXJQWVZYXK"))
+        );
+    }
+
+    #[test]
+    fn trained_bigrams() {
+        let model = NgramModel::train(2, "abab, ab!").unwrap();
+        assert_eq!(model.n(), 2);
+        // AB seen 3 times, BA 2 times out of 5 bigrams
+        assert!((model.score(&indexes("AB")) - (0.6f64).ln()).abs() < 1e-9);
+        assert!((model.score(&indexes("ABA")) - (0.6f64 * 0.4).ln()).abs() < 1e-9);
+        assert!(model.score(&indexes("AA")) < model.score(&indexes("BA")));
+    }
+
+    #[test]
+    fn error_on_unsupported_ngram_length() {
+        assert_eq!(
+            NgramModel::train(5, "ABCDEFGH"),
+            Err(Error::NgramLengthOutOfRange(5))
+        );
+        assert_eq!(
+            NgramModel::train(0, "ABCDEFGH"),
+            Err(Error::NgramLengthOutOfRange(0))
+        );
+    }
+
+    #[test]
+    fn error_on_too_short_corpus() {
+        assert_eq!(
+            NgramModel::train(3, "A B"),
+            Err(Error::CorpusTooShort {
+                required: 3,
+                actual: 2
+            })
+        );
+    }
+}
@@ -0,0 +1,536 @@
+// Plugboard (Steckerbrett) recovery for known rotor setting
+//
+// Machine without plugboard applies known permutation U(i) to the letter number i, so ciphertext
+// is c = S(U(i)(S(p))), where S is the unknown plugboard. With a crib every (p, c) pair gives
+// S(c) = U(i)(S(p)) - guessing S for one letter gives S for others, the same way the bombe did,
+// and guesses which lead to contradiction are dropped. Letters not touched by the crib (or all
+// of them if there is no crib) are found by hill climbing on the n-gram score of the plaintext.
+
+use crate::analysis::ngram::NgramModel;
+use crate::analysis::{index, letter, RotorSetting};
+use crate::error::Error;
+use crate::plugboard::PlugboardConnection;
+
+const LETTERS: usize = 26;
+const MAX_PAIRS: usize = 13;
+// no more partial solutions are searched for once that many are consistent with the crib
+const MAX_CRIB_SOLUTIONS: usize = 100;
+
+type Steckers = [u8; LETTERS];
+type PartialSteckers = [Option<u8>; LETTERS];
+
+/// Known plaintext for the ciphertext letters starting at `position`.
+#[derive(Debug, PartialEq)]
+pub struct Crib {
+    pub(crate) plaintext: Vec<u8>,
+    pub(crate) position: usize,
+}
+
+impl Crib {
+    pub fn new(plaintext: &str, position: usize) -> Result<Crib, Error> {
+        Error::check_alphabet(plaintext)?;
+        Ok(Crib {
+            plaintext: plaintext.chars().map(index).collect(),
+            position,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PairConfidence {
+    pub connection: PlugboardConnection,
+    // from 0 to 1
+    pub confidence: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlugboardSolution {
+    pub pairs: Vec<PairConfidence>,
+    // n-gram score of the plaintext
+    pub score: f64,
+    pub plaintext: String,
+}
+
+impl PlugboardSolution {
+    /// Connections which can be passed to `EnigmaBuilder::plugboard_connections`.
+    pub fn connections(&self) -> Vec<PlugboardConnection> {
+        self.pairs.iter().map(|p| p.connection.clone()).collect()
+    }
+}
+
+pub struct PlugboardSolver {
+    setting: RotorSetting,
+    model: NgramModel,
+    max_pairs: usize,
+}
+
+impl PlugboardSolver {
+    pub fn new(setting: RotorSetting, model: NgramModel) -> PlugboardSolver {
+        PlugboardSolver {
+            setting,
+            model,
+            max_pairs: MAX_PAIRS,
+        }
+    }
+
+    pub fn max_pairs(mut self, max_pairs: usize) -> Self {
+        self.max_pairs = max_pairs;
+        self
+    }
+
+    pub fn solve(&self, ciphertext: &str, crib: Option<&Crib>) -> Result<PlugboardSolution, Error> {
+        if self.max_pairs > MAX_PAIRS {
+            return Err(Error::TooManyPlugboardPairs {
+                max: MAX_PAIRS,
+                actual: self.max_pairs,
+            });
+        }
+        Error::check_alphabet(ciphertext)?;
+        let ciphertext: Vec<u8> = ciphertext.chars().map(index).collect();
+        let permutations = self.setting.scrambler_permutations(ciphertext.len());
+        let problem = Problem {
+            ciphertext,
+            permutations,
+            model: &self.model,
+            max_pairs: self.max_pairs,
+        };
+
+        match crib {
+            None => {
+                let mut steckers = identity();
+                problem.climb(&mut steckers, &[false; LETTERS]);
+                Ok(problem.solution(&steckers, &[None; LETTERS], &[]))
+            }
+            Some(crib) => {
+                let constraints = problem.constraints(crib)?;
+                let mut crib_solutions = vec![];
+                problem.search([None; LETTERS], &constraints, &mut crib_solutions);
+                if crib_solutions.is_empty() {
+                    return Err(Error::NoConsistentPlugboard);
+                }
+
+                let mut best: Option<(f64, Steckers, PartialSteckers)> = None;
+                for partial in &crib_solutions {
+                    let mut fixed = [false; LETTERS];
+                    let mut steckers = identity();
+                    for (i, s) in partial.iter().enumerate() {
+                        if let Some(s) = s {
+                            fixed[i] = true;
+                            steckers[i] = *s;
+                        }
+                    }
+                    let score = problem.climb(&mut steckers, &fixed);
+                    if best.as_ref().is_none_or(|(b, _, _)| score > *b) {
+                        best = Some((score, steckers, *partial));
+                    }
+                }
+                let (_, steckers, partial) = best.unwrap();
+                Ok(problem.solution(&steckers, &partial, &crib_solutions))
+            }
+        }
+    }
+}
+
+struct Problem<'a> {
+    ciphertext: Vec<u8>,
+    permutations: Vec<Steckers>,
+    model: &'a NgramModel,
+    max_pairs: usize,
+}
+
+impl<'a> Problem<'a> {
+    fn decode(&self, steckers: &Steckers) -> Vec<u8> {
+        self.ciphertext
+            .iter()
+            .zip(self.permutations.iter())
+            .map(|(c, u)| steckers[u[steckers[*c as usize] as usize] as usize])
+            .collect()
+    }
+
+    fn score(&self, steckers: &Steckers) -> f64 {
+        self.model.score(&self.decode(steckers))
+    }
+
+    // (plaintext letter, ciphertext letter, letter number) for every crib letter
+    fn constraints(&self, crib: &Crib) -> Result<Vec<(u8, u8, usize)>, Error> {
+        if crib.position + crib.plaintext.len() > self.ciphertext.len() {
+            return Err(Error::CribOutOfRange {
+                crib_length: crib.plaintext.len(),
+                position: crib.position,
+                ciphertext_length: self.ciphertext.len(),
+            });
+        }
+        let mut constraints = vec![];
+        for (j, p) in crib.plaintext.iter().enumerate() {
+            let i = crib.position + j;
+            if *p == self.ciphertext[i] {
+                return Err(Error::SelfEncipherment {
+                    letter: letter(*p),
+                    position: i,
+                });
+            }
+            constraints.push((*p, self.ciphertext[i], i));
+        }
+        Ok(constraints)
+    }
+
+    fn search(
+        &self,
+        mut partial: PartialSteckers,
+        constraints: &[(u8, u8, usize)],
+        solutions: &mut Vec<PartialSteckers>,
+    ) {
+        if solutions.len() >= MAX_CRIB_SOLUTIONS || !self.propagate(&mut partial, constraints) {
+            return;
+        }
+
+        // guess the letter which appears in the crib most often
+        let mut occurrences = [0; LETTERS];
+        for (p, c, _) in constraints {
+            if partial[*p as usize].is_none() {
+                occurrences[*p as usize] += 1;
+            }
+            if partial[*c as usize].is_none() {
+                occurrences[*c as usize] += 1;
+            }
+        }
+        let (guessed, count) = occurrences
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, count)| **count)
+            .unwrap();
+        if *count == 0 {
+            solutions.push(partial);
+            return;
+        }
+
+        for s in 0..LETTERS as u8 {
+            let mut guess = partial;
+            if self.assign(&mut guess, guessed as u8, s) {
+                self.search(guess, constraints, solutions);
+            }
+        }
+    }
+
+    fn propagate(&self, partial: &mut PartialSteckers, constraints: &[(u8, u8, usize)]) -> bool {
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (p, c, i) in constraints {
+                let u = &self.permutations[*i];
+                let implied = match (partial[*p as usize], partial[*c as usize]) {
+                    (Some(sp), None) => Some((*c, u[sp as usize])),
+                    (None, Some(sc)) => Some((*p, u[sc as usize])),
+                    (Some(sp), Some(sc)) => {
+                        if u[sp as usize] != sc {
+                            return false;
+                        }
+                        None
+                    }
+                    (None, None) => None,
+                };
+                if let Some((a, b)) = implied {
+                    if !self.assign(partial, a, b) {
+                        return false;
+                    }
+                    changed = true;
+                }
+            }
+        }
+        true
+    }
+
+    fn assign(&self, partial: &mut PartialSteckers, a: u8, b: u8) -> bool {
+        match (partial[a as usize], partial[b as usize]) {
+            (Some(sa), Some(sb)) => sa == b && sb == a,
+            (None, None) => {
+                let pairs = partial
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, s)| matches!(s, Some(s) if (*s as usize) > *i))
+                    .count();
+                if a != b && pairs >= self.max_pairs {
+                    return false;
+                }
+                partial[a as usize] = Some(b);
+                partial[b as usize] = Some(a);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // improves the plugboard by single changes of pairs as long as the score grows
+    fn climb(&self, steckers: &mut Steckers, fixed: &[bool; LETTERS]) -> f64 {
+        let mut best_score = self.score(steckers);
+        let mut improved = true;
+        while improved {
+            improved = false;
+            for a in 0..LETTERS {
+                for b in a + 1..LETTERS {
+                    if fixed[a] || fixed[b] {
+                        continue;
+                    }
+                    for candidate in self.changes(steckers, a, b) {
+                        let score = self.score(&candidate);
+                        if score > best_score {
+                            best_score = score;
+                            *steckers = candidate;
+                            improved = true;
+                        }
+                    }
+                }
+            }
+        }
+        best_score
+    }
+
+    fn changes(&self, steckers: &Steckers, a: usize, b: usize) -> Vec<Steckers> {
+        if steckers[a] as usize == b {
+            let mut unplugged = *steckers;
+            disconnect(&mut unplugged, a);
+            return vec![unplugged];
+        }
+
+        let (x, y) = (steckers[a] as usize, steckers[b] as usize);
+        let mut changes = vec![];
+
+        let mut plugged = *steckers;
+        disconnect(&mut plugged, a);
+        disconnect(&mut plugged, b);
+        connect(&mut plugged, a, b);
+        changes.push(plugged);
+
+        if x != a && y != b {
+            // a-x, b-y => a-b, x-y and a-y, b-x
+            let mut swapped = plugged;
+            connect(&mut swapped, x, y);
+            changes.push(swapped);
+
+            let mut crossed = *steckers;
+            connect(&mut crossed, a, y);
+            connect(&mut crossed, b, x);
+            changes.push(crossed);
+        }
+
+        changes
+            .into_iter()
+            .filter(|s| pair_count(s) <= self.max_pairs)
+            .collect()
+    }
+
+    fn solution(
+        &self,
+        steckers: &Steckers,
+        partial: &PartialSteckers,
+        crib_solutions: &[PartialSteckers],
+    ) -> PlugboardSolution {
+        let score = self.score(steckers);
+        let mut pairs = vec![];
+        for a in 0..LETTERS {
+            let b = steckers[a] as usize;
+            if b <= a {
+                continue;
+            }
+            let confidence = if partial[a].is_some() {
+                // share of the crib solutions agreeing on the pair
+                let agreeing = crib_solutions
+                    .iter()
+                    .filter(|s| s[a] == Some(b as u8))
+                    .count();
+                agreeing as f64 / crib_solutions.len() as f64
+            } else {
+                // probability of the plaintext with the pair against the one without it
+                let mut unplugged = *steckers;
+                disconnect(&mut unplugged, a);
+                1.0 / (1.0 + (self.score(&unplugged) - score).exp())
+            };
+            pairs.push(PairConfidence {
+                connection: PlugboardConnection {
+                    left: letter(a as u8),
+                    right: letter(b as u8),
+                },
+                confidence,
+            });
+        }
+        PlugboardSolution {
+            pairs,
+            score,
+            plaintext: self.decode(steckers).into_iter().map(letter).collect(),
+        }
+    }
+}
+
+fn identity() -> Steckers {
+    let mut steckers = [0; LETTERS];
+    for (i, s) in steckers.iter_mut().enumerate() {
+        *s = i as u8;
+    }
+    steckers
+}
+
+fn disconnect(steckers: &mut Steckers, a: usize) {
+    let b = steckers[a] as usize;
+    steckers[a] = a as u8;
+    steckers[b] = b as u8;
+}
+
+fn connect(steckers: &mut Steckers, a: usize, b: usize) {
+    steckers[a] = b as u8;
+    steckers[b] = a as u8;
+}
+
+fn pair_count(steckers: &Steckers) -> usize {
+    steckers
+        .iter()
+        .enumerate()
+        .filter(|(i, s)| **s as usize > *i)
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::{RotorOrder, ENIGMA_I_ROTORS};
+    use crate::enigma_builder::EnigmaBuilder;
+    use crate::reflector::Reflector;
+
+    const CORPUS: &str = "\
+        THE CONVOY LEFT THE HARBOUR AT DAWN AND HEADED NORTH ALONG THE COAST. THE WEATHER WAS \
+        CALM BUT THE CAPTAIN ORDERED ALL SHIPS TO KEEP A CLOSE WATCH FOR SUBMARINES. AT NOON \
+        THE ESCORT REPORTED A CONTACT TO THE EAST AND TWO DESTROYERS WERE SENT TO INVESTIGATE. \
+        NOTHING WAS FOUND AND THE SHIPS RETURNED TO THEIR STATIONS BEFORE THE EVENING. DURING \
+        THE NIGHT THE WIND GREW STRONGER AND THE SEA BECAME ROUGH, SO THE CONVOY SLOWED DOWN \
+        TO KEEP THE SMALLER SHIPS TOGETHER. IN THE MORNING THE COMMANDER SENT A REPORT TO THE \
+        HEADQUARTERS WITH THE POSITION OF THE CONVOY AND THE STATE OF THE FUEL ON EVERY SHIP. \
+        THE ANSWER CAME IN THE AFTERNOON WITH NEW ORDERS TO CHANGE THE COURSE AND TO MEET \
+        ANOTHER GROUP OF SHIPS NEAR THE ISLANDS. THE CREWS WERE TIRED BUT THE SPIRIT WAS GOOD \
+        AND EVERYONE KNEW THAT THE WORST PART OF THE JOURNEY WAS STILL AHEAD OF THEM.";
+
+    const PLAINTEXT: &str = "\
+        ALLSHIPSARETOREPORTTHEIRPOSITIONANDTHESTATEOFTHEIRFUELBEFORETHEENDOFTHEWATCHTHE\
+        WEATHERINTHENORTHISGETTINGWORSEANDTHECOMMANDERWANTSTHECONVOYTOSTAYCLOSETOTHECOAST\
+        ANOTHERGROUPOFSHIPSWILLJOINTHEMNEARTHEISLANDSINTHEMORNINGANDTHEESCORTWILLTAKETHE\
+        LEADTHEREISNOCHANGETOTHEORDERSFROMTHEHEADQUARTERSANDTHECREWSHOULDBEREADYATDAWN";
+
+    fn setting() -> RotorSetting {
+        let rotor_order =
+            RotorOrder::new(ENIGMA_I_ROTORS[1], ENIGMA_I_ROTORS[3], ENIGMA_I_ROTORS[0]);
+        RotorSetting::new(rotor_order, Reflector::b, "CXM", "RGE").unwrap()
+    }
+
+    fn encode(pairs: &[&str]) -> String {
+        let [left, middle, right] = setting().rotor_order().rotors();
+        let mut enigma = EnigmaBuilder::init()
+            .rotor_left(left.with_ring_setting('C').unwrap())
+            .rotor_middle(middle.with_ring_setting('X').unwrap())
+            .rotor_right(right.with_ring_setting('M').unwrap())
+            .reflector(Reflector::b())
+            .plugboard_connections(
+                pairs
+                    .iter()
+                    .map(|p| PlugboardConnection::create(p).unwrap())
+                    .collect(),
+            )
+            .build()
+            .unwrap();
+        enigma.change_rotor_setting("RGE").unwrap();
+        enigma.encode_for_current_rotor_setting(PLAINTEXT).unwrap()
+    }
+
+    fn sorted_connections(solution: &PlugboardSolution) -> Vec<String> {
+        let mut connections: Vec<String> = solution
+            .connections()
+            .iter()
+            .map(|c| c.to_string())
+            .collect();
+        connections.sort();
+        connections
+    }
+
+    #[test]
+    fn recovers_plugboard_with_crib() {
+        let pairs = ["AR", "EK", "HT", "LW", "NS", "OQ", "PU", "BY", "CJ", "DZ"];
+        let ciphertext = encode(&pairs);
+        let crib = Crib::new(&PLAINTEXT[..30], 0).unwrap();
+
+        let solver =
+            PlugboardSolver::new(setting(), NgramModel::train(2, CORPUS).unwrap()).max_pairs(10);
+        let solution = solver.solve(&ciphertext, Some(&crib)).unwrap();
+
+        assert_eq!(solution.plaintext, PLAINTEXT);
+        assert_eq!(
+            sorted_connections(&solution),
+            vec!["AR", "BY", "CJ", "DZ", "EK", "HT", "LW", "NS", "OQ", "PU"]
+        );
+        let crib_pair = solution
+            .pairs
+            .iter()
+            .find(|p| p.connection.left() == 'A')
+            .unwrap();
+        assert_eq!(crib_pair.confidence, 1.0);
+    }
+
+    #[test]
+    fn recovers_plugboard_without_crib() {
+        let pairs = ["AR", "EK", "HT", "NS"];
+        let ciphertext = encode(&pairs);
+
+        let solver = PlugboardSolver::new(setting(), NgramModel::train(2, CORPUS).unwrap());
+        let solution = solver.solve(&ciphertext, None).unwrap();
+
+        assert_eq!(sorted_connections(&solution), vec!["AR", "EK", "HT", "NS"]);
+        assert_eq!(solution.plaintext, PLAINTEXT);
+        for pair in &solution.pairs {
+            assert!(pair.confidence > 0.99);
+        }
+    }
+
+    #[test]
+    fn no_pairs_found_for_unplugged_machine() {
+        let ciphertext = encode(&[]);
+        let solver = PlugboardSolver::new(setting(), NgramModel::train(2, CORPUS).unwrap());
+        let solution = solver.solve(&ciphertext, None).unwrap();
+        assert!(solution.pairs.is_empty());
+        assert_eq!(solution.plaintext, PLAINTEXT);
+    }
+
+    #[test]
+    fn error_on_crib_letter_encoded_to_itself() {
+        let solver = PlugboardSolver::new(setting(), NgramModel::german_monograms());
+        let crib = Crib::new("XZY", 1).unwrap();
+        assert_eq!(
+            solver.solve("ABZDE", Some(&crib)),
+            Err(Error::SelfEncipherment {
+                letter: 'Z',
+                position: 2
+            })
+        );
+    }
+
+    #[test]
+    fn error_on_crib_out_of_ciphertext() {
+        let solver = PlugboardSolver::new(setting(), NgramModel::german_monograms());
+        let crib = Crib::new("XYZ", 3).unwrap();
+        assert_eq!(
+            solver.solve("ABCDE", Some(&crib)),
+            Err(Error::CribOutOfRange {
+                crib_length: 3,
+                position: 3,
+                ciphertext_length: 5
+            })
+        );
+    }
+
+    #[test]
+    fn error_on_too_many_pairs() {
+        let solver = PlugboardSolver::new(setting(), NgramModel::german_monograms()).max_pairs(14);
+        assert_eq!(
+            solver.solve("ABCDE", None),
+            Err(Error::TooManyPlugboardPairs {
+                max: 13,
+                actual: 14
+            })
+        );
+    }
+}
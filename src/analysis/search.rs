@@ -0,0 +1,611 @@
+// Brute force search over rotor orders, ring settings and rotor positions
+//
+// Every key is tried on `CompactMachine` - the machine reduced to lookup tables, which is cheap
+// to copy and does not allocate while decoding. The keyspace is split into chunks of a single
+// rotor order, ring setting and left rotor position (676 keys each), which are picked up by the
+// worker threads one by one.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::analysis::{index, letter, ReflectorFactory, RotorOrder, RotorSetting};
+use crate::error::Error;
+use crate::plugboard::{Plugboard, PlugboardConnection};
+
+const LETTERS: usize = 26;
+const POSITIONS: u64 = 26 * 26 * 26;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CompactMachine {
+    // left, middle, right
+    forward: [[u8; LETTERS]; 3],
+    backward: [[u8; LETTERS]; 3],
+    turnovers: [u32; 3],
+    rings: [u8; 3],
+    positions: [u8; 3],
+    reflector: [u8; LETTERS],
+    plugboard: [u8; LETTERS],
+}
+
+impl CompactMachine {
+    pub fn new(rotor_order: RotorOrder, reflector: ReflectorFactory) -> CompactMachine {
+        let mut machine = CompactMachine {
+            forward: [[0; LETTERS]; 3],
+            backward: [[0; LETTERS]; 3],
+            turnovers: [0; 3],
+            rings: [0; 3],
+            positions: [0; 3],
+            reflector: reflector().wiring(),
+            plugboard: [0; LETTERS],
+        };
+        for (i, rotor) in rotor_order.rotors().iter().enumerate() {
+            machine.forward[i] = rotor.wiring();
+            for (right, left) in machine.forward[i].iter().enumerate() {
+                machine.backward[i][*left as usize] = right as u8;
+            }
+            for turnover in rotor.turnover_offsets() {
+                machine.turnovers[i] |= 1 << turnover;
+            }
+        }
+        for (i, p) in machine.plugboard.iter_mut().enumerate() {
+            *p = i as u8;
+        }
+        machine
+    }
+
+    pub fn from_setting(setting: &RotorSetting) -> CompactMachine {
+        let mut machine = CompactMachine::new(setting.rotor_order(), setting.reflector);
+        machine.rings = letters_to_indexes(setting.ring_setting());
+        machine.positions = letters_to_indexes(setting.position());
+        machine
+    }
+
+    /// Fails the same way a strict `Plugboard` does, e.g. for a letter plugged twice.
+    pub fn plugboard(mut self, connections: &[PlugboardConnection]) -> Result<Self, Error> {
+        let mut plugboard = Plugboard::identity().strict();
+        for connection in connections {
+            plugboard.connect(connection.left(), connection.right())?;
+        }
+        for (i, p) in self.plugboard.iter_mut().enumerate() {
+            *p = plugboard.encode_from_right(i as u8);
+        }
+        Ok(self)
+    }
+
+    /// Rings and positions as letter indexes (0 for 'A') for left, middle and right rotor.
+    pub fn set_rings(&mut self, rings: [u8; 3]) {
+        self.rings = rings;
+    }
+
+    pub fn set_positions(&mut self, positions: [u8; 3]) {
+        self.positions = positions;
+    }
+
+    pub fn positions(&self) -> [u8; 3] {
+        self.positions
+    }
+
+    pub fn step(&mut self) {
+        let right_at_turnover = self.turnovers[2] & (1 << self.positions[2]) != 0;
+        let middle_at_turnover = self.turnovers[1] & (1 << self.positions[1]) != 0;
+        self.positions[2] = (self.positions[2] + 1) % LETTERS as u8;
+        if right_at_turnover || middle_at_turnover {
+            self.positions[1] = (self.positions[1] + 1) % LETTERS as u8;
+            if middle_at_turnover {
+                self.positions[0] = (self.positions[0] + 1) % LETTERS as u8;
+            }
+        }
+    }
+
+    /// Steps the rotors and encodes a single letter index.
+    pub fn press(&mut self, letter: u8) -> u8 {
+        self.step();
+
+        let mut shifts = [0; 3];
+        for (i, shift) in shifts.iter_mut().enumerate() {
+            *shift = (self.positions[i] + LETTERS as u8 - self.rings[i]) as usize;
+        }
+
+        let mut c = self.plugboard[letter as usize] as usize;
+        for i in (0..3).rev() {
+            c = (self.forward[i][(c + shifts[i]) % LETTERS] as usize + 2 * LETTERS - shifts[i])
+                % LETTERS;
+        }
+        c = self.reflector[c] as usize;
+        for (backward, shift) in self.backward.iter().zip(shifts.iter()) {
+            c = (backward[(c + shift) % LETTERS] as usize + 2 * LETTERS - shift) % LETTERS;
+        }
+        self.plugboard[c]
+    }
+
+    /// Encodes (or decodes) all the letters into `output`, which is cleared first.
+    pub fn encode(&mut self, text: &[u8], output: &mut Vec<u8>) {
+        output.clear();
+        for c in text {
+            output.push(self.press(*c));
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RingSettings {
+    Fixed([u8; 3]),
+    // ring of the left rotor only shifts its position, so it does not need to be searched
+    MiddleAndRight,
+    All,
+}
+
+impl RingSettings {
+    fn all(&self) -> Vec<[u8; 3]> {
+        let range = |searched: bool| if searched { 0..LETTERS as u8 } else { 0..1 };
+        match *self {
+            RingSettings::Fixed(rings) => vec![rings],
+            RingSettings::MiddleAndRight | RingSettings::All => {
+                let mut rings = vec![];
+                for l in range(*self == RingSettings::All) {
+                    for m in range(true) {
+                        for r in range(true) {
+                            rings.push([l, m, r]);
+                        }
+                    }
+                }
+                rings
+            }
+        }
+    }
+}
+
+pub struct SearchSpace {
+    rotor_orders: Vec<RotorOrder>,
+    reflector: ReflectorFactory,
+    ring_settings: RingSettings,
+    plugboard: Vec<PlugboardConnection>,
+}
+
+impl SearchSpace {
+    /// All positions of the given rotor orders, with rings set to 'A' and no plugboard.
+    pub fn new(rotor_orders: Vec<RotorOrder>, reflector: ReflectorFactory) -> SearchSpace {
+        SearchSpace {
+            rotor_orders,
+            reflector,
+            ring_settings: RingSettings::Fixed([0; 3]),
+            plugboard: vec![],
+        }
+    }
+
+    pub fn ring_settings(mut self, ring_settings: RingSettings) -> Self {
+        self.ring_settings = ring_settings;
+        self
+    }
+
+    pub fn plugboard_connections(mut self, plugboard: Vec<PlugboardConnection>) -> Self {
+        self.plugboard = plugboard;
+        self
+    }
+
+    pub fn size(&self) -> u64 {
+        self.rotor_orders.len() as u64 * self.ring_settings.all().len() as u64 * POSITIONS
+    }
+
+    fn chunks(&self) -> Result<Vec<Chunk>, Error> {
+        let mut chunks = vec![];
+        let ring_settings = self.ring_settings.all();
+        for (order, rotor_order) in self.rotor_orders.iter().enumerate() {
+            let machine =
+                CompactMachine::new(*rotor_order, self.reflector).plugboard(&self.plugboard)?;
+            for rings in &ring_settings {
+                for left in 0..LETTERS as u8 {
+                    let mut machine = machine;
+                    machine.set_rings(*rings);
+                    chunks.push(Chunk {
+                        order,
+                        machine,
+                        left,
+                    });
+                }
+            }
+        }
+        Ok(chunks)
+    }
+}
+
+// rotor order (index in the search space), rings and positions
+type Key = (usize, [u8; 3], [u8; 3]);
+
+struct Chunk {
+    order: usize,
+    machine: CompactMachine,
+    left: u8,
+}
+
+#[derive(Clone, Debug)]
+pub struct Candidate {
+    pub rotor_order: RotorOrder,
+    pub reflector: ReflectorFactory,
+    pub ring_setting: String,
+    pub position: String,
+    pub score: f64,
+}
+
+impl Candidate {
+    /// Setting which can be passed to the plugboard solver.
+    pub fn rotor_setting(&self) -> RotorSetting {
+        RotorSetting::new(
+            self.rotor_order,
+            self.reflector,
+            &self.ring_setting,
+            &self.position,
+        )
+        .unwrap()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Progress {
+    pub tested: u64,
+    pub total: u64,
+}
+
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+pub struct SearchResult {
+    // best first
+    pub candidates: Vec<Candidate>,
+    pub tested: u64,
+    pub cancelled: bool,
+}
+
+type ProgressCallback<'a> = Box<dyn Fn(Progress) + Sync + 'a>;
+
+pub struct SearchEngine<'a> {
+    space: SearchSpace,
+    threads: usize,
+    keep_best: usize,
+    on_progress: Option<ProgressCallback<'a>>,
+    cancellation: CancellationToken,
+}
+
+impl<'a> SearchEngine<'a> {
+    pub fn new(space: SearchSpace) -> SearchEngine<'a> {
+        SearchEngine {
+            space,
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            keep_best: 10,
+            on_progress: None,
+            cancellation: CancellationToken::new(),
+        }
+    }
+
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    pub fn keep_best(mut self, keep_best: usize) -> Self {
+        self.keep_best = keep_best;
+        self
+    }
+
+    /// Called (from the worker threads) every time a chunk of 676 keys is tested.
+    pub fn on_progress<F: Fn(Progress) + Sync + 'a>(mut self, on_progress: F) -> Self {
+        self.on_progress = Some(Box::new(on_progress));
+        self
+    }
+
+    pub fn cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = cancellation;
+        self
+    }
+
+    /// Decodes `ciphertext` with every key of the search space and keeps the keys for which
+    /// `score` returned the highest values.
+    pub fn run<F>(&self, ciphertext: &str, score: F) -> Result<SearchResult, Error>
+    where
+        F: Fn(&[u8]) -> f64 + Sync,
+    {
+        Error::check_alphabet(ciphertext)?;
+        let ciphertext: Vec<u8> = ciphertext.chars().map(index).collect();
+
+        let chunks = self.space.chunks()?;
+        let next_chunk = AtomicUsize::new(0);
+        let tested = AtomicU64::new(0);
+        let total = self.space.size();
+        let best: Mutex<Vec<(f64, Key)>> = Mutex::new(vec![]);
+
+        thread::scope(|scope| {
+            for _ in 0..self.threads {
+                scope.spawn(|| {
+                    let mut plaintext = Vec::with_capacity(ciphertext.len());
+                    let mut local_best = vec![];
+                    loop {
+                        if self.cancellation.is_cancelled() {
+                            break;
+                        }
+                        let chunk = match chunks.get(next_chunk.fetch_add(1, Ordering::Relaxed)) {
+                            Some(chunk) => chunk,
+                            None => break,
+                        };
+                        for middle in 0..LETTERS as u8 {
+                            for right in 0..LETTERS as u8 {
+                                let mut machine = chunk.machine;
+                                machine.set_positions([chunk.left, middle, right]);
+                                machine.encode(&ciphertext, &mut plaintext);
+                                let s = score(&plaintext);
+                                keep(
+                                    &mut local_best,
+                                    (
+                                        s,
+                                        (
+                                            chunk.order,
+                                            chunk.machine.rings,
+                                            [chunk.left, middle, right],
+                                        ),
+                                    ),
+                                    self.keep_best,
+                                );
+                            }
+                        }
+                        let tested = tested
+                            .fetch_add(LETTERS as u64 * LETTERS as u64, Ordering::Relaxed)
+                            + LETTERS as u64 * LETTERS as u64;
+                        if let Some(on_progress) = &self.on_progress {
+                            on_progress(Progress { tested, total });
+                        }
+                    }
+                    let mut best = best.lock().unwrap();
+                    for candidate in local_best {
+                        keep(&mut best, candidate, self.keep_best);
+                    }
+                });
+            }
+        });
+
+        let candidates = best
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .map(|(score, (order, rings, positions))| Candidate {
+                rotor_order: self.space.rotor_orders[order],
+                reflector: self.space.reflector,
+                ring_setting: rings.iter().map(|i| letter(*i)).collect(),
+                position: positions.iter().map(|i| letter(*i)).collect(),
+                score,
+            })
+            .collect();
+        Ok(SearchResult {
+            candidates,
+            tested: tested.into_inner(),
+            cancelled: self.cancellation.is_cancelled(),
+        })
+    }
+}
+
+// keeps `limit` highest scores sorted from the best one
+fn keep<T>(best: &mut Vec<(f64, T)>, candidate: (f64, T), limit: usize)
+where
+    T: Copy,
+{
+    if best.len() == limit && best.last().is_none_or(|worst| worst.0 >= candidate.0) {
+        return;
+    }
+    let i = best
+        .iter()
+        .position(|b| b.0 < candidate.0)
+        .unwrap_or(best.len());
+    best.insert(i, candidate);
+    best.truncate(limit);
+}
+
+fn letters_to_indexes(letters: &str) -> [u8; 3] {
+    let mut indexes = [0; 3];
+    for (i, c) in letters.chars().enumerate() {
+        indexes[i] = index(c);
+    }
+    indexes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::stats::index_of_coincidence;
+    use crate::analysis::ENIGMA_I_ROTORS;
+    use crate::enigma_builder::EnigmaBuilder;
+    use crate::reflector::Reflector;
+
+    const PLAINTEXT: &str = "\
+        DIEERSTEFLOTTILLEMELDETFEINDLICHEZERSTOERERINPLANQUADRATFUENFDREIDIEBOOTESOLLEN\
+        SOFORTNACHWESTENABLAUFENUNDWEITEREBEFEHLEABWARTENWETTERGUTSICHTZEHNSEEMEILEN";
+
+    fn rotor_order() -> RotorOrder {
+        RotorOrder::new(ENIGMA_I_ROTORS[4], ENIGMA_I_ROTORS[2], ENIGMA_I_ROTORS[0])
+    }
+
+    fn encode(rings: &str, position: &str, plaintext: &str) -> String {
+        let [left, middle, right] = rotor_order().rotors();
+        let mut rings = rings.chars();
+        let mut enigma = EnigmaBuilder::init()
+            .rotor_left(left.with_ring_setting(rings.next().unwrap()).unwrap())
+            .rotor_middle(middle.with_ring_setting(rings.next().unwrap()).unwrap())
+            .rotor_right(right.with_ring_setting(rings.next().unwrap()).unwrap())
+            .reflector(Reflector::b())
+            .plugboard_connections(vec![
+                PlugboardConnection::create("AQ").unwrap(),
+                PlugboardConnection::create("EZ").unwrap(),
+            ])
+            .build()
+            .unwrap();
+        enigma.change_rotor_setting(position).unwrap();
+        enigma.encode_for_current_rotor_setting(plaintext).unwrap()
+    }
+
+    #[test]
+    fn compact_machine_encodes_same_as_enigma() {
+        for (rings, position) in [("AAA", "AAA"), ("QBZ", "ADU"), ("HGF", "KEV")].iter() {
+            let setting = RotorSetting::new(rotor_order(), Reflector::b, rings, position).unwrap();
+            let mut machine = CompactMachine::from_setting(&setting)
+                .plugboard(&[
+                    PlugboardConnection::create("AQ").unwrap(),
+                    PlugboardConnection::create("EZ").unwrap(),
+                ])
+                .unwrap();
+            let plaintext: Vec<u8> = PLAINTEXT.chars().map(index).collect();
+            let mut encoded = vec![];
+            machine.encode(&plaintext, &mut encoded);
+            let encoded: String = encoded.into_iter().map(letter).collect();
+
+            assert_eq!(encoded, encode(rings, position, PLAINTEXT));
+        }
+    }
+
+    #[test]
+    fn error_on_conflicting_plugboard_connections() {
+        let connections = [
+            PlugboardConnection::create("AB").unwrap(),
+            PlugboardConnection::create("AC").unwrap(),
+        ];
+        let conflict = Error::LetterAlreadyPlugged {
+            letter: 'A',
+            connected_to: 'B',
+        };
+        assert_eq!(
+            CompactMachine::new(rotor_order(), Reflector::b)
+                .plugboard(&connections)
+                .err(),
+            Some(conflict.clone())
+        );
+        let space = SearchSpace::new(vec![rotor_order()], Reflector::b)
+            .plugboard_connections(connections.to_vec());
+        assert_eq!(
+            SearchEngine::new(space).run("ABC", |_| 0.0).err(),
+            Some(conflict)
+        );
+    }
+
+    #[test]
+    fn finds_rotor_positions() {
+        let ciphertext = encode("AAA", "QEX", PLAINTEXT);
+        let space =
+            SearchSpace::new(vec![rotor_order()], Reflector::b).plugboard_connections(vec![
+                PlugboardConnection::create("AQ").unwrap(),
+                PlugboardConnection::create("EZ").unwrap(),
+            ]);
+        assert_eq!(space.size(), 17576);
+
+        let result = SearchEngine::new(space)
+            .threads(4)
+            .keep_best(3)
+            .run(&ciphertext, index_of_coincidence)
+            .unwrap();
+
+        assert_eq!(result.tested, 17576);
+        assert!(!result.cancelled);
+        assert_eq!(result.candidates.len(), 3);
+        assert_eq!(result.candidates[0].position, "QEX");
+        assert_eq!(result.candidates[0].ring_setting, "AAA");
+        assert_eq!(result.candidates[0].rotor_order.to_string(), "V III I");
+        assert!(result.candidates[0].score > result.candidates[1].score);
+    }
+
+    #[test]
+    fn searches_rotor_orders_and_rings() {
+        let crib: Vec<u8> = PLAINTEXT[..20].chars().map(index).collect();
+        let ciphertext = encode("AAF", "BCX", &PLAINTEXT[..20]);
+        let wheels = [ENIGMA_I_ROTORS[0], ENIGMA_I_ROTORS[2], ENIGMA_I_ROTORS[4]];
+        let space = SearchSpace::new(RotorOrder::all(&wheels), Reflector::b)
+            .ring_settings(RingSettings::Fixed([0, 0, 5]))
+            .plugboard_connections(vec![
+                PlugboardConnection::create("AQ").unwrap(),
+                PlugboardConnection::create("EZ").unwrap(),
+            ]);
+        assert_eq!(space.size(), 6 * 17576);
+
+        let result = SearchEngine::new(space)
+            .run(&ciphertext, |plaintext| {
+                plaintext
+                    .iter()
+                    .zip(crib.iter())
+                    .filter(|(a, b)| a == b)
+                    .count() as f64
+            })
+            .unwrap();
+        let best = &result.candidates[0];
+        assert_eq!(best.score, 20.0);
+        assert_eq!(best.rotor_order.to_string(), "V III I");
+        assert_eq!(best.position, "BCX");
+        assert_eq!(best.rotor_setting().ring_setting(), "AAF");
+    }
+
+    #[test]
+    fn reports_progress() {
+        let ciphertext = encode("AAA", "QEX", &PLAINTEXT[..40]);
+        let calls = AtomicU64::new(0);
+        let last = AtomicU64::new(0);
+        let result = SearchEngine::new(SearchSpace::new(vec![rotor_order()], Reflector::b))
+            .threads(2)
+            .on_progress(|progress| {
+                assert_eq!(progress.total, 17576);
+                calls.fetch_add(1, Ordering::Relaxed);
+                last.fetch_max(progress.tested, Ordering::Relaxed);
+            })
+            .run(&ciphertext, index_of_coincidence)
+            .unwrap();
+        assert_eq!(result.tested, 17576);
+        assert_eq!(calls.into_inner(), 26);
+        assert_eq!(last.into_inner(), 17576);
+    }
+
+    #[test]
+    fn stops_when_cancelled() {
+        let token = CancellationToken::new();
+        token.cancel();
+        let result = SearchEngine::new(SearchSpace::new(vec![rotor_order()], Reflector::b))
+            .cancellation(token)
+            .run("ABCDEF", index_of_coincidence)
+            .unwrap();
+        assert!(result.cancelled);
+        assert_eq!(result.tested, 0);
+        assert!(result.candidates.is_empty());
+    }
+
+    #[test]
+    fn cancelling_from_progress_callback() {
+        let token = CancellationToken::new();
+        let callback_token = token.clone();
+        let result = SearchEngine::new(SearchSpace::new(
+            RotorOrder::all(&ENIGMA_I_ROTORS),
+            Reflector::b,
+        ))
+        .threads(1)
+        .cancellation(token)
+        .on_progress(move |_| callback_token.cancel())
+        .run("ABCDEF", index_of_coincidence)
+        .unwrap();
+        assert!(result.cancelled);
+        assert_eq!(result.tested, 676);
+    }
+
+    #[test]
+    fn ring_settings_to_search() {
+        assert_eq!(RingSettings::Fixed([1, 2, 3]).all(), vec![[1, 2, 3]]);
+        assert_eq!(RingSettings::MiddleAndRight.all().len(), 676);
+        assert_eq!(RingSettings::All.all().len(), 17576);
+    }
+}
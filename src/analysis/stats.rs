@@ -0,0 +1,488 @@
+// Statistics of a ciphertext, used to triage intercepts before any attack is run
+//
+// Enigma never encrypts a letter to itself, so a crib can only be placed where none of its
+// letters is opposite the same ciphertext letter, and known plaintext which does match the
+// ciphertext somewhere proves that the text was not produced by an Enigma.
+
+use std::collections::HashMap;
+use std::fmt::Display;
+
+use crate::analysis::plugboard_solver::Crib;
+use crate::analysis::{index, letter};
+use crate::enigma::SUPPORTED_ALPHABET;
+use crate::error::Error;
+
+const LETTERS: usize = 26;
+// coincidences are counted for shifts up to that, or half of the text if it is shorter
+const MAX_SHIFT: usize = 100;
+const REPORTED_SHIFTS: usize = 5;
+/// Longest repeat `CiphertextStatistics::repeated_ngrams` looks for, so that long runs of
+/// the same letters do not take time growing with the cube of the text length.
+pub const MAX_REPEAT_LENGTH: usize = 32;
+
+pub const GERMAN_INDEX_OF_COINCIDENCE: f64 = 0.0762;
+pub const ENGLISH_INDEX_OF_COINCIDENCE: f64 = 0.0667;
+pub const RANDOM_INDEX_OF_COINCIDENCE: f64 = 1.0 / LETTERS as f64;
+
+/// Index of coincidence of the letters - higher for a language than for random letters.
+pub fn index_of_coincidence(text: &[u8]) -> f64 {
+    if text.len() < 2 {
+        return 0.0;
+    }
+    let mut counts = [0u64; LETTERS];
+    for c in text {
+        counts[*c as usize] += 1;
+    }
+    let n = text.len() as u64;
+    counts.iter().map(|c| c * c.saturating_sub(1)).sum::<u64>() as f64 / (n * (n - 1)) as f64
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RepeatedNgram {
+    pub ngram: String,
+    pub positions: Vec<usize>,
+}
+
+impl RepeatedNgram {
+    /// Distances between the following occurrences.
+    pub fn distances(&self) -> Vec<usize> {
+        self.positions.windows(2).map(|p| p[1] - p[0]).collect()
+    }
+}
+
+impl Display for RepeatedNgram {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let join = |numbers: &[usize]| {
+            numbers
+                .iter()
+                .map(|n| n.to_string())
+                .collect::<Vec<String>>()
+                .join(", ")
+        };
+        write!(
+            f,
+            "{} at {} (distances: {})",
+            self.ngram,
+            join(&self.positions),
+            join(&self.distances())
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CribReport {
+    pub crib: String,
+    // positions in the ciphertext at which the crib can be placed
+    pub possible_positions: Vec<usize>,
+    // set when the crib is known plaintext at that position
+    pub position: Option<usize>,
+    // positions at which the known plaintext letter is opposite the same ciphertext letter
+    pub self_encipherments: Vec<usize>,
+}
+
+impl CribReport {
+    /// False if the known plaintext proves the ciphertext was not produced by an Enigma.
+    pub fn possible_enigma_ciphertext(&self) -> bool {
+        self.self_encipherments.is_empty()
+    }
+}
+
+impl Display for CribReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let positions: Vec<String> = self
+            .possible_positions
+            .iter()
+            .map(|p| p.to_string())
+            .collect();
+        writeln!(
+            f,
+            "Crib {} can be placed at {} positions: {}",
+            self.crib,
+            positions.len(),
+            positions.join(", ")
+        )?;
+        if let Some(position) = self.position {
+            if self.possible_enigma_ciphertext() {
+                writeln!(
+                    f,
+                    "No letter of the crib at position {} is encrypted to itself",
+                    position
+                )?;
+            } else {
+                let positions: Vec<String> = self
+                    .self_encipherments
+                    .iter()
+                    .map(|p| p.to_string())
+                    .collect();
+                writeln!(
+                    f,
+                    "NOT AN ENIGMA CIPHERTEXT: crib at position {} is encrypted to itself at {}",
+                    position,
+                    positions.join(", ")
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+pub struct Report {
+    pub length: usize,
+    pub counts: [usize; LETTERS],
+    pub index_of_coincidence: f64,
+    pub repeated_ngrams: Vec<RepeatedNgram>,
+    // coincidences for each shift (starting from 1)
+    pub coincidences: Vec<usize>,
+    pub crib: Option<CribReport>,
+}
+
+impl Report {
+    /// Shifts with the highest number of coincidences, as (shift, coincidences), best first.
+    pub fn best_shifts(&self, count: usize) -> Vec<(usize, usize)> {
+        let mut shifts: Vec<(usize, usize)> = self
+            .coincidences
+            .iter()
+            .enumerate()
+            .map(|(i, c)| (i + 1, *c))
+            .collect();
+        shifts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        shifts.truncate(count);
+        shifts
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Length: {}", self.length)?;
+        writeln!(f, "Letter frequencies:")?;
+        for (i, count) in self.counts.iter().enumerate() {
+            writeln!(
+                f,
+                "  {} {:>4} {:>5.1}%",
+                letter(i as u8),
+                count,
+                100.0 * *count as f64 / self.length.max(1) as f64
+            )?;
+        }
+        writeln!(
+            f,
+            "Index of coincidence: {:.4} (German: {:.4}, English: {:.4}, random: {:.4})",
+            self.index_of_coincidence,
+            GERMAN_INDEX_OF_COINCIDENCE,
+            ENGLISH_INDEX_OF_COINCIDENCE,
+            RANDOM_INDEX_OF_COINCIDENCE
+        )?;
+        writeln!(f, "Repeated n-grams: {}", self.repeated_ngrams.len())?;
+        for repeat in &self.repeated_ngrams {
+            writeln!(f, "  {}", repeat)?;
+        }
+        writeln!(f, "Shifts with most coincidences:")?;
+        for (shift, coincidences) in self.best_shifts(REPORTED_SHIFTS) {
+            let compared = self.length - shift;
+            writeln!(
+                f,
+                "  {:>3}: {} of {} ({:.1}%)",
+                shift,
+                coincidences,
+                compared,
+                100.0 * coincidences as f64 / compared as f64
+            )?;
+        }
+        if let Some(crib) = &self.crib {
+            write!(f, "{}", crib)?;
+        }
+        Ok(())
+    }
+}
+
+pub struct CiphertextStatistics {
+    text: Vec<u8>,
+}
+
+impl CiphertextStatistics {
+    /// Whitespace is skipped, so the ciphertext can be passed in groups of five letters.
+    pub fn new(ciphertext: &str) -> Result<CiphertextStatistics, Error> {
+        let mut text = vec![];
+        for (i, c) in ciphertext
+            .chars()
+            .enumerate()
+            .filter(|(_, c)| !c.is_whitespace())
+        {
+            if !SUPPORTED_ALPHABET.contains(c) {
+                return Err(Error::UnsupportedCharacter {
+                    character: c,
+                    index: i,
+                });
+            }
+            text.push(index(c));
+        }
+        Ok(CiphertextStatistics { text })
+    }
+
+    pub fn len(&self) -> usize {
+        self.text.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    pub fn counts(&self) -> [usize; LETTERS] {
+        let mut counts = [0; LETTERS];
+        for c in &self.text {
+            counts[*c as usize] += 1;
+        }
+        counts
+    }
+
+    pub fn index_of_coincidence(&self) -> f64 {
+        index_of_coincidence(&self.text)
+    }
+
+    /// N-grams of at least `min_length` letters found more than once, ordered by position.
+    /// Repeats which are only a part of a longer repeat are not listed, repeats longer than
+    /// `MAX_REPEAT_LENGTH` letters are listed by their beginning.
+    pub fn repeated_ngrams(&self, min_length: usize) -> Vec<RepeatedNgram> {
+        let min_length = min_length.max(1);
+        let max_length = self.text.len().min(min_length.max(MAX_REPEAT_LENGTH));
+        // positions of the repeated n-grams by their length
+        let mut by_length: Vec<Vec<Vec<usize>>> = vec![];
+        for n in min_length..=max_length {
+            let mut positions: HashMap<&[u8], Vec<usize>> = HashMap::new();
+            for (i, ngram) in self.text.windows(n).enumerate() {
+                positions.entry(ngram).or_default().push(i);
+            }
+            let repeated: Vec<Vec<usize>> =
+                positions.into_values().filter(|p| p.len() > 1).collect();
+            if repeated.is_empty() {
+                break;
+            }
+            by_length.push(repeated);
+        }
+
+        let mut repeats = vec![];
+        for (i, repeated) in by_length.iter().enumerate() {
+            let n = min_length + i;
+            // the longest n-grams looked for can only continue each other
+            let longer = by_length
+                .get(i + 1)
+                .or_else(|| Some(repeated).filter(|_| n == max_length));
+            for positions in repeated {
+                let extended = longer.is_some_and(|longer| {
+                    longer.iter().any(|l| {
+                        l.len() == positions.len()
+                            && ((l == positions && n < max_length)
+                                || l.iter().zip(positions).all(|(l, p)| l + 1 == *p))
+                    })
+                });
+                if !extended {
+                    repeats.push(RepeatedNgram {
+                        ngram: self.text[positions[0]..positions[0] + n]
+                            .iter()
+                            .map(|c| letter(*c))
+                            .collect(),
+                        positions: positions.clone(),
+                    });
+                }
+            }
+        }
+        repeats.sort_by(|a, b| a.positions.cmp(&b.positions));
+        repeats
+    }
+
+    /// Number of positions at which the text has the same letter as the text shifted by `shift`.
+    pub fn coincidences(&self, shift: usize) -> usize {
+        if shift >= self.text.len() {
+            return 0;
+        }
+        self.text
+            .iter()
+            .zip(self.text[shift..].iter())
+            .filter(|(a, b)| a == b)
+            .count()
+    }
+
+    /// Positions at which none of the crib letters is opposite the same ciphertext letter.
+    pub fn crib_positions(&self, crib: &str) -> Result<Vec<usize>, Error> {
+        let crib = Crib::new(crib, 0)?;
+        if crib.plaintext.is_empty() || crib.plaintext.len() > self.text.len() {
+            return Ok(vec![]);
+        }
+        Ok((0..=self.text.len() - crib.plaintext.len())
+            .filter(|p| {
+                crib.plaintext
+                    .iter()
+                    .zip(self.text[*p..].iter())
+                    .all(|(p, c)| p != c)
+            })
+            .collect())
+    }
+
+    /// Ciphertext positions at which the known plaintext letter is the same as the ciphertext
+    /// one - any of them means that the text can not be Enigma output.
+    pub fn self_encipherments(&self, crib: &Crib) -> Vec<usize> {
+        crib.plaintext
+            .iter()
+            .enumerate()
+            .map(|(i, p)| (crib.position + i, p))
+            .filter(|(i, p)| self.text.get(*i) == Some(p))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    pub fn report(&self) -> Report {
+        let max_shift = MAX_SHIFT.min(self.text.len() / 2);
+        Report {
+            length: self.text.len(),
+            counts: self.counts(),
+            index_of_coincidence: self.index_of_coincidence(),
+            repeated_ngrams: self.repeated_ngrams(3),
+            coincidences: (1..=max_shift).map(|s| self.coincidences(s)).collect(),
+            crib: None,
+        }
+    }
+
+    /// Report with the positions the crib fits at; if `position` is given, the crib is also
+    /// checked as known plaintext at that position.
+    pub fn report_with_crib(&self, crib: &str, position: Option<usize>) -> Result<Report, Error> {
+        let possible_positions = self.crib_positions(crib)?;
+        let self_encipherments = match position {
+            Some(position) => self.self_encipherments(&Crib::new(crib, position)?),
+            None => vec![],
+        };
+        let mut report = self.report();
+        report.crib = Some(CribReport {
+            crib: crib.into(),
+            possible_positions,
+            position,
+            self_encipherments,
+        });
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn index_of_coincidence_of_text() {
+        assert_eq!(index_of_coincidence(&[0, 0, 0, 0]), 1.0);
+        assert_eq!(index_of_coincidence(&[0, 1, 2, 3]), 0.0);
+        assert_eq!(index_of_coincidence(&[0]), 0.0);
+        // ordered pairs: 2 of A and 2 of B out of 12
+        let stats = CiphertextStatistics::new("AB BA").unwrap();
+        assert!((stats.index_of_coincidence() - 4.0 / 12.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn letter_counts() {
+        let stats = CiphertextStatistics::new("QWEQQ ZZA").unwrap();
+        assert_eq!(stats.len(), 8);
+        let counts = stats.counts();
+        assert_eq!(counts[index('Q') as usize], 3);
+        assert_eq!(counts[index('Z') as usize], 2);
+        assert_eq!(counts[index('B') as usize], 0);
+    }
+
+    #[test]
+    fn repeated_ngrams_with_distances() {
+        //                                    0         10        20
+        let stats = CiphertextStatistics::new("ABCDXYZABCDQQQXYZRABC").unwrap();
+        assert_eq!(
+            stats.repeated_ngrams(3),
+            vec![
+                RepeatedNgram {
+                    ngram: "ABCD".into(),
+                    positions: vec![0, 7],
+                },
+                RepeatedNgram {
+                    ngram: "ABC".into(),
+                    positions: vec![0, 7, 18],
+                },
+                RepeatedNgram {
+                    ngram: "XYZ".into(),
+                    positions: vec![4, 14],
+                },
+            ]
+        );
+        assert_eq!(stats.repeated_ngrams(3)[1].distances(), vec![7, 11]);
+    }
+
+    #[test]
+    fn long_repeats_are_cut() {
+        let stats = CiphertextStatistics::new(&"A".repeat(5000)).unwrap();
+        let repeats = stats.repeated_ngrams(3);
+        // each length repeats at a different number of positions
+        assert_eq!(repeats.len(), MAX_REPEAT_LENGTH - 3 + 1);
+        assert!(repeats.iter().all(|r| r.ngram.len() <= MAX_REPEAT_LENGTH));
+
+        let repeat = "ABCDEFGHIJKLMNOPQRSTUVWXYZZYXWVUTSRQPONM";
+        let stats = CiphertextStatistics::new(&format!("{}XX{}", repeat, repeat)).unwrap();
+        assert_eq!(
+            stats.repeated_ngrams(3),
+            vec![RepeatedNgram {
+                ngram: repeat[..MAX_REPEAT_LENGTH].into(),
+                positions: vec![0, 42],
+            }]
+        );
+    }
+
+    #[test]
+    fn coincidences_for_shift() {
+        let stats = CiphertextStatistics::new("ABCABCABD").unwrap();
+        assert_eq!(stats.coincidences(3), 5);
+        assert_eq!(stats.coincidences(1), 0);
+        assert_eq!(stats.coincidences(20), 0);
+        assert_eq!(stats.report().best_shifts(1), vec![(3, 5)]);
+    }
+
+    #[test]
+    fn crib_can_not_be_placed_over_the_same_letters() {
+        let stats = CiphertextStatistics::new("WETTERXWQ").unwrap();
+        assert_eq!(stats.crib_positions("WETTER").unwrap(), vec![2]);
+        assert_eq!(
+            stats.crib_positions("WETTERBERICHT").unwrap(),
+            Vec::<usize>::new()
+        );
+    }
+
+    #[test]
+    fn known_plaintext_encrypted_to_itself() {
+        let stats = CiphertextStatistics::new("QWERTZUIOP").unwrap();
+        assert_eq!(
+            stats.self_encipherments(&Crib::new("AERB", 1).unwrap()),
+            vec![2, 3]
+        );
+
+        let report = stats.report_with_crib("AERB", Some(1)).unwrap();
+        let crib = report.crib.unwrap();
+        assert!(!crib.possible_enigma_ciphertext());
+        assert_eq!(crib.self_encipherments, vec![2, 3]);
+        assert!(crib.to_string().contains(
+            "NOT AN ENIGMA CIPHERTEXT: crib at position 1 is encrypted to itself at 2, 3"
+        ));
+
+        let report = stats.report_with_crib("AERB", Some(5)).unwrap();
+        assert!(report.crib.unwrap().possible_enigma_ciphertext());
+    }
+
+    #[test]
+    fn error_on_unsupported_character() {
+        assert_eq!(
+            CiphertextStatistics::new("ABc").err(),
+            Some(Error::UnsupportedCharacter {
+                character: 'c',
+                index: 2
+            })
+        );
+        let stats = CiphertextStatistics::new("ABC").unwrap();
+        assert_eq!(
+            stats.crib_positions("A1").err(),
+            Some(Error::UnsupportedCharacter {
+                character: '1',
+                index: 1
+            })
+        );
+    }
+}
@@ -0,0 +1,236 @@
+// Sequence of rotor positions, which depends only on the notches of the rotors and the way
+// they step - wirings and ring settings do not matter. Questions like "did the middle rotor
+// turn over inside the crib" are answered by stepping through all 17576 positions, which is
+// fast enough not to need anything clever.
+
+use crate::enigma::Enigma;
+use crate::error::Error;
+use crate::rotors::rotor_chain::{RotorChain, Stepping};
+
+use super::{letter, RotorOrder};
+
+const POSITIONS: usize = 26 * 26 * 26;
+
+/// Positions reached from a start position: after `lead_in` key presses they repeat every
+/// `length` key presses. Positions reached only by setting the rotors by hand, e.g. with the
+/// middle rotor on its notch, are never repeated and count to the lead-in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cycle {
+    pub lead_in: u64,
+    pub length: u64,
+}
+
+pub struct SteppingAnalysis {
+    rotor_chain: RotorChain,
+}
+
+impl SteppingAnalysis {
+    /// Rotors of `rotor_order` stepping like in Enigma I.
+    pub fn new(rotor_order: RotorOrder) -> SteppingAnalysis {
+        let [left, middle, right] = rotor_order.rotors();
+        SteppingAnalysis {
+            rotor_chain: RotorChain::new(left, middle, right),
+        }
+    }
+
+    /// Rotors and stepping of `enigma`, e.g. of Enigma G with its gear stepping.
+    pub fn of(enigma: &Enigma) -> SteppingAnalysis {
+        SteppingAnalysis {
+            rotor_chain: enigma.rotor_chain().clone(),
+        }
+    }
+
+    /// E.g. 16900 key presses (26 * 25 * 26) for rotors with one notch each, as the double
+    /// step makes the middle rotor skip a position once per turn of the left one.
+    pub fn cycle(&self, start: &str) -> Result<Cycle, Error> {
+        let mut chain = self.chain_at(start)?;
+        let mut first_seen = vec![u64::MAX; POSITIONS];
+        let mut key_presses = 0;
+        loop {
+            let seen = &mut first_seen[index(&chain.positions())];
+            if *seen != u64::MAX {
+                return Ok(Cycle {
+                    lead_in: *seen,
+                    length: key_presses - *seen,
+                });
+            }
+            *seen = key_presses;
+            chain.rotate();
+            key_presses += 1;
+        }
+    }
+
+    /// Positions from which the next key press steps the middle rotor by its own notch.
+    pub fn double_step_positions(&self) -> Vec<String> {
+        (0..POSITIONS)
+            .map(positions)
+            .filter(|p| {
+                let mut chain = self.chain_at(p).unwrap();
+                chain.rotate().double_step
+            })
+            .collect()
+    }
+
+    /// How the rotors step on each of `key_presses` from `start`.
+    pub fn steppings(&self, start: &str, key_presses: usize) -> Result<Vec<Stepping>, Error> {
+        let mut chain = self.chain_at(start)?;
+        Ok((0..key_presses).map(|_| chain.rotate()).collect())
+    }
+
+    /// Key presses (counted from 0) within `key_presses` from `start` which step the middle
+    /// rotor, e.g. to tell whether it turned inside a crib.
+    pub fn middle_turnovers(&self, start: &str, key_presses: usize) -> Result<Vec<usize>, Error> {
+        self.turnovers(start, key_presses, |s| s.middle)
+    }
+
+    /// Key presses (counted from 0) within `key_presses` from `start` which step the left
+    /// rotor.
+    pub fn left_turnovers(&self, start: &str, key_presses: usize) -> Result<Vec<usize>, Error> {
+        self.turnovers(start, key_presses, |s| s.left)
+    }
+
+    fn turnovers<F: Fn(&Stepping) -> bool>(
+        &self,
+        start: &str,
+        key_presses: usize,
+        stepped: F,
+    ) -> Result<Vec<usize>, Error> {
+        Ok(self
+            .steppings(start, key_presses)?
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| stepped(s))
+            .map(|(i, _)| i)
+            .collect())
+    }
+
+    /// Positions before the first key press, after which the windows show `sequence` one key
+    /// press after another. There can be more of them, because the double step leads to
+    /// positions which can be reached also the regular way.
+    pub fn starts_for(&self, sequence: &[&str]) -> Result<Vec<String>, Error> {
+        for positions in sequence {
+            self.chain_at(positions)?;
+        }
+        Ok((0..POSITIONS)
+            .map(positions)
+            .filter(|start| {
+                let mut chain = self.chain_at(start).unwrap();
+                sequence.iter().all(|expected| {
+                    chain.rotate();
+                    chain.positions() == *expected
+                })
+            })
+            .collect())
+    }
+
+    fn chain_at(&self, positions: &str) -> Result<RotorChain, Error> {
+        let mut chain = self.rotor_chain.clone();
+        chain.change_setting(positions)?;
+        Ok(chain)
+    }
+}
+
+// window letters as a number, 'AAA' is 0
+fn index(positions: &str) -> usize {
+    positions
+        .bytes()
+        .fold(0, |index, b| index * 26 + (b - b'A') as usize)
+}
+
+fn positions(index: usize) -> String {
+    [index / 676, index / 26 % 26, index % 26]
+        .iter()
+        .map(|i| letter(*i as u8))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::ENIGMA_I_ROTORS;
+    use crate::model::Model;
+
+    fn analysis(left: usize, middle: usize, right: usize) -> SteppingAnalysis {
+        SteppingAnalysis::new(RotorOrder::new(
+            ENIGMA_I_ROTORS[left],
+            ENIGMA_I_ROTORS[middle],
+            ENIGMA_I_ROTORS[right],
+        ))
+    }
+
+    #[test]
+    fn positions_and_index() {
+        assert_eq!(index("AAA"), 0);
+        assert_eq!(index("BCD"), 676 + 2 * 26 + 3);
+        assert_eq!(positions(index("QEV")), "QEV");
+    }
+
+    #[test]
+    fn period_of_single_notch_rotors() {
+        let analysis = analysis(0, 1, 2);
+        assert_eq!(
+            analysis.cycle("AAA"),
+            Ok(Cycle {
+                lead_in: 0,
+                length: 16900
+            })
+        );
+        // middle rotor set on its notch by hand, it leaves it at once and never comes back
+        // to the same positions
+        assert_eq!(analysis.cycle("AEA").unwrap().lead_in, 1);
+        assert!(analysis.cycle("AA").is_err());
+    }
+
+    #[test]
+    fn period_with_gear_stepping() {
+        let g = Model::by_name("G").unwrap();
+        let enigma = g
+            .builder()
+            .rotor_left(g.rotor("I").unwrap())
+            .rotor_middle(g.rotor("II").unwrap())
+            .rotor_right(g.rotor("III").unwrap())
+            .reflector(g.reflector("UKW").unwrap())
+            .build()
+            .unwrap();
+        // like an odometer, all positions are used
+        assert_eq!(
+            SteppingAnalysis::of(&enigma).cycle("AAA").unwrap().length,
+            17576
+        );
+    }
+
+    #[test]
+    fn double_steps() {
+        // rotor II in the middle has its notch at 'E', rotor III on the right at 'V'
+        let positions = analysis(0, 1, 2).double_step_positions();
+        assert_eq!(positions.len(), 26 * 25);
+        assert!(positions.contains(&"AEA".to_string()));
+        // the middle rotor is carried by the right one then
+        assert!(!positions.contains(&"AEV".to_string()));
+    }
+
+    #[test]
+    fn turnovers_within_message() {
+        let analysis = analysis(0, 1, 2);
+        // ADU -> ADV -> AEW -> BFX -> BFY
+        assert_eq!(analysis.middle_turnovers("ADU", 4), Ok(vec![1, 2]));
+        assert_eq!(analysis.left_turnovers("ADU", 4), Ok(vec![2]));
+        assert_eq!(analysis.left_turnovers("ADU", 2), Ok(vec![]));
+        let steppings = analysis.steppings("ADU", 3).unwrap();
+        assert!(steppings[2].double_step);
+    }
+
+    #[test]
+    fn starts_leading_to_positions() {
+        let analysis = analysis(0, 1, 2);
+        assert_eq!(analysis.starts_for(&["AEW", "BFX"]), Ok(vec!["ADV".into()]));
+        // reached by the double step or just by the right rotor
+        assert_eq!(
+            analysis.starts_for(&["BFX"]),
+            Ok(vec!["AEW".to_string(), "BFW".to_string()])
+        );
+        // the left rotor never steps without the middle one
+        assert_eq!(analysis.starts_for(&["ADU", "BDV"]), Ok(vec![]));
+        assert!(analysis.starts_for(&["AD1"]).is_err());
+    }
+}
@@ -0,0 +1,444 @@
+// Zygalski sheets (perforated sheets)
+//
+// Message keys were sent twice, enciphered at the basic position chosen by the operator (sent in
+// clear), e.g. basic position "GKU" followed by "XLRXMP". When the same letter appears on
+// positions 1 and 4 (or 2 and 5, 3 and 6) we call it a "female". A female can occur only for some
+// of the rotor positions - those positions are holes on the sheet. One sheet covers all middle and
+// right rotor positions for given rotor order and left rotor position.
+//
+// Sheets are computed with all rings at 'A' and the females are checked for the exact stepping of
+// the machine. Unknown ring settings only shift the sheets (that's what stacking is about), but
+// they also move the turnover point, so - same as with the original paper sheets - an indicator
+// enciphered across a middle rotor turnover can be missed.
+
+use std::fmt::Display;
+
+use crate::alphabet::Letter;
+use crate::analysis::{index, letter, ReflectorFactory, RotorOrder};
+use crate::enigma::SUPPORTED_ALPHABET;
+use crate::error::Error;
+
+const LETTERS: usize = 26;
+const INDICATOR_LENGTH: usize = 6;
+
+/// Positions of the doubled message key on which a female can show up.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FemalePair {
+    /// positions 1 and 4
+    First,
+    /// positions 2 and 5
+    Second,
+    /// positions 3 and 6
+    Third,
+}
+
+impl FemalePair {
+    pub const ALL: [FemalePair; 3] = [FemalePair::First, FemalePair::Second, FemalePair::Third];
+
+    // first of the two positions, counted from 0
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    fn bit(self) -> u8 {
+        1 << self.index()
+    }
+}
+
+pub struct ZygalskiSheet {
+    rotor_order: RotorOrder,
+    left_position: char,
+    // bit 0 - female possible on positions 1/4, bit 1 - 2/5, bit 2 - 3/6
+    cells: [[u8; LETTERS]; LETTERS],
+}
+
+impl ZygalskiSheet {
+    pub fn generate(
+        rotor_order: RotorOrder,
+        reflector: ReflectorFactory,
+        left_position: char,
+    ) -> Result<ZygalskiSheet, Error> {
+        if !SUPPORTED_ALPHABET.contains(left_position) {
+            return Err(Error::UnsupportedCharacter {
+                character: left_position,
+                index: 0,
+            });
+        }
+
+        let mut enigma = rotor_order.scrambler(reflector);
+        let mut cells = [[0; LETTERS]; LETTERS];
+        let mut encoded = [[' '; LETTERS]; INDICATOR_LENGTH];
+        for middle in 0..LETTERS as u8 {
+            for right in 0..LETTERS as u8 {
+                let setting: String = [left_position, letter(middle), letter(right)]
+                    .iter()
+                    .collect();
+                enigma.change_rotor_setting(&setting)?;
+                for encoded_at_step in encoded.iter_mut() {
+                    enigma.step();
+                    for (i, encoded) in encoded_at_step.iter_mut().enumerate() {
+                        *encoded = enigma
+                            .encode_without_stepping(Letter::from_index(i as u8))
+                            .to_char();
+                    }
+                }
+
+                let mut females = 0;
+                for pair in FemalePair::ALL.iter() {
+                    let p = pair.index();
+                    let is_female_possible =
+                        (0..LETTERS).any(|i| encoded[p][i] == encoded[p + 3][i]);
+                    if is_female_possible {
+                        females |= pair.bit();
+                    }
+                }
+                cells[middle as usize][right as usize] = females;
+            }
+        }
+
+        Ok(ZygalskiSheet {
+            rotor_order,
+            left_position,
+            cells,
+        })
+    }
+
+    pub fn rotor_order(&self) -> RotorOrder {
+        self.rotor_order
+    }
+
+    pub fn left_position(&self) -> char {
+        self.left_position
+    }
+
+    pub fn has_hole(&self, middle: char, right: char, pair: FemalePair) -> Result<bool, Error> {
+        let (middle, right) = (Letter::new(middle)?, Letter::new(right)?);
+        Ok(self.hole(middle.index(), right.index(), pair))
+    }
+
+    fn hole(&self, middle: u8, right: u8, pair: FemalePair) -> bool {
+        self.cells[middle as usize][right as usize] & pair.bit() != 0
+    }
+
+    pub fn hole_count(&self, pair: FemalePair) -> usize {
+        self.cells
+            .iter()
+            .flatten()
+            .filter(|females| *females & pair.bit() != 0)
+            .count()
+    }
+
+    /// ASCII picture of the sheet: rows are middle rotor positions, columns are right rotor
+    /// positions, 'O' is a hole and '.' is the paper.
+    pub fn render(&self, pair: FemalePair) -> String {
+        let mut lines = vec![];
+        lines.push(format!(
+            "{} / {} / females {}-{}",
+            self.rotor_order,
+            self.left_position,
+            pair.index() + 1,
+            pair.index() + 4
+        ));
+        lines.push(format!("  {}", SUPPORTED_ALPHABET));
+        for (middle, row) in self.cells.iter().enumerate() {
+            let holes: String = row
+                .iter()
+                .map(|females| if females & pair.bit() != 0 { 'O' } else { '.' })
+                .collect();
+            lines.push(format!("{} {}", letter(middle as u8), holes));
+        }
+        lines.join("\n")
+    }
+}
+
+impl Display for ZygalskiSheet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.render(FemalePair::First))
+    }
+}
+
+/// All 26 sheets (one per left rotor position) for a single rotor order.
+pub struct SheetSet {
+    sheets: Vec<ZygalskiSheet>,
+}
+
+impl SheetSet {
+    pub fn generate(
+        rotor_order: RotorOrder,
+        reflector: ReflectorFactory,
+    ) -> Result<SheetSet, Error> {
+        let sheets = SUPPORTED_ALPHABET
+            .chars()
+            .map(|left| ZygalskiSheet::generate(rotor_order, reflector, left))
+            .collect::<Result<_, _>>()?;
+        Ok(SheetSet { sheets })
+    }
+
+    pub fn rotor_order(&self) -> RotorOrder {
+        self.sheets[0].rotor_order
+    }
+
+    pub fn sheet(&self, left_position: char) -> Result<&ZygalskiSheet, Error> {
+        Ok(&self.sheets[Letter::new(left_position)?.index() as usize])
+    }
+
+    /// Stacks the sheets for every female found in `indicators` and returns ring settings
+    /// (as the offset of the rotor core against the letter in the window) for which light
+    /// passes through all the sheets.
+    pub fn stack(&self, indicators: &[Indicator]) -> Vec<SurvivingPosition> {
+        let females: Vec<(&Indicator, FemalePair)> = indicators
+            .iter()
+            .flat_map(|indicator| indicator.females().into_iter().map(move |p| (indicator, p)))
+            .collect();
+
+        let mut survivors = vec![];
+        for ring_left in 0..LETTERS as u8 {
+            for ring_middle in 0..LETTERS as u8 {
+                for ring_right in 0..LETTERS as u8 {
+                    let ring = [ring_left, ring_middle, ring_right];
+                    let light_passes = females.iter().all(|(indicator, pair)| {
+                        let [l, m, r] = indicator.core_position(ring);
+                        self.sheets[l as usize].hole(m, r, *pair)
+                    });
+                    if light_passes {
+                        survivors.push(SurvivingPosition {
+                            rotor_order: self.rotor_order(),
+                            ring_setting: ring.iter().map(|i| letter(*i)).collect(),
+                        });
+                    }
+                }
+            }
+        }
+        survivors
+    }
+}
+
+/// Basic position (sent in clear) and the doubled message key enciphered at that position.
+#[derive(Debug, PartialEq)]
+pub struct Indicator {
+    basic_position: [u8; 3],
+    encoded_message_key: [u8; INDICATOR_LENGTH],
+}
+
+impl Indicator {
+    pub fn new(basic_position: &str, encoded_message_key: &str) -> Result<Indicator, Error> {
+        Error::check_length("Basic position", basic_position, 3)?;
+        Error::check_length("Encoded message key", encoded_message_key, INDICATOR_LENGTH)?;
+        Error::check_alphabet(basic_position)?;
+        Error::check_alphabet(encoded_message_key)?;
+
+        let mut indicator = Indicator {
+            basic_position: [0; 3],
+            encoded_message_key: [0; INDICATOR_LENGTH],
+        };
+        for (i, c) in basic_position.chars().enumerate() {
+            indicator.basic_position[i] = index(c);
+        }
+        for (i, c) in encoded_message_key.chars().enumerate() {
+            indicator.encoded_message_key[i] = index(c);
+        }
+        Ok(indicator)
+    }
+
+    /// Pairs on which the same letter was repeated.
+    pub fn females(&self) -> Vec<FemalePair> {
+        FemalePair::ALL
+            .iter()
+            .copied()
+            .filter(|p| {
+                self.encoded_message_key[p.index()] == self.encoded_message_key[p.index() + 3]
+            })
+            .collect()
+    }
+
+    // letter indexes
+    fn core_position(&self, ring: [u8; 3]) -> [u8; 3] {
+        let mut position = [0; 3];
+        for i in 0..3 {
+            position[i] = (self.basic_position[i] + LETTERS as u8 - ring[i]) % 26;
+        }
+        position
+    }
+}
+
+#[derive(Debug)]
+pub struct SurvivingPosition {
+    pub rotor_order: RotorOrder,
+    pub ring_setting: String,
+}
+
+/// Runs the stacking for every rotor order and collects all the survivors.
+pub fn solve(sheet_sets: &[SheetSet], indicators: &[Indicator]) -> Vec<SurvivingPosition> {
+    sheet_sets
+        .iter()
+        .flat_map(|sheet_set| sheet_set.stack(indicators))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::ENIGMA_I_ROTORS;
+    use crate::reflector::Reflector;
+
+    fn rotor_order() -> RotorOrder {
+        RotorOrder::new(ENIGMA_I_ROTORS[0], ENIGMA_I_ROTORS[1], ENIGMA_I_ROTORS[2])
+    }
+
+    #[test]
+    fn holes_match_indicators_enciphered_at_the_position() {
+        let sheet = ZygalskiSheet::generate(rotor_order(), Reflector::b, 'A').unwrap();
+
+        let mut enigma = rotor_order().scrambler(Reflector::b);
+        for (middle, right) in [('A', 'A'), ('K', 'B'), ('Z', 'X')].iter() {
+            for key in SUPPORTED_ALPHABET.chars() {
+                let setting: String = ['A', *middle, *right].iter().collect();
+                enigma.change_rotor_setting(&setting).unwrap();
+                let doubled_key: String = [key, key, key, key, key, key].iter().collect();
+                let encoded = enigma
+                    .encode_for_current_rotor_setting(&doubled_key)
+                    .unwrap();
+
+                let indicator = Indicator::new(&setting, &encoded).unwrap();
+                for pair in indicator.females() {
+                    assert_eq!(sheet.has_hole(*middle, *right, pair), Ok(true));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn error_on_position_out_of_alphabet() {
+        let sheet = ZygalskiSheet::generate(rotor_order(), Reflector::b, 'A').unwrap();
+        assert_eq!(
+            sheet.has_hole('a', 'B', FemalePair::First),
+            Err(Error::UnsupportedCharacter {
+                character: 'a',
+                index: 0
+            })
+        );
+        assert!(sheet.has_hole('A', '1', FemalePair::First).is_err());
+    }
+
+    #[test]
+    fn sheet_has_roughly_40_percent_of_holes() {
+        let sheet = ZygalskiSheet::generate(rotor_order(), Reflector::b, 'Q').unwrap();
+        for pair in FemalePair::ALL.iter() {
+            let holes = sheet.hole_count(*pair);
+            assert!(holes > 676 * 3 / 10 && holes < 676 * 5 / 10, "{}", holes);
+        }
+    }
+
+    #[test]
+    fn sheet_renders_as_ascii() {
+        let sheet = ZygalskiSheet::generate(rotor_order(), Reflector::b, 'A').unwrap();
+        let rendered = sheet.to_string();
+        let lines: Vec<&str> = rendered.lines().collect();
+        assert_eq!(lines.len(), 28);
+        assert_eq!(lines[0], "I II III / A / females 1-4");
+        assert!(sheet
+            .render(FemalePair::Third)
+            .starts_with("I II III / A / females 3-6\n"));
+        assert_eq!(lines[1], "  ABCDEFGHIJKLMNOPQRSTUVWXYZ");
+        assert!(lines[2].starts_with("A "));
+        assert_eq!(
+            lines[2].chars().skip(2).filter(|c| *c == 'O').count(),
+            (0..26)
+                .filter(|r| sheet.has_hole('A', letter(*r), FemalePair::First).unwrap())
+                .count()
+        );
+    }
+
+    #[test]
+    fn error_on_unsupported_left_position() {
+        assert_eq!(
+            ZygalskiSheet::generate(rotor_order(), Reflector::b, '1').err(),
+            Some(Error::UnsupportedCharacter {
+                character: '1',
+                index: 0
+            })
+        );
+    }
+
+    #[test]
+    fn indicator_females() {
+        let indicator = Indicator::new("GKU", "XLRXMR").unwrap();
+        assert_eq!(
+            indicator.females(),
+            vec![FemalePair::First, FemalePair::Third]
+        );
+        assert_eq!(
+            Indicator::new("GKU", "ABCDEF").unwrap().females(),
+            Vec::<FemalePair>::new()
+        );
+    }
+
+    #[test]
+    fn error_on_invalid_indicator() {
+        assert_eq!(
+            Indicator::new("GK", "XLRXMR"),
+            Err(Error::InvalidLength {
+                subject: "Basic position",
+                expected: 3,
+                actual: 2
+            })
+        );
+        assert_eq!(
+            Indicator::new("GKU", "XLRXM"),
+            Err(Error::InvalidLength {
+                subject: "Encoded message key",
+                expected: 6,
+                actual: 5
+            })
+        );
+        assert_eq!(
+            Indicator::new("GKU", "XLRXMr"),
+            Err(Error::UnsupportedCharacter {
+                character: 'r',
+                index: 5
+            })
+        );
+    }
+
+    #[test]
+    fn stacking_finds_the_ring_setting() {
+        let ring = [7u8, 19, 2];
+        let mut enigma = rotor_order().scrambler(Reflector::b);
+        let mut indicators = vec![];
+
+        // simple pseudo random sequence, so the test stays deterministic
+        let mut seed = 12345u32;
+        let mut next = || {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            ((seed >> 16) % 26) as u8
+        };
+        while indicators
+            .iter()
+            .map(|i: &Indicator| i.females().len())
+            .sum::<usize>()
+            < 12
+        {
+            let basic_position = [next(), next(), next()];
+            let key = [next(), next(), next()];
+            // letters in the windows are the core positions shifted by the ring setting
+            let core: String = (0..3)
+                .map(|i| letter((basic_position[i] + 26 - ring[i]) % 26))
+                .collect();
+            enigma.change_rotor_setting(&core).unwrap();
+            let doubled_key: String = key.iter().chain(key.iter()).map(|i| letter(*i)).collect();
+            let encoded = enigma
+                .encode_for_current_rotor_setting(&doubled_key)
+                .unwrap();
+            let basic_position: String = basic_position.iter().map(|i| letter(*i)).collect();
+            indicators.push(Indicator::new(&basic_position, &encoded).unwrap());
+        }
+
+        let sheet_set = SheetSet::generate(rotor_order(), Reflector::b).unwrap();
+        assert_eq!(sheet_set.sheet('Q').unwrap().left_position(), 'Q');
+        assert!(sheet_set.sheet('q').is_err());
+        let survivors = sheet_set.stack(&indicators);
+
+        assert!(survivors.iter().any(|s| s.ring_setting == "HTC"));
+        assert!(survivors.len() < 20, "{}", survivors.len());
+    }
+}
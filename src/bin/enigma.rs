@@ -1,30 +1,59 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
 use std::{io, process};
 
 use enigma::{
+    analysis::keyspace::{Keyspace, KeyspaceCalculator},
+    analysis::stats::CiphertextStatistics,
     enigma::{EncodingResult, Enigma},
     enigma_builder::{BuildError, RotorPlacement},
     entry_disk::EntryDisk,
+    machine::Machine,
+    stream::{encode_stream, NonLetterPolicy},
+    typex::Typex,
 };
 use simple_logger::SimpleLogger;
 
 use chrono::Local;
 use enigma::enigma::SUPPORTED_ALPHABET;
 use enigma::enigma_builder::EnigmaBuilder;
+use enigma::machine_setting::MachineSetting;
+use enigma::model::Model;
 use enigma::plugboard::PlugboardConnection;
 use enigma::reflector::Reflector;
 use enigma::rotors::rotor::Rotor;
 
-use structopt::StructOpt;
+use structopt::{clap::AppSettings, StructOpt};
 
 use chrono::DateTime;
 use std::fmt::{Display, Error, Formatter};
 
+// exit codes as in BSD sysexits.h
+// message, ciphertext or rotor positions are invalid
+const EXIT_DATA_ERROR: i32 = 65;
+const EXIT_IO_ERROR: i32 = 74;
+// Enigma can not be built from the parts passed as arguments
+const EXIT_CONFIG_ERROR: i32 = 78;
+
+fn exit_with(code: i32, error: impl Display) -> ! {
+    eprintln!("{}", error);
+    process::exit(code)
+}
+
 fn main() {
-    SimpleLogger::new().init().unwrap();
+    let mut args = Arguments::from_args();
 
-    let args = Arguments::from_args();
+    // commands write their results to standard output, so no logs are printed there
+    if let Some(command) = args.command.take() {
+        run_command(command, args);
+        return;
+    }
+
+    SimpleLogger::new().init().unwrap();
 
-    let mut enigma_builder = if args.use_sample {
+    let mut enigma_builder = if let Some(config) = &args.config {
+        builder_from_config(config)
+    } else if args.use_sample {
         EnigmaBuilder::init()
             .entry_disk(EntryDisk::identity())
             .rotor_left(Rotor::enigma_i_wehrmacht_i())
@@ -34,6 +63,9 @@ fn main() {
     } else {
         EnigmaBuilder::init()
     };
+    if args.permissive {
+        enigma_builder = enigma_builder.permissive();
+    }
 
     let mut basic_position = args.basic_position;
     let mut message_key = args.message_key;
@@ -41,21 +73,12 @@ fn main() {
 
     let enigma: Option<Enigma>;
 
-    if !args.allow_cli_questions {
-        let built = enigma_builder
-            .build()
-            .map_err(|build_error| match build_error {
-                BuildError::RotorError(rotor_placement, msg) => {
-                    format!("{} ({} rotor)", msg, rotor_placement)
-                }
-                BuildError::PlugboardError(msg) => msg,
-                BuildError::EntryDiskError(msg) => msg,
-                BuildError::ReflectorError(msg) => msg,
-            });
-        if let Err(err) = built {
-            panic!("Unexpected error: {}", err);
+    let allow_cli_questions = args.allow_cli_questions;
+    if !allow_cli_questions {
+        match enigma_builder.build() {
+            Ok(built) => enigma = Some(built),
+            Err(err) => exit_with(EXIT_CONFIG_ERROR, err),
         }
-        enigma = Some(built.unwrap());
     } else {
         let reflector = args.reflector.unwrap_or_else(read_reflector_from_cli);
         enigma_builder = enigma_builder.reflector(reflector);
@@ -74,23 +97,23 @@ fn main() {
         } else {
             args.plugboard_connections
                 .into_iter()
-                .map(|c| match c {
-                    PlugboardConnectionOption::Existing(existing) => existing,
-                    PlugboardConnectionOption::None => panic!("Must not happen"),
+                .filter_map(|c| match c {
+                    PlugboardConnectionOption::Existing(existing) => Some(existing),
+                    PlugboardConnectionOption::None => None,
                 })
                 .collect()
         };
         enigma_builder = enigma_builder.plugboard_connections(plugboard_connections);
 
-        if let None = basic_position {
+        if basic_position.is_none() {
             basic_position = Some(read_basic_position_from_cli())
         }
 
-        if let None = message_key {
+        if message_key.is_none() {
             message_key = Some(read_message_key_from_cli())
         }
 
-        if let None = message_to_encode {
+        if message_to_encode.is_none() {
             message_to_encode = Some(read_message_to_encode_from_cli())
         }
 
@@ -98,10 +121,10 @@ fn main() {
         while let Err(build_error) = en {
             println!();
             match build_error {
-                BuildError::RotorError(rotor_placement, err) => {
+                BuildError::MissingRotor(rotor_placement) => {
                     eprintln!(
                         "Rotor ({}) error when building Enigma: {}",
-                        rotor_placement, err
+                        rotor_placement, build_error
                     );
                     match rotor_placement {
                         RotorPlacement::Left => {
@@ -117,20 +140,27 @@ fn main() {
                     }
                     en = enigma_builder.build();
                 }
-                BuildError::PlugboardError(err) => {
+                BuildError::Plugboard(err) => {
                     eprintln!("Plugboard error when building Enigma: {}", err);
                     enigma_builder =
                         enigma_builder.plugboard_connections(read_plugboard_connections_from_cli());
                     en = enigma_builder.build();
                 }
-                BuildError::EntryDiskError(err) => {
-                    todo!(
-                        "Entry disk reading from CLI is not implemented yet: {}",
-                        err
-                    );
-                }
-                BuildError::ReflectorError(err) => {
-                    eprintln!("Reflector error when building Enigma: {}", err);
+                BuildError::MissingEntryDisk => exit_with(
+                    EXIT_CONFIG_ERROR,
+                    format!(
+                        "{} (reading entry disk from CLI is not supported)",
+                        build_error
+                    ),
+                ),
+                BuildError::MissingGreekWheel
+                | BuildError::WheelNotInModel { .. }
+                | BuildError::DuplicateWheel(_)
+                | BuildError::WrongWheelKind { .. }
+                | BuildError::ReflectorNotSettable { .. }
+                | BuildError::ReversedWheel { .. } => exit_with(EXIT_CONFIG_ERROR, build_error),
+                BuildError::MissingReflector => {
+                    eprintln!("Reflector error when building Enigma: {}", build_error);
                     enigma_builder = enigma_builder.reflector(read_reflector_from_cli());
                     en = enigma_builder.build();
                 }
@@ -151,6 +181,9 @@ fn main() {
     );
 
     while let Err(err) = encoding_result {
+        if !allow_cli_questions {
+            exit_with(EXIT_DATA_ERROR, err);
+        }
         eprintln!("Failed to encode the message due to error:");
         eprintln!("{}", err);
         println!("Provide new message to encode.");
@@ -179,6 +212,518 @@ fn main() {
     println!("{}", message);
 }
 
+fn run_command(command: Command, args: Arguments) {
+    match command {
+        Command::Stats {
+            ciphertext,
+            crib,
+            crib_position,
+        } => {
+            let report =
+                CiphertextStatistics::new(&ciphertext.to_uppercase()).and_then(
+                    |stats| match crib {
+                        Some(crib) => stats.report_with_crib(&crib.to_uppercase(), crib_position),
+                        None => Ok(stats.report()),
+                    },
+                );
+            match report {
+                Ok(report) => print!("{}", report),
+                Err(err) => exit_with(EXIT_DATA_ERROR, err),
+            }
+        }
+        Command::SaveConfig { setting, output } => {
+            let setting = setting
+                .parse::<MachineSetting>()
+                .and_then(|setting| setting.build().map(|_| setting))
+                .unwrap_or_else(|err| exit_with(EXIT_CONFIG_ERROR, err));
+            save_config(&setting, &output);
+        }
+        Command::Encrypt {
+            input,
+            output,
+            position,
+            non_letters,
+        } => {
+            let mut enigma =
+                build_from_arguments(args).unwrap_or_else(|err| exit_with(EXIT_CONFIG_ERROR, err));
+            if let Err(err) = enigma.change_rotor_setting(&position) {
+                exit_with(EXIT_DATA_ERROR, err);
+            }
+            if let Err(err) = encrypt(&mut enigma, &input, &output, non_letters) {
+                match err.kind() {
+                    io::ErrorKind::InvalidData => exit_with(EXIT_DATA_ERROR, err),
+                    _ => exit_with(EXIT_IO_ERROR, err),
+                }
+            }
+        }
+        Command::Lampboard { position } => {
+            let mut enigma =
+                build_from_arguments(args).unwrap_or_else(|err| exit_with(EXIT_CONFIG_ERROR, err));
+            if let Err(err) = enigma.change_rotor_setting(&position) {
+                exit_with(EXIT_DATA_ERROR, err);
+            }
+            if let Err(err) = lampboard(enigma) {
+                exit_with(EXIT_IO_ERROR, err);
+            }
+        }
+        Command::Keyspace {
+            model,
+            rotors,
+            cables,
+            ukw_d,
+            uhr,
+        } => match keyspace(&model, &rotors, cables, ukw_d, uhr) {
+            Ok(keyspace) => println!("{}", keyspace),
+            Err(err) => exit_with(EXIT_CONFIG_ERROR, err),
+        },
+        Command::Typex {
+            rotors,
+            rings,
+            position,
+            input,
+            output,
+            non_letters,
+        } => {
+            let mut typex = build_typex(&rotors, &rings)
+                .unwrap_or_else(|err| exit_with(EXIT_CONFIG_ERROR, err));
+            if let Err(err) = typex.change_rotor_setting(&position) {
+                exit_with(EXIT_DATA_ERROR, err);
+            }
+            if let Err(err) = encrypt(&mut typex, &input, &output, non_letters) {
+                match err.kind() {
+                    io::ErrorKind::InvalidData => exit_with(EXIT_DATA_ERROR, err),
+                    _ => exit_with(EXIT_IO_ERROR, err),
+                }
+            }
+        }
+    }
+}
+
+fn keyspace(
+    model: &str,
+    rotors: &[String],
+    cables: Option<usize>,
+    ukw_d: bool,
+    uhr: bool,
+) -> Result<Keyspace, enigma::Error> {
+    let model = Model::by_name(model).ok_or_else(|| enigma::Error::UnknownModel(model.into()))?;
+    let mut calculator = KeyspaceCalculator::new(model);
+    if !rotors.is_empty() {
+        let names: Vec<&str> = rotors.iter().map(String::as_str).collect();
+        calculator = calculator.rotors(&names)?;
+    }
+    if let Some(cables) = cables {
+        calculator = calculator.cables(cables);
+    }
+    if ukw_d {
+        calculator = calculator.ukw_d();
+    }
+    if uhr {
+        calculator = calculator.uhr();
+    }
+    calculator.calculate()
+}
+
+// rotors like 'A B CR D E', where 'R' marks a reversed rotor
+fn build_typex(rotors: &str, rings: &str) -> Result<Typex, enigma::Error> {
+    let names: Vec<&str> = rotors.split_whitespace().collect();
+    for (subject, actual) in [("Typex rotors", names.len()), ("Typex rings", rings.len())] {
+        if actual != 5 {
+            return Err(enigma::Error::InvalidLength {
+                subject,
+                expected: 5,
+                actual,
+            });
+        }
+    }
+    let mut wheels = Vec::with_capacity(5);
+    for (name, ring) in names.iter().zip(rings.chars()) {
+        let (name, reversed) = match name.strip_suffix('R') {
+            Some(name) => (name, true),
+            None => (*name, false),
+        };
+        let rotor = Typex::rotor(name)
+            .ok_or_else(|| enigma::Error::UnknownRotor(name.into()))?
+            .with_ring_setting(ring)?;
+        wheels.push(if reversed { rotor.reversed() } else { rotor });
+    }
+    let wheels: [Rotor; 5] = std::convert::TryInto::try_into(wheels).unwrap();
+    Ok(Typex::new(wheels, Typex::reflector("Standard").unwrap()))
+}
+
+// builds Enigma from the parts passed as arguments, without asking for missing ones
+fn build_from_arguments(args: Arguments) -> Result<Enigma, BuildError> {
+    let mut enigma_builder = if let Some(config) = &args.config {
+        builder_from_config(config)
+    } else if args.use_sample {
+        EnigmaBuilder::init()
+            .rotor_left(Rotor::enigma_i_wehrmacht_i())
+            .rotor_middle(Rotor::enigma_i_wehrmacht_ii())
+            .rotor_right(Rotor::enigma_i_wehrmacht_iii())
+            .reflector(Reflector::b())
+    } else {
+        EnigmaBuilder::init()
+    };
+    if args.permissive {
+        enigma_builder = enigma_builder.permissive();
+    }
+    if let Some(reflector) = args.reflector {
+        enigma_builder = enigma_builder.reflector(reflector);
+    }
+    if let Some(rotor) = args.rotor_left {
+        enigma_builder = enigma_builder.rotor_left(rotor);
+    }
+    if let Some(rotor) = args.rotor_middle {
+        enigma_builder = enigma_builder.rotor_middle(rotor);
+    }
+    if let Some(rotor) = args.rotor_right {
+        enigma_builder = enigma_builder.rotor_right(rotor);
+    }
+    let plugboard_connections = args
+        .plugboard_connections
+        .into_iter()
+        .filter_map(|c| match c {
+            PlugboardConnectionOption::Existing(existing) => Some(existing),
+            PlugboardConnectionOption::None => None,
+        })
+        .collect();
+    enigma_builder
+        .plugboard_connections(plugboard_connections)
+        .build()
+}
+
+fn builder_from_config(path: &str) -> EnigmaBuilder {
+    load_config(path)
+        .builder()
+        .unwrap_or_else(|err| exit_with(EXIT_CONFIG_ERROR, err))
+}
+
+#[cfg(feature = "serde")]
+fn load_config(path: &str) -> MachineSetting {
+    let file = File::open(path).unwrap_or_else(|err| {
+        exit_with(
+            EXIT_IO_ERROR,
+            format!("Can not read config {}: {}", path, err),
+        )
+    });
+    serde_json::from_reader(BufReader::new(file)).unwrap_or_else(|err| {
+        exit_with(
+            EXIT_CONFIG_ERROR,
+            format!("Invalid config {}: {}", path, err),
+        )
+    })
+}
+
+#[cfg(feature = "serde")]
+fn save_config(setting: &MachineSetting, output: &str) {
+    let writer: Box<dyn Write> = if output == "-" {
+        Box::new(io::stdout())
+    } else {
+        Box::new(File::create(output).unwrap_or_else(|err| {
+            exit_with(
+                EXIT_IO_ERROR,
+                format!("Can not write config {}: {}", output, err),
+            )
+        }))
+    };
+    let mut writer = BufWriter::new(writer);
+    if let Err(err) = serde_json::to_writer_pretty(&mut writer, setting)
+        .map_err(io::Error::from)
+        .and_then(|_| writeln!(writer))
+        .and_then(|_| writer.flush())
+    {
+        exit_with(EXIT_IO_ERROR, err);
+    }
+}
+
+#[cfg(not(feature = "serde"))]
+fn load_config(_path: &str) -> MachineSetting {
+    exit_with(EXIT_CONFIG_ERROR, SERDE_FEATURE_REQUIRED)
+}
+
+#[cfg(not(feature = "serde"))]
+fn save_config(_setting: &MachineSetting, _output: &str) {
+    exit_with(EXIT_CONFIG_ERROR, SERDE_FEATURE_REQUIRED)
+}
+
+#[cfg(not(feature = "serde"))]
+const SERDE_FEATURE_REQUIRED: &str =
+    "Config files are supported only when built with the 'serde' feature";
+
+fn encrypt(
+    machine: &mut dyn Machine,
+    input: &str,
+    output: &str,
+    non_letters: NonLetterPolicy,
+) -> io::Result<u64> {
+    let reader: Box<dyn Read> = if input == "-" {
+        Box::new(io::stdin())
+    } else {
+        Box::new(File::open(input)?)
+    };
+    let writer: Box<dyn Write> = if output == "-" {
+        Box::new(io::stdout())
+    } else {
+        Box::new(File::create(output)?)
+    };
+    encode_stream(
+        machine,
+        BufReader::new(reader),
+        BufWriter::new(writer),
+        non_letters,
+    )
+}
+
+// rows of keys and lamps as on the machine, the German QWERTZ layout
+const KEYBOARD_ROWS: [&str; 3] = ["QWERTZUIO", "ASDFGHJK", "PYXCVBNML"];
+
+const CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+const LIT_LAMP: &str = "\x1b[1;30;43m";
+const PRESSED_KEY: &str = "\x1b[7m";
+const RESET_STYLE: &str = "\x1b[0m";
+const ESCAPE: u8 = 0x1b;
+// Ctrl-C, read as a key since the terminal does not raise SIGINT for it
+const END_OF_TEXT: u8 = 0x03;
+const END_OF_TRANSMISSION: u8 = 0x04;
+
+const LAMPBOARD_HELP: &str =
+    "Type letters to encode, ':' for a command: pos ADU, rings AAA, plug AB, unplug A, clear, quit";
+
+// escape sequence sent by a terminal, e.g. for the arrow keys
+enum Escape {
+    // ESC, a key of its own unless '[' or 'O' follows
+    Started,
+    // ESC [ with parameters up to the final byte
+    Csi,
+    // ESC O and a single final byte
+    Ss3,
+}
+
+// interactive Enigma - keys are pressed with `Enigma::press`, so the rotors step exactly as
+// when encoding a message
+struct Lampboard {
+    enigma: Enigma,
+    // last key pressed and the lamp it lit
+    lit: Option<(char, char)>,
+    tape: String,
+    // command being typed after ':'
+    command: Option<String>,
+    escape: Option<Escape>,
+    status: String,
+}
+
+impl Lampboard {
+    fn new(enigma: Enigma) -> Lampboard {
+        Lampboard {
+            enigma,
+            lit: None,
+            tape: String::new(),
+            command: None,
+            escape: None,
+            status: String::from(LAMPBOARD_HELP),
+        }
+    }
+
+    // returns false when the user quits
+    fn handle(&mut self, byte: u8) -> bool {
+        if let Some(escape) = self.escape.take() {
+            match (escape, byte) {
+                (Escape::Started, b'[') => self.escape = Some(Escape::Csi),
+                (Escape::Started, b'O') => self.escape = Some(Escape::Ss3),
+                (Escape::Started, _) => return self.handle(byte),
+                // parameter and intermediate bytes
+                (Escape::Csi, 0x20..=0x3f) => self.escape = Some(Escape::Csi),
+                // final byte
+                (Escape::Csi, _) | (Escape::Ss3, _) => {}
+            }
+            return true;
+        }
+        if byte == ESCAPE {
+            self.escape = Some(Escape::Started);
+        }
+        if let Some(command) = &mut self.command {
+            match byte {
+                b'\n' | b'\r' => {
+                    let command = self.command.take().unwrap();
+                    return self.run(&command);
+                }
+                ESCAPE => self.command = None,
+                // backspace or delete
+                0x08 | 0x7f => {
+                    command.pop();
+                }
+                END_OF_TEXT | END_OF_TRANSMISSION => return false,
+                _ if byte.is_ascii_graphic() || byte == b' ' => command.push(byte as char),
+                _ => {}
+            }
+            return true;
+        }
+        match byte {
+            b':' => self.command = Some(String::new()),
+            END_OF_TEXT | END_OF_TRANSMISSION => return false,
+            _ if byte.is_ascii_alphabetic() => {
+                let key = byte.to_ascii_uppercase() as char;
+                let lamp = self.enigma.press(key).unwrap();
+                self.lit = Some((key, lamp));
+                self.tape.push(lamp);
+            }
+            _ => {}
+        }
+        true
+    }
+
+    fn run(&mut self, command: &str) -> bool {
+        let command = command.trim().to_uppercase();
+        let mut words = command.split_whitespace();
+        let (name, argument) = (words.next().unwrap_or(""), words.next().unwrap_or(""));
+        let result = match name {
+            "" => Ok(()),
+            "QUIT" | "EXIT" => return false,
+            "POS" => self.enigma.change_rotor_setting(argument),
+            "RINGS" => self.enigma.change_ring_setting(argument),
+            "PLUG" => match argument.chars().collect::<Vec<char>>()[..] {
+                [from, to] => self.enigma.plugboard_mut().connect(from, to),
+                _ => Err(enigma::Error::InvalidLength {
+                    subject: "Plug",
+                    expected: 2,
+                    actual: argument.chars().count(),
+                }),
+            },
+            "UNPLUG" => match argument.chars().next() {
+                Some(letter) => self.enigma.plugboard_mut().disconnect(letter),
+                None => Ok(()),
+            },
+            "CLEAR" => {
+                self.tape.clear();
+                Ok(())
+            }
+            _ => {
+                self.status = format!("Unknown command: {}", name.to_lowercase());
+                return true;
+            }
+        };
+        self.lit = None;
+        self.status = match result {
+            Ok(()) => String::from(LAMPBOARD_HELP),
+            Err(err) => err.to_string(),
+        };
+        true
+    }
+
+    fn render(&self) -> String {
+        let mut screen = String::from(CLEAR_SCREEN);
+        let windows: Vec<String> = self
+            .enigma
+            .rotor_positions()
+            .chars()
+            .map(|c| format!("[{}]", c))
+            .collect();
+        screen.push_str(&format!(
+            "  {}    rings {}\r\n\r\n",
+            windows.join(" "),
+            self.enigma.ring_settings()
+        ));
+
+        let lamp = self.lit.map(|(_, lamp)| lamp);
+        let key = self.lit.map(|(key, _)| key);
+        screen.push_str(&board(lamp, LIT_LAMP));
+        screen.push_str("\r\n");
+        screen.push_str(&board(key, PRESSED_KEY));
+
+        let plugs: Vec<String> = self
+            .enigma
+            .plugboard()
+            .pairs()
+            .iter()
+            .map(|(a, b)| format!("{}{}", a, b))
+            .collect();
+        screen.push_str(&format!("\r\n  plugs {}\r\n", plugs.join(" ")));
+        screen.push_str(&format!("  tape  {}\r\n\r\n", tape_groups(&self.tape)));
+        match &self.command {
+            Some(command) => screen.push_str(&format!(":{}", command)),
+            None => screen.push_str(&self.status),
+        }
+        screen
+    }
+}
+
+// one row of lamps or keys per line, `highlighted` drawn with `style`
+fn board(highlighted: Option<char>, style: &str) -> String {
+    let mut rows = String::new();
+    for (i, row) in KEYBOARD_ROWS.iter().enumerate() {
+        // middle row is shifted like on the machine
+        rows.push_str(if i == 1 { "    " } else { "  " });
+        for c in row.chars() {
+            if Some(c) == highlighted {
+                rows.push_str(&format!("{} {} {}", style, c, RESET_STYLE));
+            } else {
+                rows.push_str(&format!(" {} ", c));
+            }
+            rows.push(' ');
+        }
+        rows.push_str("\r\n");
+    }
+    rows
+}
+
+// letters in groups of five, as written down by the operator
+fn tape_groups(tape: &str) -> String {
+    tape.as_bytes()
+        .chunks(5)
+        .map(|group| String::from_utf8_lossy(group).into_owned())
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+// switches the terminal to read key by key without echo, and back when dropped; without
+// a terminal (or `stty`) keys are read line by line
+struct RawTerminal {
+    saved: Option<String>,
+}
+
+impl RawTerminal {
+    fn enable() -> RawTerminal {
+        let saved =
+            stty(&["-g"]).filter(|_| stty(&["-icanon", "-echo", "-isig", "min", "1"]).is_some());
+        RawTerminal { saved }
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        if let Some(saved) = &self.saved {
+            stty(&[saved.trim()]);
+        }
+    }
+}
+
+fn stty(args: &[&str]) -> Option<String> {
+    process::Command::new("stty")
+        .args(args)
+        .stdin(process::Stdio::inherit())
+        .stderr(process::Stdio::null())
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn lampboard(enigma: Enigma) -> io::Result<()> {
+    let mut lampboard = Lampboard::new(enigma);
+    let _terminal = RawTerminal::enable();
+    let mut stdout = io::stdout();
+    write!(stdout, "{}", lampboard.render())?;
+    stdout.flush()?;
+    for byte in io::stdin().lock().bytes() {
+        if !lampboard.handle(byte?) {
+            break;
+        }
+        write!(stdout, "{}", lampboard.render())?;
+        stdout.flush()?;
+    }
+    writeln!(stdout, "{}\r", RESET_STYLE)
+}
+
 fn read_reflector_from_cli() -> Reflector {
     read("UKW (reflector)", "Available: A, B or C", REFLECTOR_PARSER)
 }
@@ -273,6 +818,9 @@ impl Message {
     }
 }
 
+// placeholder, the group for a day is not known to the executable
+const IDENTIFICATION_GROUP: &str = "ABCDE";
+
 impl Display for Message {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
         // for example: U6Z DE C 1510 = 49 = EHZ TBS = TVEXS QBLTW LDAHH YEOEF
@@ -297,7 +845,7 @@ impl Display for Message {
                message_length = self.message_length,
                basic_position = self.basic_position,
                encrypted_message_key = self.encoded_message_key,
-               identification_group = "ABCDE",
+               identification_group = IDENTIFICATION_GROUP,
                encoded_message = encoded_message
         )
     }
@@ -340,6 +888,104 @@ mod tests {
         }
     }
 
+    mod lampboard {
+        use super::*;
+
+        fn sample_lampboard() -> Lampboard {
+            let mut args = Arguments::from_iter(&["test", "--use-sample", "lampboard"]);
+            assert_eq!(
+                args.command.take(),
+                Some(Command::Lampboard {
+                    position: String::from("AAA")
+                })
+            );
+            Lampboard::new(build_from_arguments(args).unwrap())
+        }
+
+        fn type_in(lampboard: &mut Lampboard, input: &str) -> bool {
+            input.bytes().all(|b| lampboard.handle(b))
+        }
+
+        #[test]
+        fn keys_light_lamps_and_fill_tape() {
+            let mut lampboard = sample_lampboard();
+            assert!(type_in(&mut lampboard, "aaaaaA"));
+            assert_eq!(lampboard.lit, Some(('A', 'W')));
+            assert_eq!(lampboard.enigma.rotor_positions(), "AAG");
+            assert_eq!(tape_groups(&lampboard.tape), "BDZGO W");
+
+            let screen = lampboard.render();
+            assert!(screen.starts_with(CLEAR_SCREEN));
+            assert!(screen.contains("[A] [A] [G]"));
+            assert!(screen.contains(&format!("{} W {}", LIT_LAMP, RESET_STYLE)));
+            assert!(screen.contains(&format!("{} A {}", PRESSED_KEY, RESET_STYLE)));
+            assert!(screen.contains("tape  BDZGO W"));
+        }
+
+        #[test]
+        fn commands_change_machine() {
+            let mut lampboard = sample_lampboard();
+            assert!(type_in(&mut lampboard, ":rings bbb\n:plug ab\n:pos adu\n"));
+            assert_eq!(lampboard.enigma.ring_settings(), "BBB");
+            assert_eq!(lampboard.enigma.rotor_positions(), "ADU");
+            assert_eq!(lampboard.enigma.plugboard().pairs(), vec![('A', 'B')]);
+            assert!(lampboard.render().contains("plugs AB"));
+
+            assert!(type_in(&mut lampboard, "x:unplug b\n:clear\n"));
+            assert!(lampboard.enigma.plugboard().pairs().is_empty());
+            assert!(lampboard.tape.is_empty());
+            assert_eq!(lampboard.status, LAMPBOARD_HELP);
+        }
+
+        #[test]
+        fn command_being_typed_is_shown() {
+            let mut lampboard = sample_lampboard();
+            assert!(type_in(&mut lampboard, ":pos abz\x7fc"));
+            assert!(lampboard.render().ends_with(":pos abc"));
+            // escape drops the command, keys are pressed again
+            assert!(type_in(&mut lampboard, "\x1ba"));
+            assert_eq!(lampboard.enigma.rotor_positions(), "AAB");
+        }
+
+        #[test]
+        fn escape_sequences_are_not_keys() {
+            let mut lampboard = sample_lampboard();
+            assert!(type_in(&mut lampboard, "a"));
+            // arrow up, cursor and function keys
+            assert!(type_in(&mut lampboard, "\x1b[A\x1bOD\x1b[1;5C\x1b[15~"));
+            assert_eq!(lampboard.enigma.rotor_positions(), "AAB");
+            assert_eq!(lampboard.tape, "B");
+            // drop a command being typed like escape alone does, without pressing a key
+            assert!(type_in(&mut lampboard, ":pos\x1b[D"));
+            assert_eq!(lampboard.command, None);
+            assert!(type_in(&mut lampboard, "b"));
+            assert_eq!(lampboard.enigma.rotor_positions(), "AAC");
+        }
+
+        #[test]
+        fn invalid_commands_are_reported() {
+            let mut lampboard = sample_lampboard();
+            assert!(type_in(&mut lampboard, ":pos ab\n"));
+            assert_eq!(
+                lampboard.status,
+                "Rotor positions must consist of 3 characters, got 2"
+            );
+            assert!(type_in(&mut lampboard, ":plug abc\n"));
+            assert_eq!(lampboard.status, "Plug must consist of 2 characters, got 3");
+            assert!(type_in(&mut lampboard, ":wheels\n"));
+            assert_eq!(lampboard.status, "Unknown command: wheels");
+            assert_eq!(lampboard.enigma.rotor_positions(), "AAA");
+        }
+
+        #[test]
+        fn quits_on_command_or_end_of_input() {
+            assert!(!type_in(&mut sample_lampboard(), "ab:quit\n"));
+            assert!(!type_in(&mut sample_lampboard(), "ab\x04"));
+            assert!(!type_in(&mut sample_lampboard(), "ab\x03"));
+            assert!(!type_in(&mut sample_lampboard(), ":pos\x03"));
+        }
+    }
+
     mod cli_arguments {
         use super::*;
 
@@ -350,6 +996,8 @@ mod tests {
                 Arguments {
                     allow_cli_questions: true,
                     use_sample: false,
+                    config: None,
+                    permissive: false,
                     reflector: None,
                     rotor_left: None,
                     rotor_middle: None,
@@ -357,7 +1005,8 @@ mod tests {
                     plugboard_connections: vec![],
                     basic_position: None,
                     message_key: None,
-                    message: None
+                    message: None,
+                    command: None
                 }
             )
         }
@@ -369,6 +1018,8 @@ mod tests {
                 Arguments {
                     allow_cli_questions: true,
                     use_sample: true,
+                    config: None,
+                    permissive: false,
                     reflector: None,
                     rotor_left: None,
                     rotor_middle: None,
@@ -376,11 +1027,172 @@ mod tests {
                     plugboard_connections: vec![],
                     basic_position: None,
                     message_key: None,
-                    message: None
+                    message: None,
+                    command: None
                 }
             )
         }
 
+        #[test]
+        fn stats_command_does_not_require_encoding_parameters() {
+            let arguments = Arguments::from_iter(&[
+                "test",
+                "stats",
+                "--ciphertext",
+                "QWERT ZUIOP",
+                "--crib",
+                "WETTER",
+                "--crib-position",
+                "2",
+            ]);
+            assert_eq!(
+                arguments.command,
+                Some(Command::Stats {
+                    ciphertext: String::from("QWERT ZUIOP"),
+                    crib: Some(String::from("WETTER")),
+                    crib_position: Some(2),
+                })
+            );
+        }
+
+        #[test]
+        fn encrypt_command_uses_enigma_parts_from_other_arguments() {
+            let arguments = Arguments::from_iter(&[
+                "test",
+                "--use-sample",
+                "encrypt",
+                "--input",
+                "-",
+                "--output",
+                "encrypted.txt",
+                "--position",
+                "HIB",
+            ]);
+            assert!(arguments.use_sample);
+            assert_eq!(
+                arguments.command,
+                Some(Command::Encrypt {
+                    input: String::from("-"),
+                    output: String::from("encrypted.txt"),
+                    position: String::from("HIB"),
+                    non_letters: NonLetterPolicy::PassThrough,
+                })
+            );
+        }
+
+        #[test]
+        fn typex_command_does_not_require_enigma_parts() {
+            let arguments = Arguments::from_iter(&[
+                "test",
+                "typex",
+                "--rotors",
+                "A B CR D E",
+                "--position",
+                "ABCDE",
+                "--input",
+                "-",
+                "--output",
+                "-",
+            ]);
+            assert_eq!(
+                arguments.command,
+                Some(Command::Typex {
+                    rotors: String::from("A B CR D E"),
+                    rings: String::from("AAAAA"),
+                    position: String::from("ABCDE"),
+                    input: String::from("-"),
+                    output: String::from("-"),
+                    non_letters: NonLetterPolicy::PassThrough,
+                })
+            );
+            assert!(build_typex("A B CR D E", "AAAAA").is_ok());
+            assert_eq!(
+                build_typex("A B CR D", "AAAAA").err(),
+                Some(enigma::Error::InvalidLength {
+                    subject: "Typex rotors",
+                    expected: 5,
+                    actual: 4
+                })
+            );
+            assert_eq!(
+                build_typex("A B CR D I", "AAAAA").err(),
+                Some(enigma::Error::UnknownRotor("I".into()))
+            );
+        }
+
+        #[test]
+        fn keyspace_command() {
+            let arguments = Arguments::from_iter(&[
+                "test", "keyspace", "--model", "I", "--rotors", "I,II,III", "--ukw-d",
+            ]);
+            assert_eq!(
+                arguments.command,
+                Some(Command::Keyspace {
+                    model: String::from("I"),
+                    rotors: vec![String::from("I"), String::from("II"), String::from("III")],
+                    cables: None,
+                    ukw_d: true,
+                    uhr: false,
+                })
+            );
+            let rotors = vec![String::from("I"), String::from("II"), String::from("III")];
+            let counted = keyspace("I", &rotors, Some(0), false, false).unwrap();
+            assert_eq!(
+                counted.total().to_string(),
+                (6 * 3 * 676 * 17576).to_string()
+            );
+            assert_eq!(
+                keyspace("Z", &[], None, false, false).err(),
+                Some(enigma::Error::UnknownModel("Z".into()))
+            );
+        }
+
+        #[test]
+        fn config_replaces_enigma_parts() {
+            let arguments = Arguments::from_iter(&[
+                "test",
+                "--config",
+                "machine.json",
+                "encrypt",
+                "--input",
+                "-",
+                "--output",
+                "-",
+                "--position",
+                "HIB",
+            ]);
+            assert_eq!(arguments.config, Some(String::from("machine.json")));
+            assert!(Arguments::from_iter_safe(&[
+                "test",
+                "--config",
+                "machine.json",
+                "--use-sample",
+                "--basic-position",
+                "AAA",
+                "--message-key",
+                "BBB",
+                "--message",
+                "CCC",
+            ])
+            .is_err());
+
+            let arguments = Arguments::from_iter(&[
+                "test",
+                "save-config",
+                "--setting",
+                "B-321-AZH-ADU",
+                "--output",
+                "machine.json",
+            ]);
+            assert_eq!(
+                arguments.command,
+                Some(Command::SaveConfig {
+                    setting: String::from("B-321-AZH-ADU"),
+                    output: String::from("machine.json"),
+                })
+            );
+        }
+
         #[test]
         fn using_sample_enigma_requires_providing_encoding_data_but_no_separate_enigma_parts() {
             let no_whitespaces = |s: String| s.split_whitespace().collect::<String>();
@@ -405,32 +1217,11 @@ mod tests {
 }
 
 const REFLECTOR_PARSER: fn(&str) -> Result<Reflector, String> = |input: &str| {
-    if input.eq("A") {
-        Ok(Reflector::a())
-    } else if input.eq("B") {
-        Ok(Reflector::b())
-    } else if input.eq("C") {
-        Ok(Reflector::c())
-    } else {
-        Err(format!("Unsupported reflector type: {}", input))
-    }
+    Reflector::by_name(input).ok_or_else(|| format!("Unsupported reflector type: {}", input))
 };
 
-const ROTOR_PARSER: fn(&str) -> Result<Rotor, String> = |input: &str| {
-    if input.eq("I") {
-        Ok(Rotor::enigma_i_wehrmacht_i())
-    } else if input.eq("II") {
-        Ok(Rotor::enigma_i_wehrmacht_ii())
-    } else if input.eq("III") {
-        Ok(Rotor::enigma_i_wehrmacht_iii())
-    } else if input.eq("IV") {
-        Ok(Rotor::m3_wehrmacht_iv())
-    } else if input.eq("V") {
-        Ok(Rotor::m3_wehrmacht_v())
-    } else {
-        Err(format!("Unsupported rotor type: {}", input))
-    }
-};
+const ROTOR_PARSER: fn(&str) -> Result<Rotor, String> =
+    |input: &str| Rotor::by_name(input).ok_or_else(|| format!("Unsupported rotor type: {}", input));
 
 #[derive(PartialEq, Debug)]
 enum PlugboardConnectionOption {
@@ -443,7 +1234,9 @@ const PLUGBOARD_CONNECTION_PARSER: fn(&str) -> Result<PlugboardConnectionOption,
         if input.chars().count() == 0 {
             Ok(PlugboardConnectionOption::None)
         } else {
-            PlugboardConnection::create(input).map(|con| PlugboardConnectionOption::Existing(con))
+            PlugboardConnection::create(input)
+                .map(PlugboardConnectionOption::Existing)
+                .map_err(|e| e.to_string())
         }
     };
 
@@ -494,13 +1287,11 @@ const SPECIFIC_COUNT_OF_SUPPORTED_ALPHABET_CHARACTERS_PARSER: fn(
     }
 };
 
-const BASIC_POSITION_PARSER: fn(&str) -> Result<String, String> = |input: &str| {
-    return SPECIFIC_COUNT_OF_SUPPORTED_ALPHABET_CHARACTERS_PARSER(input, 3);
-};
+const BASIC_POSITION_PARSER: fn(&str) -> Result<String, String> =
+    |input: &str| SPECIFIC_COUNT_OF_SUPPORTED_ALPHABET_CHARACTERS_PARSER(input, 3);
 
-const MESSAGE_KEY_PARSER: fn(&str) -> Result<String, String> = |input: &str| {
-    return SPECIFIC_COUNT_OF_SUPPORTED_ALPHABET_CHARACTERS_PARSER(input, 3);
-};
+const MESSAGE_KEY_PARSER: fn(&str) -> Result<String, String> =
+    |input: &str| SPECIFIC_COUNT_OF_SUPPORTED_ALPHABET_CHARACTERS_PARSER(input, 3);
 
 const MESSAGE_PARSER: fn(&str) -> Result<String, String> = |input: &str| {
     let mut errors = vec![];
@@ -546,7 +1337,14 @@ where
     println!("| write 'exit' to quit");
     loop {
         let mut v = String::new();
-        io::stdin().read_line(&mut v).expect("Failed to read line");
+        match io::stdin().read_line(&mut v) {
+            Ok(0) => exit_with(
+                EXIT_DATA_ERROR,
+                format!("No value provided for: {}", title_to_print),
+            ),
+            Ok(_) => {}
+            Err(err) => exit_with(EXIT_IO_ERROR, err),
+        }
         let v = v.trim();
         if v.eq("exit") {
             process::exit(0);
@@ -563,7 +1361,7 @@ where
 }
 
 #[derive(StructOpt, PartialEq, Debug)]
-#[structopt(name = "Enigma")]
+#[structopt(name = "Enigma", setting = AppSettings::SubcommandsNegateReqs)]
 struct Arguments {
     #[structopt(
         long = "allow-cli-questions",
@@ -577,10 +1375,23 @@ struct Arguments {
     )]
     use_sample: bool,
 
+    #[structopt(
+        long = "config",
+        help = "Machine described in a JSON file written by the save-config command, used as a base for overriding parameters",
+        conflicts_with = "use-sample"
+    )]
+    config: Option<String>,
+
+    #[structopt(
+        long = "permissive",
+        help = "Builds Enigma which could not be set up with the real wheels, for example with the same rotor in two slots"
+    )]
+    permissive: bool,
+
     #[structopt(
 		long="reflector",
 		help="Reflector - allowed values: A, B, C",
-		required_unless_one(&["use-sample", "allow-cli-questions"]),
+		required_unless_one(&["use-sample", "config", "allow-cli-questions"]),
 		parse(try_from_str=REFLECTOR_PARSER)
 	)]
     reflector: Option<Reflector>,
@@ -588,7 +1399,7 @@ struct Arguments {
     #[structopt(
 		long="rotor-left",
 		help="Left rotor - allowed values: I, II, III, IV, V",
-		required_unless_one(&["use-sample", "allow-cli-questions"]),
+		required_unless_one(&["use-sample", "config", "allow-cli-questions"]),
 		parse(try_from_str=ROTOR_PARSER)
 	)]
     rotor_left: Option<Rotor>,
@@ -596,7 +1407,7 @@ struct Arguments {
     #[structopt(
 		long="rotor-middle",
 		help="Middle rotor - allowed values: I, II, III, IV, V",
-		required_unless_one(&["use-sample", "allow-cli-questions"]),
+		required_unless_one(&["use-sample", "config", "allow-cli-questions"]),
 		parse(try_from_str=ROTOR_PARSER)
 	)]
     rotor_middle: Option<Rotor>,
@@ -604,7 +1415,7 @@ struct Arguments {
     #[structopt(
 		long="rotor-right",
 		help="Right rotor - allowed values: I, II, III, IV, V",
-		required_unless_one(&["use-sample", "allow-cli-questions"]),
+		required_unless_one(&["use-sample", "config", "allow-cli-questions"]),
 		parse(try_from_str=ROTOR_PARSER)
 	)]
     rotor_right: Option<Rotor>,
@@ -640,4 +1451,154 @@ struct Arguments {
 		parse(try_from_str=MESSAGE_PARSER)
 	)]
     message: Option<String>,
+
+    #[structopt(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(StructOpt, PartialEq, Debug)]
+enum Command {
+    #[structopt(
+        about = "Prints statistics of a ciphertext: letter frequencies, index of coincidence, repeated n-grams and coincidences for shifts"
+    )]
+    Stats {
+        #[structopt(
+            long = "ciphertext",
+            help = "Ciphertext to analyse, whitespaces are skipped"
+        )]
+        ciphertext: String,
+
+        #[structopt(
+            long = "crib",
+            help = "(Optional) Expected plaintext - positions at which it can be placed are listed"
+        )]
+        crib: Option<String>,
+
+        #[structopt(
+            long = "crib-position",
+            help = "(Optional) Position of the crib if it is known plaintext - checks whether any letter is encrypted to itself",
+            requires = "crib"
+        )]
+        crib_position: Option<usize>,
+    },
+    #[structopt(
+        about = "Encodes a file or standard input of any length with Enigma built from the other arguments, for example: enigma --use-sample encrypt --position HIB --input - --output -"
+    )]
+    Encrypt {
+        #[structopt(long = "input", help = "File to encode, '-' for standard input")]
+        input: String,
+
+        #[structopt(long = "output", help = "File to write, '-' for standard output")]
+        output: String,
+
+        #[structopt(
+            long = "position",
+            help = "Rotor position before the first letter, for example: HIB",
+            parse(try_from_str=MESSAGE_KEY_PARSER)
+        )]
+        position: String,
+
+        #[structopt(
+            long = "non-letters",
+            help = "What to do with characters other than letters - allowed values: skip, pass-through, fail",
+            default_value = "pass-through"
+        )]
+        non_letters: NonLetterPolicy,
+    },
+    #[structopt(
+        about = "Simulates the keyboard and lampboard of Enigma built from the other arguments in the terminal, for example: enigma --use-sample lampboard --position ADU"
+    )]
+    Lampboard {
+        #[structopt(
+            long = "position",
+            help = "Rotor position before the first key press",
+            default_value = "AAA",
+            parse(try_from_str=MESSAGE_KEY_PARSER)
+        )]
+        position: String,
+    },
+    #[structopt(
+        about = "Encodes a file or standard input of any length with Typex, the Enigma arguments are ignored, for example: enigma typex --rotors 'A B CR D E' --position ABCDE --input - --output -"
+    )]
+    Typex {
+        #[structopt(
+            long = "rotors",
+            help = "Five rotors from left to right, the first two are stators - allowed values: A to H, with 'R' appended for a rotor inserted reversed (placeholder wirings, the original ones were never published)"
+        )]
+        rotors: String,
+
+        #[structopt(
+            long = "rings",
+            help = "Ring settings of the five rotors",
+            default_value = "AAAAA"
+        )]
+        rings: String,
+
+        #[structopt(
+            long = "position",
+            help = "Rotor positions before the first letter, for example: ABCDE"
+        )]
+        position: String,
+
+        #[structopt(long = "input", help = "File to encode, '-' for standard input")]
+        input: String,
+
+        #[structopt(long = "output", help = "File to write, '-' for standard output")]
+        output: String,
+
+        #[structopt(
+            long = "non-letters",
+            help = "What to do with characters other than letters - allowed values: skip, pass-through, fail",
+            default_value = "pass-through"
+        )]
+        non_letters: NonLetterPolicy,
+    },
+    #[structopt(
+        about = "Prints the number of keys of an Enigma model, for example: enigma keyspace --model I --rotors I,II,III,IV,V --cables 10"
+    )]
+    Keyspace {
+        #[structopt(
+            long = "model",
+            help = "Model from the registry, for example: I, M3, M4, K"
+        )]
+        model: String,
+
+        #[structopt(
+            long = "rotors",
+            help = "(Optional) Rotors available to pick from, comma separated - all rotors of the model by default",
+            use_delimiter = true
+        )]
+        rotors: Vec<String>,
+
+        #[structopt(
+            long = "cables",
+            help = "(Optional) Number of plugboard cables - 10 for models with a plugboard by default"
+        )]
+        cables: Option<usize>,
+
+        #[structopt(
+            long = "ukw-d",
+            help = "Rewirable reflector UKW-D instead of the fixed ones"
+        )]
+        ukw_d: bool,
+
+        #[structopt(
+            long = "uhr",
+            help = "Uhr connected instead of the 10 plugboard cables"
+        )]
+        uhr: bool,
+    },
+    #[structopt(
+        about = "Writes a machine setting as JSON to be loaded with --config, for example: enigma save-config --setting 'M3 UKW-B III II I rings 01 26 08 stecker AB CD EF pos ADU' --output machine.json"
+    )]
+    SaveConfig {
+        #[structopt(
+            long = "setting",
+            help = "Machine setting in the long or the compact form, for example: B-321-AZH-ADU-AB.CD.EF"
+        )]
+        setting: String,
+
+        #[structopt(long = "output", help = "File to write, '-' for standard output")]
+        output: String,
+    },
 }
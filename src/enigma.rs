@@ -2,10 +2,18 @@
 // reflector = UKW
 // entry disc = ETW
 
+use std::fmt::Display;
+use std::str::FromStr;
+
+use crate::alphabet::Letter;
+use crate::enigma_builder::RotorPlacement;
 use crate::entry_disk::EntryDisk;
+use crate::error::Error;
+use crate::machine::{KeyHistory, Machine};
+use crate::permutation::{MachinePermutations, Permutation};
 use crate::plugboard::Plugboard;
 use crate::reflector::Reflector;
-use crate::rotors::rotor_chain::RotorChain;
+use crate::rotors::rotor_chain::{RotorChain, Stepping};
 
 pub const SUPPORTED_ALPHABET: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
 
@@ -16,11 +24,13 @@ pub struct EncodingResult {
     pub encoded_message: String,
 }
 
+#[derive(Clone)]
 pub struct Enigma {
     plugboard: Plugboard,
     entry_disk: EntryDisk,
     rotor_chain: RotorChain,
     reflector: Reflector,
+    history: KeyHistory,
 }
 
 impl Enigma {
@@ -30,11 +40,13 @@ impl Enigma {
         rotor_chain: RotorChain,
         reflector: Reflector,
     ) -> Enigma {
+        let history = KeyHistory::new(&rotor_chain);
         Enigma {
             plugboard,
             entry_disk,
             rotor_chain,
             reflector,
+            history,
         }
     }
 
@@ -43,44 +55,39 @@ impl Enigma {
         basic_position: String,
         message_key: String,
         message: String,
-    ) -> Result<EncodingResult, String> {
-        let mut message_vector = vec![];
-        let mut message_errors = vec![];
+    ) -> Result<EncodingResult, Error> {
+        let mut msg = String::with_capacity(message.len());
 
-        for c in message.chars() {
+        for (index, c) in message.chars().enumerate() {
             if c.is_whitespace() {
-                message_vector.push(String::from('X'));
+                msg.push('X');
+            } else if c.is_ascii_alphabetic() {
+                msg.push(c.to_ascii_uppercase());
             } else {
                 // TODO part converting to uppercase should be moved to encoding method
                 let uppercase = c.to_uppercase().to_string();
                 if SUPPORTED_ALPHABET.contains(&uppercase) {
-                    message_vector.push(uppercase);
+                    msg.push_str(&uppercase);
                 } else {
-                    message_errors.push(format!("Unsupported character '{}'", c));
+                    return Err(Error::UnsupportedCharacter {
+                        character: c,
+                        index,
+                    });
                 }
             }
         }
 
-        if !message_errors.is_empty() {
-            return Err(message_errors.join(", "));
-        }
-
         // 1. Set rotors to positions of 'basic_position'
-        if let Err(e) = self.rotor_chain.change_setting(&basic_position) {
-            return Err(e);
-        }
+        self.change_rotor_setting(&basic_position)?;
 
         // 2. Encode 'message_key' and read encoded string
-        let encoded_message_key = self.encode_for_current_rotor_setting(&message_key);
+        let encoded_message_key = self.encode_for_current_rotor_setting(&message_key)?;
 
         // 3. Set rotors to positions of 'message_key'
-        if let Err(e) = self.rotor_chain.change_setting(&message_key) {
-            return Err(e);
-        }
+        self.change_rotor_setting(&message_key)?;
 
         // 4. Encode the message using 'message_key' rotor setting
-        let msg = message_vector.join("");
-        let encoded_message = self.encode_for_current_rotor_setting(&msg);
+        let encoded_message = self.encode_for_current_rotor_setting(&msg)?;
 
         // 5. Return required values for printing message
         Ok(EncodingResult {
@@ -91,21 +98,315 @@ impl Enigma {
         })
     }
 
-    fn encode_for_current_rotor_setting(&mut self, msg: &String) -> String {
-        let mut v: Vec<char> = Vec::with_capacity(msg.chars().count());
-        for c in msg.chars() {
-            self.rotor_chain.rotate();
+    pub fn change_rotor_setting(&mut self, setting: &str) -> Result<(), Error> {
+        self.rotor_chain.change_setting(setting)?;
+        self.history.start(&self.rotor_chain);
+        Ok(())
+    }
+
+    /// Ring settings of left, middle and right rotor, changed without taking the rotors out -
+    /// the window letters stay as they are.
+    pub fn change_ring_setting(&mut self, rings: &str) -> Result<(), Error> {
+        self.rotor_chain.change_ring_setting(rings)
+    }
+
+    pub fn ring_settings(&self) -> String {
+        self.rotor_chain.ring_settings()
+    }
+
+    pub fn plugboard(&self) -> &Plugboard {
+        &self.plugboard
+    }
+
+    /// Plugboard to replug between messages, its limits (strictness, number of cables) still
+    /// apply.
+    pub fn plugboard_mut(&mut self) -> &mut Plugboard {
+        &mut self.plugboard
+    }
+
+    pub(crate) fn rotor_chain(&self) -> &RotorChain {
+        &self.rotor_chain
+    }
+
+    /// Window letters of left, middle and right rotor.
+    pub fn rotor_positions(&self) -> String {
+        self.rotor_chain.positions()
+    }
+
+    pub fn snapshot(&self) -> MachineState {
+        MachineState {
+            positions: self.rotor_chain.positions(),
+            key_presses: self.history.key_presses(),
+        }
+    }
+
+    /// Turns the rotors back to the positions from the snapshot. Snapshot of a machine with
+    /// different rotors can be restored too, only positions and the counter are taken from it.
+    pub fn restore(&mut self, state: &MachineState) -> Result<(), Error> {
+        self.rotor_chain.change_setting(&state.positions)?;
+        self.history.restore(&self.rotor_chain, state.key_presses);
+        Ok(())
+    }
+
+    /// Turns the rotors back to the positions before the last key press. Positions are
+    /// replayed from the last rotor setting, so the double step is undone exactly; without
+    /// that history (e.g. after restoring a snapshot) the regular stepping is assumed.
+    pub fn step_back(&mut self) {
+        self.history.step_back(&mut self.rotor_chain);
+    }
+
+    /// Turns the rotors back to the last rotor setting. Without the positions of that setting
+    /// (after restoring a snapshot) the regular stepping is assumed.
+    pub fn reset(&mut self) {
+        self.history.reset(&mut self.rotor_chain);
+    }
+
+    /// Steps the rotors as if `key_presses` keys were pressed, without stepping one by one.
+    pub fn advance(&mut self, key_presses: u64) {
+        self.rotor_chain.advance(key_presses);
+        self.history.pressed(key_presses);
+    }
+
+    pub(crate) fn step(&mut self) {
+        self.rotor_chain.rotate();
+        self.history.pressed(1);
+    }
+
+    /// Steps the rotors and returns the letter lit on the lampboard.
+    pub fn press(&mut self, key: char) -> Result<char, Error> {
+        Enigma::validate_key(key)?;
+        self.step();
+        Ok(self
+            .encode_without_stepping(Letter::from_index(key as u8 - b'A'))
+            .to_char())
+    }
+
+    /// Same as `press`, but also returns how the rotors stepped and which letter left each
+    /// part of the machine.
+    pub fn press_traced(&mut self, key: char) -> Result<PressTrace, Error> {
+        Enigma::validate_key(key)?;
+        let positions_before = self.rotor_chain.positions();
+        let stepping = self.rotor_chain.rotate();
+        self.history.pressed(1);
+        let positions_after = self.rotor_chain.positions();
+
+        let mut path = Vec::with_capacity(11);
+        let lamp = self.encode_index_traced(key as u8 - b'A', |stage, i| {
+            path.push((stage, (b'A' + i) as char))
+        });
+        Ok(PressTrace {
+            key,
+            positions_before,
+            positions_after,
+            stepping,
+            path,
+            lamp: (b'A' + lamp) as char,
+        })
+    }
+
+    /// Permutations of the machine parts for a letter typed when the windows show
+    /// `positions` (after the rotors stepped), without turning the rotors of this machine.
+    pub fn permutations_at(&self, positions: &str) -> Result<MachinePermutations, Error> {
+        let mut rotor_chain = self.rotor_chain.clone();
+        rotor_chain.change_setting(positions)?;
+        let rotors = rotor_chain.rotors();
+        Ok(MachinePermutations {
+            plugboard: Permutation::from_fn(|i| self.plugboard.encode_from_right(i)),
+            entry_disk: Permutation::from_fn(|i| self.entry_disk.encode_from_right(i)),
+            rotors: [0, 1, 2].map(|r| Permutation::from_fn(|i| rotors[r].encode_from_right(i))),
+            reflector: Permutation::from_fn(|i| self.reflector.encode(i)),
+        })
+    }
+
+    pub(crate) fn validate_key(key: char) -> Result<(), Error> {
+        if !key.is_ascii_uppercase() {
+            return Err(Error::UnsupportedCharacter {
+                character: key,
+                index: 0,
+            });
+        }
+        Ok(())
+    }
+
+    pub(crate) fn encode_for_current_rotor_setting(&mut self, msg: &str) -> Result<String, Error> {
+        Error::check_alphabet(msg)?;
+        let mut encoded = String::with_capacity(msg.len());
+        for b in msg.bytes() {
+            self.step();
+            encoded.push(
+                self.encode_without_stepping(Letter::from_index(b - b'A'))
+                    .to_char(),
+            );
+        }
+        Ok(encoded)
+    }
+
+    // encodes single letter for the rotor positions currently visible in the windows
+    pub(crate) fn encode_without_stepping(&self, letter: Letter) -> Letter {
+        Letter::from_index(self.encode_index(letter.index()))
+    }
+
+    fn encode_index(&self, i: u8) -> u8 {
+        let c_encoded = self.plugboard.encode_from_right(i);
+        let c_encoded = self.entry_disk.encode_from_right(c_encoded);
+        let c_encoded = self.rotor_chain.encode_from_right(c_encoded);
+        let c_encoded = self.reflector.encode(c_encoded);
+        let c_encoded = self.rotor_chain.encode_from_left(c_encoded);
+        let c_encoded = self.entry_disk.encode_from_left(c_encoded);
+        self.plugboard.encode_from_left(c_encoded)
+    }
+
+    // `record` is called with the letter leaving each stage
+    fn encode_index_traced<F: FnMut(Stage, u8)>(&self, i: u8, mut record: F) -> u8 {
+        let c_encoded = self.plugboard.encode_from_right(i);
+        record(Stage::Plugboard, c_encoded);
+        let c_encoded = self.entry_disk.encode_from_right(c_encoded);
+        record(Stage::EntryDisk, c_encoded);
+        let c_encoded = self
+            .rotor_chain
+            .encode_from_right_traced(c_encoded, |placement, c| record(Stage::Rotor(placement), c));
+        let c_encoded = self.reflector.encode(c_encoded);
+        record(Stage::Reflector, c_encoded);
+        let c_encoded = self
+            .rotor_chain
+            .encode_from_left_traced(c_encoded, |placement, c| record(Stage::Rotor(placement), c));
+        let c_encoded = self.entry_disk.encode_from_left(c_encoded);
+        record(Stage::EntryDisk, c_encoded);
+        let c_encoded = self.plugboard.encode_from_left(c_encoded);
+        record(Stage::Plugboard, c_encoded);
+        c_encoded
+    }
+}
+
+impl Machine for Enigma {
+    fn press(&mut self, key: char) -> Result<char, Error> {
+        Enigma::press(self, key)
+    }
+
+    fn advance(&mut self, key_presses: u64) {
+        Enigma::advance(self, key_presses)
+    }
+
+    fn step_back(&mut self) {
+        Enigma::step_back(self)
+    }
+
+    fn rotor_positions(&self) -> String {
+        Enigma::rotor_positions(self)
+    }
+
+    fn change_rotor_setting(&mut self, setting: &str) -> Result<(), Error> {
+        Enigma::change_rotor_setting(self, setting)
+    }
+
+    fn snapshot(&self) -> MachineState {
+        Enigma::snapshot(self)
+    }
+
+    fn restore(&mut self, state: &MachineState) -> Result<(), Error> {
+        Enigma::restore(self, state)
+    }
+
+    fn reset(&mut self) {
+        Enigma::reset(self)
+    }
+}
+
+/// Rotor positions and the number of key presses since the rotor setting was changed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MachineState {
+    positions: String,
+    key_presses: u64,
+}
+
+impl MachineState {
+    /// Any number of positions is accepted, the machine checks it when restoring the state.
+    pub fn new(positions: &str, key_presses: u64) -> Result<MachineState, Error> {
+        if positions.is_empty() {
+            return Err(Error::InvalidMachineState(format!(":{}", key_presses)));
+        }
+        Error::check_alphabet(positions)?;
+        Ok(MachineState {
+            positions: positions.into(),
+            key_presses,
+        })
+    }
+
+    /// Window letters of all rotors, from left to right.
+    pub fn positions(&self) -> &str {
+        &self.positions
+    }
+
+    pub fn key_presses(&self) -> u64 {
+        self.key_presses
+    }
+}
+
+// for example 'AEW:3', which can be stored and parsed back
+impl Display for MachineState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.positions, self.key_presses)
+    }
+}
+
+impl FromStr for MachineState {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (positions, key_presses) = s
+            .split_once(':')
+            .ok_or_else(|| Error::InvalidMachineState(s.into()))?;
+        let key_presses = key_presses
+            .parse()
+            .map_err(|_| Error::InvalidKeyPresses(key_presses.into()))?;
+        MachineState::new(positions, key_presses)
+    }
+}
+
+/// Part of the machine the signal passes through.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stage {
+    Plugboard,
+    EntryDisk,
+    Rotor(RotorPlacement),
+    Reflector,
+}
 
-            let c_encoded = self.plugboard.encode_from_right(c);
-            let c_encoded = self.entry_disk.encode_from_right(c_encoded);
-            let c_encoded = self.rotor_chain.encode_from_right(c_encoded);
-            let c_encoded = self.reflector.encode(c_encoded);
-            let c_encoded = self.rotor_chain.encode_from_left(c_encoded);
-            let c_encoded = self.entry_disk.encode_from_left(c_encoded);
-            let c_encoded = self.plugboard.encode_from_left(c_encoded);
-            v.push(c_encoded);
+impl Display for Stage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Stage::Plugboard => f.write_str("plugboard"),
+            Stage::EntryDisk => f.write_str("ETW"),
+            Stage::Rotor(placement) => write!(f, "{} rotor", placement),
+            Stage::Reflector => f.write_str("UKW"),
         }
-        v.iter().collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PressTrace {
+    pub key: char,
+    // window letters of left, middle and right rotor
+    pub positions_before: String,
+    pub positions_after: String,
+    pub stepping: Stepping,
+    // letter leaving each stage, from the key to the lamp (11 stages)
+    pub path: Vec<(Stage, char)>,
+    pub lamp: char,
+}
+
+impl Display for PressTrace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{} -> {}: {}",
+            self.positions_before, self.positions_after, self.stepping
+        )?;
+        write!(f, "key {}", self.key)?;
+        for (stage, c) in &self.path {
+            write!(f, " -> {} {}", stage, c)?;
+        }
+        write!(f, " -> lamp {}", self.lamp)
     }
 }
 
@@ -180,11 +481,405 @@ mod test {
         test_enigma_i(initial_rotor_settings, encoded, decoded);
     }
 
+    #[test]
+    fn test_encoding_with_ring_settings() {
+        init();
+
+        let rotor_chain = RotorChain::new(
+            Rotor::enigma_i_wehrmacht_i()
+                .with_ring_setting('B')
+                .unwrap(),
+            Rotor::enigma_i_wehrmacht_ii()
+                .with_ring_setting('B')
+                .unwrap(),
+            Rotor::enigma_i_wehrmacht_iii()
+                .with_ring_setting('B')
+                .unwrap(),
+        );
+        let mut enigma = Enigma::new(
+            Plugboard::identity(),
+            EntryDisk::identity(),
+            rotor_chain,
+            Reflector::b(),
+        );
+
+        assert_eq!(enigma.change_rotor_setting("AAA"), Ok(()));
+        assert_eq!(
+            enigma.encode_for_current_rotor_setting("AAAAA").unwrap(),
+            "EWTYX"
+        );
+    }
+
+    fn enigma_i(initial_rotor_settings: &str) -> Enigma {
+        let rotor_chain = RotorChain::new(
+            Rotor::enigma_i_wehrmacht_i(),
+            Rotor::enigma_i_wehrmacht_ii(),
+            Rotor::enigma_i_wehrmacht_iii(),
+        );
+        let mut enigma = Enigma::new(
+            Plugboard::identity(),
+            EntryDisk::identity(),
+            rotor_chain,
+            Reflector::b(),
+        );
+        enigma.change_rotor_setting(initial_rotor_settings).unwrap();
+        enigma
+    }
+
+    #[test]
+    fn error_on_unsupported_message_character() {
+        let mut enigma = enigma_i("AAA");
+        let result = enigma.encode("AAA".into(), "BBB".into(), "Hello, world".into());
+        assert_eq!(
+            result.err(),
+            Some(Error::UnsupportedCharacter {
+                character: ',',
+                index: 5
+            })
+        );
+    }
+
+    mod press {
+        use super::*;
+
+        #[test]
+        fn lights_same_lamps_as_encoding() {
+            let mut enigma = enigma_i("AAA");
+            let lamps: String = "AAAAA".chars().map(|c| enigma.press(c).unwrap()).collect();
+            assert_eq!(lamps, "BDZGO");
+        }
+
+        #[test]
+        fn error_on_unsupported_key() {
+            let mut enigma = enigma_i("AAA");
+            assert_eq!(
+                enigma.press('a'),
+                Err(Error::UnsupportedCharacter {
+                    character: 'a',
+                    index: 0
+                })
+            );
+            // rotors did not step
+            assert_eq!(enigma.press('A'), Ok('B'));
+        }
+
+        #[test]
+        fn traces_signal_path() {
+            let mut enigma = enigma_i("AAA");
+            let trace = enigma.press_traced('A').unwrap();
+            assert_eq!(trace.positions_before, "AAA");
+            assert_eq!(trace.positions_after, "AAB");
+            assert_eq!(
+                trace.stepping,
+                Stepping {
+                    left: false,
+                    middle: false,
+                    right: true,
+                    double_step: false
+                }
+            );
+            let path: String = trace.path.iter().map(|(_, c)| c).collect();
+            assert_eq!(path, "AACDFSSEBBB");
+            assert_eq!(trace.path[2].0, Stage::Rotor(RotorPlacement::Right));
+            assert_eq!(trace.path[5].0, Stage::Reflector);
+            assert_eq!(trace.lamp, 'B');
+            assert_eq!(
+                trace.to_string(),
+                "AAA -> AAB: right stepped\n\
+                key A -> plugboard A -> ETW A -> Right rotor C -> Middle rotor D -> Left rotor F \
+                -> UKW S -> Left rotor S -> Middle rotor E -> Right rotor B -> ETW B -> plugboard B \
+                -> lamp B"
+            );
+        }
+
+        #[test]
+        fn traces_double_step() {
+            let mut enigma = enigma_i("ADU");
+            assert_eq!(enigma.press_traced('A').unwrap().positions_after, "ADV");
+
+            let trace = enigma.press_traced('A').unwrap();
+            assert_eq!(trace.positions_after, "AEW");
+            assert!(trace.stepping.middle);
+            assert!(!trace.stepping.double_step);
+
+            let trace = enigma.press_traced('A').unwrap();
+            assert_eq!(trace.positions_after, "BFX");
+            assert_eq!(
+                trace.stepping,
+                Stepping {
+                    left: true,
+                    middle: true,
+                    right: true,
+                    double_step: true
+                }
+            );
+            assert_eq!(
+                trace.stepping.to_string(),
+                "left, middle, right stepped (double step)"
+            );
+        }
+    }
+
+    mod live_changes {
+        use super::*;
+
+        #[test]
+        fn ring_setting_changed_without_rebuilding() {
+            let mut enigma = enigma_i("AAA");
+            assert_eq!(enigma.change_ring_setting("BBB"), Ok(()));
+            assert_eq!(enigma.ring_settings(), "BBB");
+            assert_eq!(enigma.rotor_positions(), "AAA");
+            let lamps: String = "AAAAA".chars().map(|c| enigma.press(c).unwrap()).collect();
+            assert_eq!(lamps, "EWTYX");
+        }
+
+        #[test]
+        fn error_on_invalid_ring_setting() {
+            let mut enigma = enigma_i("AAA");
+            assert!(enigma.change_ring_setting("BB").is_err());
+            assert!(enigma.change_ring_setting("BB1").is_err());
+            assert_eq!(enigma.ring_settings(), "AAA");
+        }
+
+        #[test]
+        fn plugs_changed_between_messages() {
+            let mut enigma = enigma_i("AAA");
+            enigma.plugboard_mut().connect('A', 'B').unwrap();
+            // without plugs 'A' lights 'B', so 'B' entering the rotors leaves them as 'A',
+            // which is plugged to 'B' again
+            assert_eq!(enigma.press('A'), Ok('B'));
+            assert_eq!(enigma.plugboard().pairs(), vec![('A', 'B')]);
+        }
+    }
+
+    mod machine_state {
+        use super::*;
+
+        #[test]
+        fn snapshot_and_restore() {
+            let mut enigma = enigma_i("ADU");
+            enigma.press('A').unwrap();
+            let state = enigma.snapshot();
+            assert_eq!(state, MachineState::new("ADV", 1).unwrap());
+            assert_eq!(enigma.rotor_positions(), "ADV");
+
+            let lamps: String = "AAAA".chars().map(|c| enigma.press(c).unwrap()).collect();
+            assert_eq!(enigma.snapshot(), MachineState::new("BFZ", 5).unwrap());
+
+            enigma.restore(&state).unwrap();
+            assert_eq!(enigma.snapshot(), state);
+            let repeated: String = "AAAA".chars().map(|c| enigma.press(c).unwrap()).collect();
+            assert_eq!(repeated, lamps);
+        }
+
+        #[test]
+        fn changing_rotor_setting_resets_key_presses() {
+            let mut enigma = enigma_i("AAA");
+            enigma.press('A').unwrap();
+            enigma.press_traced('A').unwrap();
+            assert_eq!(enigma.snapshot().key_presses(), 2);
+            enigma.change_rotor_setting("QWE").unwrap();
+            assert_eq!(enigma.snapshot(), MachineState::new("QWE", 0).unwrap());
+        }
+
+        #[test]
+        fn cloned_machine_steps_independently() {
+            let mut enigma = enigma_i("AAA");
+            enigma.press('A').unwrap();
+            let mut cloned = enigma.clone();
+            assert_eq!(cloned.press('A'), Ok('D'));
+            assert_eq!(cloned.rotor_positions(), "AAC");
+            assert_eq!(enigma.rotor_positions(), "AAB");
+            assert_eq!(enigma.press('A'), Ok('D'));
+        }
+
+        #[test]
+        fn text_form() {
+            let state = MachineState::new("AEW", 3).unwrap();
+            assert_eq!(state.to_string(), "AEW:3");
+            assert_eq!("AEW:3".parse(), Ok(state));
+            assert_eq!(
+                "AEW".parse::<MachineState>(),
+                Err(Error::InvalidMachineState("AEW".into()))
+            );
+            assert_eq!(
+                "AEW:x".parse::<MachineState>(),
+                Err(Error::InvalidKeyPresses("x".into()))
+            );
+            assert_eq!(
+                ":3".parse::<MachineState>(),
+                Err(Error::InvalidMachineState(":3".into()))
+            );
+            assert_eq!(
+                MachineState::new("AE1", 0),
+                Err(Error::UnsupportedCharacter {
+                    character: '1',
+                    index: 2
+                })
+            );
+        }
+
+        #[test]
+        fn restoring_state_of_other_machine_fails() {
+            let mut enigma = enigma_i("AAA");
+            assert_eq!(
+                enigma.restore(&"AAAAA:3".parse().unwrap()),
+                Err(Error::InvalidLength {
+                    subject: "Rotor positions",
+                    expected: 3,
+                    actual: 5
+                })
+            );
+        }
+
+        #[test]
+        fn reset_returns_to_rotor_setting() {
+            let mut enigma = enigma_i("ADU");
+            let lamps = enigma.encode_for_current_rotor_setting("HELLO").unwrap();
+            enigma.reset();
+            assert_eq!(enigma.snapshot(), MachineState::new("ADU", 0).unwrap());
+            assert_eq!(enigma.encode_text("HELLO"), Ok(lamps));
+
+            // without history the rotors are stepped back
+            enigma.restore(&"BFX:3".parse().unwrap()).unwrap();
+            enigma.reset();
+            assert_eq!(enigma.snapshot(), MachineState::new("ADU", 0).unwrap());
+        }
+    }
+
+    mod permutations_at {
+        use super::*;
+
+        #[test]
+        fn machine_permutation_gives_lamps() {
+            let enigma = enigma_i("AAA");
+            // "AAAAA" gives "BDZGO" from 'AAA', the rotors step before each letter
+            let lamps: String = ["AAB", "AAC", "AAD", "AAE", "AAF"]
+                .iter()
+                .map(|p| enigma.permutations_at(p).unwrap().machine())
+                .map(|m| m.apply(Letter::new('A').unwrap()).to_char())
+                .collect();
+            assert_eq!(lamps, "BDZGO");
+            assert_eq!(enigma.rotor_positions(), "AAA");
+        }
+
+        #[test]
+        fn parts_of_machine_permutation() {
+            let mut enigma = enigma_i("AAA");
+            enigma.plugboard.connect('A', 'B').unwrap();
+            let permutations = enigma.permutations_at("QEV").unwrap();
+            let machine = permutations.machine();
+            assert!(machine.is_involution());
+            assert_eq!(machine.cycle_type(), vec![2; 13]);
+            assert_eq!(permutations.plugboard.to_cycle_notation(), "(AB)");
+            // plugboard changes the cycles of the scrambler only by renaming letters
+            let scrambler = permutations.reflector.conjugate(&permutations.scrambler());
+            assert_eq!(machine, scrambler.conjugate(&permutations.plugboard));
+            // rotor III turned to 'V' is its wiring with the letters renamed by the shift
+            let shift = Permutation::from_fn(|i| (i + 21) % 26);
+            let wiring = Permutation::new("BDFHJLCPRTXVZNYEIWGAKMUSQO").unwrap();
+            assert_eq!(permutations.rotors[2], wiring.conjugate(&shift));
+        }
+
+        #[test]
+        fn error_on_invalid_positions() {
+            assert!(enigma_i("AAA").permutations_at("AA").is_err());
+        }
+    }
+
+    mod step_back {
+        use super::*;
+
+        #[test]
+        fn undoes_key_presses() {
+            let mut enigma = enigma_i("ADU");
+            let first = enigma.press('H').unwrap();
+            enigma.press('E').unwrap();
+            enigma.press('L').unwrap();
+            assert_eq!(enigma.rotor_positions(), "BFX");
+
+            enigma.step_back();
+            enigma.step_back();
+            assert_eq!(enigma.snapshot(), MachineState::new("ADV", 1).unwrap());
+            enigma.step_back();
+            assert_eq!(enigma.snapshot(), MachineState::new("ADU", 0).unwrap());
+            assert_eq!(enigma.press('H'), Ok(first));
+
+            // without history the machine still steps back
+            enigma.step_back();
+            enigma.step_back();
+            assert_eq!(enigma.snapshot(), MachineState::new("ADT", 0).unwrap());
+        }
+
+        #[test]
+        fn steps_back_past_rotor_setting_and_again_after_press() {
+            let mut enigma = enigma_i("AAA");
+            enigma.step_back();
+            assert_eq!(enigma.rotor_positions(), "AAZ");
+            enigma.press('A').unwrap();
+            assert_eq!(enigma.snapshot(), MachineState::new("AAA", 1).unwrap());
+            enigma.step_back();
+            assert_eq!(enigma.snapshot(), MachineState::new("AAZ", 0).unwrap());
+            enigma.reset();
+            assert_eq!(enigma.rotor_positions(), "AAZ");
+        }
+
+        #[test]
+        fn resets_from_snapshot_with_many_key_presses() {
+            let mut enigma = enigma_i("AAA");
+            let state: MachineState = "ADU:18446744073709551615".parse().unwrap();
+            enigma.restore(&state).unwrap();
+            enigma.reset();
+            let mut expected = enigma.clone();
+            expected.advance(u64::MAX);
+            assert_eq!(expected.rotor_positions(), "ADU");
+        }
+
+        #[test]
+        fn key_presses_saturate() {
+            let mut enigma = enigma_i("AAA");
+            enigma.press('A').unwrap();
+            enigma.advance(u64::MAX);
+            assert_eq!(enigma.snapshot().key_presses(), u64::MAX);
+            let positions = enigma.rotor_positions();
+            enigma.press('A').unwrap();
+            enigma.step_back();
+            assert_eq!(enigma.rotor_positions(), positions);
+        }
+
+        #[test]
+        fn undoes_double_step_of_middle_rotor_set_on_its_notch() {
+            let mut enigma = enigma_i("AEA");
+            enigma.press('A').unwrap();
+            assert_eq!(enigma.rotor_positions(), "BFB");
+            enigma.step_back();
+            assert_eq!(enigma.rotor_positions(), "AEA");
+        }
+
+        #[test]
+        fn advance_jumps_to_crib_position() {
+            let mut enigma = enigma_i("AAA");
+            let encoded = enigma
+                .encode_for_current_rotor_setting(&"A".repeat(70))
+                .unwrap();
+
+            enigma.change_rotor_setting("AAA").unwrap();
+            enigma.advance(60);
+            assert_eq!(enigma.snapshot().key_presses(), 60);
+            assert_eq!(
+                enigma
+                    .encode_for_current_rotor_setting("AAAAAAAAAA")
+                    .unwrap(),
+                encoded[60..]
+            );
+        }
+    }
+
     mod test_encoding_for_custom_plugboard {
         use super::*;
 
         #[test]
-		fn test_1() {
+        fn test_1() {
             let mut plugboard = Plugboard::identity();
             let _ = plugboard.connect('A', 'B');
             let _ = plugboard.connect('E', 'F');
@@ -215,6 +910,36 @@ mod test {
                 "SQBZSUPLCQJPOXVIBYKHHFRZGHKTAGVZPWIMVEESBHRLRGQFCGPPRLXJCBTNQMBQKEETFX",
             );
         }
+
+        #[test]
+        fn key_sheet_plugboard_in_builder() {
+            use crate::enigma_builder::{BuildError, EnigmaBuilder};
+            use crate::plugboard::PlugboardConnection;
+
+            let builder = || {
+                EnigmaBuilder::init()
+                    .rotor_left(Rotor::enigma_i_wehrmacht_i())
+                    .rotor_middle(Rotor::enigma_i_wehrmacht_ii())
+                    .rotor_right(Rotor::enigma_i_wehrmacht_iii())
+                    .reflector(Reflector::b())
+                    .plugboard("AE GL QX".parse().unwrap())
+            };
+            let enigma = builder().build().unwrap();
+            assert_eq!(enigma.plugboard().to_string(), "AE GL QX");
+
+            let result = builder()
+                .plugboard_connections(vec![PlugboardConnection::create("XY").unwrap()])
+                .build();
+            assert_eq!(
+                result.err(),
+                Some(BuildError::Plugboard(Box::new(
+                    Error::LetterAlreadyPlugged {
+                        letter: 'X',
+                        connected_to: 'Q'
+                    }
+                )))
+            );
+        }
     }
 
     fn test_enigma_i(initial_rotor_settings: &str, decoded: &str, encoded: &str) {
@@ -231,11 +956,14 @@ mod test {
         let entry_disk = EntryDisk::identity();
 
         let mut r1 = Rotor::enigma_i_wehrmacht_i();
-        r1.turn_to_character(initial_rotor_settings.chars().nth(0).unwrap());
+        r1.turn_to_character(initial_rotor_settings.chars().next().unwrap())
+            .unwrap();
         let mut r2 = Rotor::enigma_i_wehrmacht_ii();
-        r2.turn_to_character(initial_rotor_settings.chars().nth(1).unwrap());
+        r2.turn_to_character(initial_rotor_settings.chars().nth(1).unwrap())
+            .unwrap();
         let mut r3 = Rotor::enigma_i_wehrmacht_iii();
-        r3.turn_to_character(initial_rotor_settings.chars().nth(2).unwrap());
+        r3.turn_to_character(initial_rotor_settings.chars().nth(2).unwrap())
+            .unwrap();
 
         let rotor_chain = RotorChain::new(r1, r2, r3);
 
@@ -244,7 +972,7 @@ mod test {
         let mut enigma = Enigma::new(plugboard, entry_disk, rotor_chain, reflector);
 
         assert_eq!(
-            enigma.encode_for_current_rotor_setting(&String::from(decoded)),
+            enigma.encode_for_current_rotor_setting(decoded).unwrap(),
             encoded
         );
     }
@@ -2,12 +2,15 @@ use std::fmt::Display;
 
 use crate::enigma::Enigma;
 use crate::entry_disk::EntryDisk;
-use crate::plugboard::{Plugboard, PlugboardConnection};
+pub use crate::error::BuildError;
+use crate::error::Error;
+use crate::model::{Model, SteppingType, WheelKind, WheelSpec};
+use crate::plugboard::{Plugboard, PlugboardConnection, HISTORICAL_MAX_CABLES};
 use crate::reflector::Reflector;
 use crate::rotors::rotor::Rotor;
 use crate::rotors::rotor_chain::RotorChain;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum RotorPlacement {
     Left,
     Middle,
@@ -23,21 +26,17 @@ impl Display for RotorPlacement {
     }
 }
 
-#[derive(Debug)]
-pub enum BuildError {
-    RotorError(RotorPlacement, String),
-    PlugboardError(String),
-    EntryDiskError(String),
-    ReflectorError(String),
-}
-
 pub struct EnigmaBuilder {
     entry_disk: Option<EntryDisk>,
     reflector: Option<Reflector>,
     rotor_left: Option<Rotor>,
     rotor_middle: Option<Rotor>,
     rotor_right: Option<Rotor>,
+    plugboard: Option<Plugboard>,
     plugboard_connections: Vec<PlugboardConnection>,
+    model: Option<&'static Model>,
+    greek_wheel: Option<Rotor>,
+    strict: bool,
 }
 
 impl EnigmaBuilder {
@@ -48,10 +47,31 @@ impl EnigmaBuilder {
             rotor_left: None,
             rotor_middle: None,
             rotor_right: None,
+            plugboard: None,
             plugboard_connections: vec![],
+            model: None,
+            greek_wheel: None,
+            strict: true,
         }
     }
 
+    /// Builds machines which could not be set up with the real wheels, e.g. with the same
+    /// rotor in two slots, a rotor inserted reversed, more than the 10 issued plugboard cables
+    /// or with wheels of another model. By default these are rejected.
+    pub fn permissive(mut self) -> Self {
+        self.strict = false;
+        self
+    }
+
+    /// Uses the entry disk and stepping of the model. Wheels shipped with other models and
+    /// more plugboard cables than the model had are rejected, unless the builder is
+    /// permissive. Wheels with custom wiring are accepted.
+    pub fn model(mut self, model: &'static Model) -> Self {
+        self.entry_disk = Some(model.entry_disk());
+        self.model = Some(model);
+        self
+    }
+
     pub fn entry_disk(mut self, entry_disk: EntryDisk) -> Self {
         self.entry_disk = Some(entry_disk);
         self
@@ -77,6 +97,19 @@ impl EnigmaBuilder {
         self
     }
 
+    /// Thin wheel of M4 between the left rotor and the reflector, it never steps so it
+    /// should be turned to its position before, with `Rotor::with_position`.
+    pub fn greek_wheel(mut self, greek_wheel: Rotor) -> Self {
+        self.greek_wheel = Some(greek_wheel);
+        self
+    }
+
+    /// Plugboard the connections are added to, an empty one if not set.
+    pub fn plugboard(mut self, plugboard: Plugboard) -> Self {
+        self.plugboard = Some(plugboard);
+        self
+    }
+
     pub fn plugboard_connections(
         mut self,
         plugboard_connections: Vec<PlugboardConnection>,
@@ -86,55 +119,333 @@ impl EnigmaBuilder {
     }
 
     pub fn build(&mut self) -> Result<Enigma, BuildError> {
-        if let Err(e) = self.validate_ready_to_build() {
-            return Err(e);
-        }
+        self.validate_ready_to_build()?;
 
-        let mut plugboard = Plugboard::identity();
+        let mut plugboard = self.plugboard.clone().unwrap_or_else(Plugboard::identity);
         for pc in &self.plugboard_connections {
             if let Err(e) = plugboard.connect(pc.left, pc.right) {
-                return Err(BuildError::PlugboardError(e));
+                return Err(BuildError::Plugboard(Box::new(e)));
             }
         }
+        if self.strict {
+            self.validate_configuration(&plugboard)?;
+        }
 
+        let stepping_type = self.model.map_or(SteppingType::Ladder, |m| m.stepping);
         let rotor_chain = RotorChain::new(
             self.rotor_left.take().unwrap(),
             self.rotor_middle.take().unwrap(),
             self.rotor_right.take().unwrap(),
-        );
+        )
+        .with_stepping_type(stepping_type);
+        let mut reflector = self.reflector.take().unwrap();
+        if let Some(greek_wheel) = self.greek_wheel.take() {
+            reflector = reflector.with_greek_wheel(&greek_wheel);
+        }
         Ok(Enigma::new(
             plugboard,
             self.entry_disk.take().unwrap(),
             rotor_chain,
-            self.reflector.take().unwrap(),
+            reflector,
         ))
     }
 
     fn validate_ready_to_build(&self) -> Result<(), BuildError> {
-        if let None = self.rotor_left {
-            return Err(BuildError::RotorError(
-                RotorPlacement::Left,
-                "Left rotor is required".into(),
-            ));
-        }
-        if let None = self.rotor_middle {
-            return Err(BuildError::RotorError(
-                RotorPlacement::Middle,
-                "Middle rotor is required".into(),
-            ));
-        }
-        if let None = self.rotor_right {
-            return Err(BuildError::RotorError(
-                RotorPlacement::Right,
-                "Right rotor is required".into(),
-            ));
-        }
-        if let None = self.entry_disk {
-            return Err(BuildError::EntryDiskError("Entry disk is required".into()));
-        }
-        if let None = self.reflector {
-            return Err(BuildError::ReflectorError("Reflector is required".into()));
+        if self.rotor_left.is_none() {
+            return Err(BuildError::MissingRotor(RotorPlacement::Left));
+        }
+        if self.rotor_middle.is_none() {
+            return Err(BuildError::MissingRotor(RotorPlacement::Middle));
+        }
+        if self.rotor_right.is_none() {
+            return Err(BuildError::MissingRotor(RotorPlacement::Right));
+        }
+        if self.entry_disk.is_none() {
+            return Err(BuildError::MissingEntryDisk);
+        }
+        if self.reflector.is_none() {
+            return Err(BuildError::MissingReflector);
+        }
+        if self.greek_wheel.is_none() && self.model.is_some_and(|m| !m.greek_wheels.is_empty()) {
+            return Err(BuildError::MissingGreekWheel);
+        }
+        Ok(())
+    }
+
+    // checks whether the machine could be set up with the real wheels
+    fn validate_configuration(&self, plugboard: &Plugboard) -> Result<(), BuildError> {
+        let reflector_kind = match self.greek_wheel {
+            Some(_) => WheelKind::ThinReflector,
+            None => WheelKind::Reflector,
+        };
+        let rotor_slots = [
+            ("left rotor", &self.rotor_left),
+            ("middle rotor", &self.rotor_middle),
+            ("right rotor", &self.rotor_right),
+            ("greek wheel", &self.greek_wheel),
+        ];
+        if let Some((slot, _)) = rotor_slots
+            .iter()
+            .find(|(_, rotor)| rotor.as_ref().is_some_and(Rotor::is_reversed))
+        {
+            return Err(BuildError::ReversedWheel { slot });
+        }
+        // only wheels shipped with the library, custom ones can be made in any number
+        let wheels: Vec<(&'static str, &'static WheelSpec)> = rotor_slots
+            .iter()
+            .filter_map(|(slot, rotor)| rotor.as_ref().and_then(Rotor::spec).map(|s| (*slot, s)))
+            .chain(
+                self.reflector
+                    .as_ref()
+                    .and_then(Reflector::spec)
+                    .map(|s| ("reflector", s)),
+            )
+            .collect();
+
+        for (i, (slot, spec)) in wheels.iter().enumerate() {
+            let expected = match *slot {
+                "greek wheel" => WheelKind::Thin,
+                "reflector" => reflector_kind,
+                _ => WheelKind::Rotor,
+            };
+            if spec.kind != expected {
+                return Err(BuildError::WrongWheelKind {
+                    wheel: spec.to_string(),
+                    slot,
+                });
+            }
+            if wheels[..i].iter().any(|(_, other)| other == spec) {
+                return Err(BuildError::DuplicateWheel(spec.to_string()));
+            }
+        }
+
+        // cables issued with the machines, none for models without a plugboard
+        let max = match self.model {
+            Some(model) if !model.plugboard => 0,
+            _ => HISTORICAL_MAX_CABLES,
+        };
+        if plugboard.cables() > max {
+            return Err(BuildError::Plugboard(Box::new(
+                Error::TooManyPlugboardPairs {
+                    max,
+                    actual: plugboard.cables(),
+                },
+            )));
+        }
+
+        let model = match self.model {
+            Some(model) => model,
+            None => return Ok(()),
+        };
+        for (slot, spec) in wheels.iter() {
+            let allowed = match *slot {
+                "greek wheel" => model.greek_wheels,
+                "reflector" => model.reflectors,
+                _ => model.rotors,
+            };
+            if !allowed.contains(spec) {
+                return Err(BuildError::WheelNotInModel {
+                    wheel: spec.to_string(),
+                    model: model.name,
+                });
+            }
+        }
+        let turned = self.reflector.as_ref().is_some_and(|r| r.position() != 'A');
+        if turned && !model.settable_reflector {
+            return Err(BuildError::ReflectorNotSettable { model: model.name });
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn builder(left: &str, middle: &str, right: &str) -> EnigmaBuilder {
+        EnigmaBuilder::init()
+            .rotor_left(Rotor::by_name(left).unwrap())
+            .rotor_middle(Rotor::by_name(middle).unwrap())
+            .rotor_right(Rotor::by_name(right).unwrap())
+            .reflector(Reflector::b())
+    }
+
+    mod strict {
+        use super::*;
+
+        #[test]
+        fn rejects_reversed_rotor() {
+            let reversed = Rotor::by_name("II").unwrap().reversed();
+            assert_eq!(
+                builder("I", "III", "IV")
+                    .rotor_middle(reversed.clone())
+                    .build()
+                    .err(),
+                Some(BuildError::ReversedWheel {
+                    slot: "middle rotor"
+                })
+            );
+            assert!(builder("I", "III", "IV")
+                .rotor_middle(reversed)
+                .permissive()
+                .build()
+                .is_ok());
+        }
+
+        #[test]
+        fn rejects_same_wheel_twice() {
+            assert_eq!(
+                builder("I", "II", "I").build().err(),
+                Some(BuildError::DuplicateWheel("Wehrmacht I".into()))
+            );
+            // wheels with custom wiring are not in the box
+            let wiring = "EKMFLGDQVZNTOWYHXUSPAIBRCJ".parse().unwrap();
+            assert!(builder("I", "II", "III")
+                .rotor_left(Rotor::new(wiring, &[]))
+                .rotor_middle(Rotor::new(wiring, &[]))
+                .build()
+                .is_ok());
+        }
+
+        #[test]
+        fn rejects_wheel_in_wrong_slot() {
+            let m4 = Model::by_name("M4").unwrap();
+            assert_eq!(
+                builder("I", "II", "III")
+                    .rotor_left(m4.greek_wheel("Beta").unwrap())
+                    .build()
+                    .err(),
+                Some(BuildError::WrongWheelKind {
+                    wheel: "M4 Beta".into(),
+                    slot: "left rotor"
+                })
+            );
+            assert_eq!(
+                builder("I", "II", "III")
+                    .greek_wheel(Rotor::by_name("IV").unwrap())
+                    .build()
+                    .err(),
+                Some(BuildError::WrongWheelKind {
+                    wheel: "Wehrmacht IV".into(),
+                    slot: "greek wheel"
+                })
+            );
+        }
+
+        #[test]
+        fn rejects_reflector_not_matching_greek_wheel() {
+            let m4 = Model::by_name("M4").unwrap();
+            assert_eq!(
+                builder("I", "II", "III")
+                    .greek_wheel(m4.greek_wheel("Gamma").unwrap())
+                    .build()
+                    .err(),
+                Some(BuildError::WrongWheelKind {
+                    wheel: "Wehrmacht B".into(),
+                    slot: "reflector"
+                })
+            );
+            assert_eq!(
+                builder("I", "II", "III")
+                    .reflector(m4.reflector("C").unwrap())
+                    .build()
+                    .err(),
+                Some(BuildError::WrongWheelKind {
+                    wheel: "M4 C".into(),
+                    slot: "reflector"
+                })
+            );
+        }
+
+        #[test]
+        fn rejects_more_cables_than_model_has() {
+            let pairs = |pairs: &[&str]| {
+                pairs
+                    .iter()
+                    .map(|p| PlugboardConnection::create(p).unwrap())
+                    .collect::<Vec<_>>()
+            };
+            let eleven = pairs(&[
+                "AB", "CD", "EF", "GH", "IJ", "KL", "MN", "OP", "QR", "ST", "UV",
+            ]);
+            let too_many = Some(BuildError::Plugboard(Box::new(
+                Error::TooManyPlugboardPairs {
+                    max: 10,
+                    actual: 11,
+                },
+            )));
+            assert_eq!(
+                builder("I", "II", "III")
+                    .plugboard_connections(eleven.clone())
+                    .build()
+                    .err(),
+                too_many
+            );
+            assert!(builder("I", "II", "III")
+                .plugboard_connections(eleven.clone())
+                .permissive()
+                .build()
+                .is_ok());
+            assert_eq!(
+                builder("I", "II", "III")
+                    .model(Model::by_name("I").unwrap())
+                    .plugboard_connections(eleven)
+                    .build()
+                    .err(),
+                too_many
+            );
+
+            let k = Model::by_name("K").unwrap();
+            assert_eq!(
+                k.builder()
+                    .rotor_left(k.rotor("I").unwrap())
+                    .rotor_middle(k.rotor("II").unwrap())
+                    .rotor_right(k.rotor("III").unwrap())
+                    .reflector(k.reflector("UKW").unwrap())
+                    .plugboard_connections(pairs(&["AB"]))
+                    .build()
+                    .err(),
+                Some(BuildError::Plugboard(Box::new(
+                    Error::TooManyPlugboardPairs { max: 0, actual: 1 }
+                )))
+            );
+        }
+    }
+
+    #[test]
+    fn reflector_is_turned_only_in_models_with_settable_reflector() {
+        let turned = Reflector::b().with_position('Q').unwrap();
+        assert_eq!(
+            builder("I", "II", "III")
+                .model(Model::by_name("M3").unwrap())
+                .reflector(turned.clone())
+                .build()
+                .err(),
+            Some(BuildError::ReflectorNotSettable { model: "M3" })
+        );
+        assert!(builder("I", "II", "III")
+            .model(Model::by_name("M3").unwrap())
+            .reflector(turned)
+            .permissive()
+            .build()
+            .is_ok());
+    }
+
+    #[test]
+    fn permissive_builds_impossible_machines() {
+        let railway = Model::by_name("Railway").unwrap();
+        let mut enigma = builder("I", "I", "I")
+            .model(Model::by_name("M3").unwrap())
+            .rotor_right(railway.rotor("I").unwrap())
+            .permissive()
+            .build()
+            .unwrap();
+        assert_eq!(
+            enigma
+                .encode_for_current_rotor_setting("AAA")
+                .unwrap()
+                .len(),
+            3
+        );
+    }
+}
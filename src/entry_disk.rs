@@ -1,34 +1,38 @@
+use crate::alphabet::Wiring;
 use crate::enigma::SUPPORTED_ALPHABET;
+use crate::error::Error;
 
+#[derive(Debug, Clone, PartialEq)]
 pub struct EntryDisk {
-    alphabet: &'static str,
+    // contact wired to each key, and the other way round
+    forward: [u8; 26],
+    backward: [u8; 26],
 }
 
 impl EntryDisk {
     pub fn identity() -> EntryDisk {
-        EntryDisk::new(SUPPORTED_ALPHABET)
+        EntryDisk::new(SUPPORTED_ALPHABET).expect("alphabet is a valid wiring")
     }
 
-    fn new(alphabet: &'static str) -> EntryDisk {
-        // TODO panic if alphabets dont contain all same characters
-        EntryDisk { alphabet }
+    /// Entry disk with the keys wired to the contacts 'A', 'B', 'C' and so on, e.g.
+    /// 'QWERTZUIOASDFGHJKPYXCVBNML' of the commercial models. Each letter must be used once.
+    pub fn new(keys: &str) -> Result<EntryDisk, Error> {
+        Wiring::new(keys).map(EntryDisk::from_wiring)
+    }
+
+    pub fn from_wiring(wiring: Wiring) -> EntryDisk {
+        EntryDisk {
+            forward: wiring.inverse(),
+            backward: wiring.forward(),
+        }
     }
 
     pub(crate) fn encode_from_right(&self, i: u8) -> u8 {
-        let character = SUPPORTED_ALPHABET
-            .chars()
-            .nth(i as usize)
-            .expect(&format!("Entry disk contains no character at index {}", i));
-        self.alphabet.find(character).unwrap() as u8
+        self.forward[i as usize]
     }
 
     pub(crate) fn encode_from_left(&self, i: u8) -> u8 {
-        let character = self
-            .alphabet
-            .chars()
-            .nth(i as usize)
-            .expect(&format!("Entry disk contains no character at index {}", i));
-        SUPPORTED_ALPHABET.find(character).unwrap() as u8
+        self.backward[i as usize]
     }
 }
 
@@ -36,6 +40,41 @@ impl EntryDisk {
 mod tests {
     use super::*;
 
+    mod new {
+        use super::*;
+
+        #[test]
+        fn keys_wired_to_contacts() {
+            let disk = EntryDisk::new("QWERTZUIOASDFGHJKPYXCVBNML").unwrap();
+            // key 'Q' is wired to contact 'A'
+            assert_eq!(disk.encode_from_right(16), 0);
+            assert_eq!(disk.encode_from_left(0), 16);
+            assert_eq!(
+                EntryDisk::new(SUPPORTED_ALPHABET),
+                Ok(EntryDisk::identity())
+            );
+        }
+
+        #[test]
+        fn error_on_invalid_wiring() {
+            assert_eq!(
+                EntryDisk::new("QWERTZ"),
+                Err(Error::InvalidLength {
+                    subject: "Wiring",
+                    expected: 26,
+                    actual: 6
+                })
+            );
+            assert_eq!(
+                EntryDisk::new("QWERTZUIOASDFGHJKPYXCVBNMQ"),
+                Err(Error::RepeatedLetter {
+                    letter: 'Q',
+                    index: 25
+                })
+            );
+        }
+    }
+
     mod encode_from_right {
         use super::*;
 
@@ -59,14 +98,6 @@ mod tests {
             let encoded = disk.encode_from_right(25);
             assert_eq!(encoded, 25);
         }
-
-        #[test]
-        #[should_panic(expected = "Entry disk contains no character at index 26")]
-        fn test_illegal_index() {
-            let disk = EntryDisk::identity();
-            let encoded = disk.encode_from_right(26);
-            assert_eq!(encoded, 26);
-        }
     }
 
     mod encode_from_left {
@@ -92,13 +123,5 @@ mod tests {
             let encoded = disk.encode_from_left(25);
             assert_eq!(encoded, 25);
         }
-
-        #[test]
-        #[should_panic(expected = "Entry disk contains no character at index 26")]
-        fn test_illegal_index() {
-            let disk = EntryDisk::identity();
-            let encoded = disk.encode_from_left(26);
-            assert_eq!(encoded, 26);
-        }
     }
 }
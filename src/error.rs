@@ -0,0 +1,297 @@
+use std::fmt::Display;
+
+use crate::enigma::SUPPORTED_ALPHABET;
+use crate::enigma_builder::RotorPlacement;
+
+/// Error returned by the library - the variants carry the offending value and, where it
+/// applies, its index in the input, so callers can match on the kind of the problem.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    UnsupportedCharacter {
+        character: char,
+        index: usize,
+    },
+    // e.g. rotor positions or an indicator of a wrong number of characters
+    InvalidLength {
+        subject: &'static str,
+        expected: usize,
+        actual: usize,
+    },
+    // wiring with a letter used twice, `index` is where it appears again
+    RepeatedLetter {
+        letter: char,
+        index: usize,
+    },
+    // reflector wiring which does not swap letters in pairs
+    ReflectorNotReciprocal {
+        letter: char,
+        index: usize,
+    },
+    InvalidPlugboardPair(String),
+    LetterAlreadyPlugged {
+        letter: char,
+        connected_to: char,
+    },
+    InvalidMachineState(String),
+    // `token` is empty when the setting ended too early
+    InvalidMachineSetting {
+        token: String,
+        expected: &'static str,
+    },
+    UnknownRotor(String),
+    UnknownReflector(String),
+    UnknownModel(String),
+    InvalidKeyPresses(String),
+    UnsupportedNonLetterPolicy(String),
+    NgramLengthOutOfRange(usize),
+    CorpusTooShort {
+        required: usize,
+        actual: usize,
+    },
+    TooManyPlugboardPairs {
+        max: usize,
+        actual: usize,
+    },
+    CribOutOfRange {
+        crib_length: usize,
+        position: usize,
+        ciphertext_length: usize,
+    },
+    SelfEncipherment {
+        letter: char,
+        position: usize,
+    },
+    NoConsistentPlugboard,
+    // constraints of a keyspace which the machine could not be used with
+    UnsupportedKeyspace(String),
+    Build(BuildError),
+}
+
+impl Error {
+    /// Fails on the first character of `text` which is not in the supported alphabet.
+    pub(crate) fn check_alphabet(text: &str) -> Result<(), Error> {
+        match text
+            .chars()
+            .enumerate()
+            .find(|(_, c)| !SUPPORTED_ALPHABET.contains(*c))
+        {
+            Some((index, character)) => Err(Error::UnsupportedCharacter { character, index }),
+            None => Ok(()),
+        }
+    }
+
+    pub(crate) fn check_length(
+        subject: &'static str,
+        text: &str,
+        expected: usize,
+    ) -> Result<(), Error> {
+        let actual = text.chars().count();
+        if actual != expected {
+            return Err(Error::InvalidLength {
+                subject,
+                expected,
+                actual,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::UnsupportedCharacter { character, index } => write!(
+                f,
+                "Character '{}' at index {} is not in supported alphabet: {}",
+                character, index, SUPPORTED_ALPHABET
+            ),
+            Error::InvalidLength {
+                subject,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "{} must consist of {} characters, got {}",
+                subject, expected, actual
+            ),
+            Error::RepeatedLetter { letter, index } => write!(
+                f,
+                "Letter '{}' at index {} is already used in the wiring",
+                letter, index
+            ),
+            Error::ReflectorNotReciprocal { letter, index } => write!(
+                f,
+                "Reflector must connect letters in pairs, but '{}' at index {} is not wired back",
+                letter, index
+            ),
+            Error::InvalidPlugboardPair(pair) => write!(
+                f,
+                "Expected pairs of 2 different letters, but found: {}",
+                pair
+            ),
+            Error::LetterAlreadyPlugged {
+                letter,
+                connected_to,
+            } => write!(
+                f,
+                "Letter '{}' is already connected to '{}'",
+                letter, connected_to
+            ),
+            Error::InvalidMachineState(state) => {
+                write!(f, "Expected machine state like 'AEW:3', got '{}'", state)
+            }
+            Error::InvalidMachineSetting { token, expected } if token.is_empty() => {
+                write!(f, "Expected {} in machine setting, got nothing", expected)
+            }
+            Error::InvalidMachineSetting { token, expected } => write!(
+                f,
+                "Expected {} in machine setting, got '{}'",
+                expected, token
+            ),
+            Error::UnknownRotor(name) => write!(f, "Unsupported rotor type: {}", name),
+            Error::UnknownReflector(name) => write!(f, "Unsupported reflector type: {}", name),
+            Error::UnknownModel(name) => write!(f, "Unsupported Enigma model: {}", name),
+            Error::InvalidKeyPresses(key_presses) => {
+                write!(f, "Expected number of key presses, got '{}'", key_presses)
+            }
+            Error::UnsupportedNonLetterPolicy(policy) => write!(
+                f,
+                "Unsupported non-letter policy: {} (allowed: skip, pass-through, fail)",
+                policy
+            ),
+            Error::NgramLengthOutOfRange(n) => {
+                write!(f, "N-gram length must be from 1 to 4, got {}", n)
+            }
+            Error::CorpusTooShort { required, actual } => write!(
+                f,
+                "Corpus must contain at least {} letters, got {}",
+                required, actual
+            ),
+            Error::TooManyPlugboardPairs { max, actual } => {
+                write!(f, "Plugboard can have at most {} pairs, got {}", max, actual)
+            }
+            Error::CribOutOfRange {
+                crib_length,
+                position,
+                ciphertext_length,
+            } => write!(
+                f,
+                "Crib of {} characters at position {} does not fit ciphertext of {} characters",
+                crib_length, position, ciphertext_length
+            ),
+            Error::SelfEncipherment { letter, position } => write!(
+                f,
+                "Crib letter '{}' at position {} is the same as ciphertext letter, but Enigma never encodes a letter to itself",
+                letter, position
+            ),
+            Error::NoConsistentPlugboard => {
+                f.write_str("No plugboard connections are consistent with the crib")
+            }
+            Error::UnsupportedKeyspace(reason) => {
+                write!(f, "Keyspace can not be computed: {}", reason)
+            }
+            Error::Build(error) => error.fmt(f),
+        }
+    }
+}
+
+// `Build` shows the message of the build error itself, so it is not repeated as the source
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Build(error) => error.source(),
+            _ => None,
+        }
+    }
+}
+
+impl From<BuildError> for Error {
+    fn from(error: BuildError) -> Self {
+        Error::Build(error)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BuildError {
+    MissingRotor(RotorPlacement),
+    MissingEntryDisk,
+    MissingReflector,
+    // M4 needs the thin wheel next to its reflector
+    MissingGreekWheel,
+    Plugboard(Box<Error>),
+    // e.g. 'Railway I' in an M3
+    WheelNotInModel { wheel: String, model: &'static str },
+    // there is only one of each wheel in the box
+    DuplicateWheel(String),
+    // e.g. a thin wheel of M4 as the left rotor
+    WrongWheelKind { wheel: String, slot: &'static str },
+    // reflector turned from 'A' in a model where it is fixed
+    ReflectorNotSettable { model: &'static str },
+    // Enigma wheels fit on the axle only one way round
+    ReversedWheel { slot: &'static str },
+}
+
+impl Display for BuildError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BuildError::MissingRotor(placement) => write!(f, "{} rotor is required", placement),
+            BuildError::MissingEntryDisk => f.write_str("Entry disk is required"),
+            BuildError::MissingReflector => f.write_str("Reflector is required"),
+            BuildError::MissingGreekWheel => f.write_str("Greek wheel is required"),
+            BuildError::Plugboard(error) => write!(f, "Plugboard error: {}", error),
+            BuildError::WheelNotInModel { wheel, model } => {
+                write!(f, "Wheel {} does not belong to Enigma {}", wheel, model)
+            }
+            BuildError::DuplicateWheel(wheel) => {
+                write!(f, "Wheel {} can be used only once", wheel)
+            }
+            BuildError::WrongWheelKind { wheel, slot } => {
+                write!(f, "Wheel {} can not be used as {}", wheel, slot)
+            }
+            BuildError::ReflectorNotSettable { model } => {
+                write!(f, "Reflector of Enigma {} can not be turned", model)
+            }
+            BuildError::ReversedWheel { slot } => {
+                write!(f, "Wheel in {} slot can not be inserted reversed", slot)
+            }
+        }
+    }
+}
+
+// the plugboard error is already part of the message
+impl std::error::Error for BuildError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_first_unsupported_character() {
+        assert_eq!(Error::check_alphabet("ABC"), Ok(()));
+        assert_eq!(
+            Error::check_alphabet("AbC1"),
+            Err(Error::UnsupportedCharacter {
+                character: 'b',
+                index: 1
+            })
+        );
+    }
+
+    #[test]
+    fn wrapped_error_is_shown_only_once() {
+        let error: Error = BuildError::MissingRotor(RotorPlacement::Middle).into();
+        assert_eq!(error.to_string(), "Middle rotor is required");
+        assert!(std::error::Error::source(&error).is_none());
+
+        let error: Error = BuildError::Plugboard(Box::new(Error::TooManyPlugboardPairs {
+            max: 10,
+            actual: 11,
+        }))
+        .into();
+        assert_eq!(
+            error.to_string(),
+            "Plugboard error: Plugboard can have at most 10 pairs, got 11"
+        );
+        assert!(std::error::Error::source(&error).is_none());
+    }
+}
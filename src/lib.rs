@@ -1,13 +1,32 @@
+pub mod alphabet;
+pub mod analysis;
 pub mod enigma;
 pub mod enigma_builder;
 pub mod entry_disk;
+pub mod error;
+pub mod machine;
+pub mod machine_setting;
+pub mod model;
+pub mod permutation;
 pub mod plugboard;
 pub mod reflector;
 pub mod rotors;
+#[cfg(feature = "serde")]
+mod serde_support;
+pub mod stream;
+pub mod typex;
 
-pub use self::enigma::{EncodingResult, Enigma};
-pub use self::enigma_builder::{BuildError, EnigmaBuilder};
+pub use self::alphabet::{Letter, Wiring};
+pub use self::enigma::{EncodingResult, Enigma, MachineState, PressTrace};
+pub use self::enigma_builder::EnigmaBuilder;
 pub use self::entry_disk::EntryDisk;
-pub use self::plugboard::PlugboardConnection;
+pub use self::error::{BuildError, Error};
+pub use self::machine::Machine;
+pub use self::machine_setting::MachineSetting;
+pub use self::model::{Model, SteppingType, WheelSpec};
+pub use self::permutation::{MachinePermutations, Permutation};
+pub use self::plugboard::{Plugboard, PlugboardConnection};
 pub use self::reflector::Reflector;
 pub use self::rotors::rotor::Rotor;
+pub use self::stream::NonLetterPolicy;
+pub use self::typex::Typex;
@@ -0,0 +1,113 @@
+// Operations common to the rotor machines of the library, so that tools like the stream
+// encoding work with any of them.
+
+use crate::enigma::MachineState;
+use crate::error::Error;
+use crate::rotors::rotor_chain::RotorChain;
+
+/// Rotor machine with a keyboard and a lampboard, e.g. `Enigma` or `Typex`.
+pub trait Machine {
+    /// Steps the rotors and returns the letter lit on the lampboard.
+    fn press(&mut self, key: char) -> Result<char, Error>;
+
+    /// Steps the rotors as if `key_presses` keys were pressed.
+    fn advance(&mut self, key_presses: u64);
+
+    /// Turns the rotors back to the positions before the last key press.
+    fn step_back(&mut self);
+
+    /// Window letters of all rotors, from left to right.
+    fn rotor_positions(&self) -> String;
+
+    fn change_rotor_setting(&mut self, setting: &str) -> Result<(), Error>;
+
+    fn snapshot(&self) -> MachineState;
+
+    fn restore(&mut self, state: &MachineState) -> Result<(), Error>;
+
+    /// Turns the rotors back to the last rotor setting.
+    fn reset(&mut self);
+
+    /// Presses the keys of `text`, which must consist of the supported letters only.
+    fn encode_text(&mut self, text: &str) -> Result<String, Error> {
+        Error::check_alphabet(text)?;
+        text.chars().map(|key| self.press(key)).collect()
+    }
+}
+
+/// Key presses since the rotor setting was last changed, so that the stepping rotors can be
+/// turned back exactly - the double step cannot be undone from the positions alone.
+#[derive(Clone)]
+pub(crate) struct KeyHistory {
+    key_presses: u64,
+    // positions before the first of `key_presses`, unknown after restoring a snapshot
+    origin: Option<String>,
+}
+
+impl KeyHistory {
+    pub(crate) fn new(rotor_chain: &RotorChain) -> KeyHistory {
+        KeyHistory {
+            key_presses: 0,
+            origin: Some(rotor_chain.positions()),
+        }
+    }
+
+    pub(crate) fn key_presses(&self) -> u64 {
+        self.key_presses
+    }
+
+    /// The counter saturates, the origin is forgotten then as it can no longer be replayed.
+    pub(crate) fn pressed(&mut self, key_presses: u64) {
+        self.key_presses = self
+            .key_presses
+            .checked_add(key_presses)
+            .unwrap_or_else(|| {
+                self.origin = None;
+                u64::MAX
+            });
+    }
+
+    /// Starts over from the current positions, after the rotor setting was changed.
+    pub(crate) fn start(&mut self, rotor_chain: &RotorChain) {
+        self.key_presses = 0;
+        self.origin = Some(rotor_chain.positions());
+    }
+
+    /// Positions before the presses are known only if there are none.
+    pub(crate) fn restore(&mut self, rotor_chain: &RotorChain, key_presses: u64) {
+        self.key_presses = key_presses;
+        self.origin = if key_presses == 0 {
+            Some(rotor_chain.positions())
+        } else {
+            None
+        };
+    }
+
+    /// Positions are replayed from the origin; without it the regular stepping is assumed.
+    pub(crate) fn step_back(&mut self, rotor_chain: &mut RotorChain) {
+        match (&self.origin, self.key_presses) {
+            // before the last rotor setting, which is then moved here
+            (_, 0) => {
+                rotor_chain.rotate_back();
+                self.origin = Some(rotor_chain.positions());
+            }
+            (Some(origin), key_presses) => {
+                rotor_chain.change_setting(origin).unwrap();
+                rotor_chain.advance(key_presses - 1);
+                self.key_presses -= 1;
+            }
+            (None, _) => {
+                rotor_chain.rotate_back();
+                self.key_presses -= 1;
+            }
+        }
+    }
+
+    pub(crate) fn reset(&mut self, rotor_chain: &mut RotorChain) {
+        match self.origin.take() {
+            Some(origin) => rotor_chain.change_setting(&origin).unwrap(),
+            None => rotor_chain.rewind(self.key_presses),
+        }
+        self.start(rotor_chain);
+    }
+}
@@ -0,0 +1,672 @@
+// Whole machine setup in one line of text, e.g.
+//   M3 UKW-B III II I rings 01 26 08 stecker AB CD EF pos ADU
+// with the greek wheel of M4 as the first of four wheels, e.g.
+//   M4 UKW-B Beta II IV I rings 01 01 01 22 stecker AT BL pos VJNA
+// or in the compact form used by other simulators (reflector, wheel order, rings, positions
+// and plugboard pairs)
+//   B-321-AZH-ADU-AB.CD.EF
+// Wheels with custom wiring are written as the wiring, followed by the turnover letters
+// after '/', e.g. 'EKMFLGDQVZNTOWYHXUSPAIBRCJ/Q'.
+
+use std::fmt::Display;
+use std::str::FromStr;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::alphabet::{Letter, Wiring};
+use crate::enigma::Enigma;
+use crate::enigma_builder::EnigmaBuilder;
+use crate::error::Error;
+use crate::model::Model;
+use crate::plugboard::Plugboard;
+use crate::reflector::Reflector;
+use crate::rotors::rotor::Rotor;
+
+const ROMAN_NUMERALS: [&str; 9] = ["I", "II", "III", "IV", "V", "VI", "VII", "VIII", "IX"];
+
+/// Wheel or reflector of a setting - a built-in one by name, or a custom wiring.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(untagged))]
+pub enum Wheel {
+    Named(String),
+    Wired {
+        wiring: Wiring,
+        // not used for reflectors
+        #[cfg_attr(feature = "serde", serde(default))]
+        turnover: Vec<Letter>,
+    },
+}
+
+impl Wheel {
+    // names are looked up among the wheels of the model, if there is one
+    fn rotor(&self, model: Option<&Model>) -> Result<Rotor, Error> {
+        match self {
+            Wheel::Named(name) => match model {
+                Some(model) => model.rotor(name),
+                None => Rotor::by_name(name),
+            }
+            .ok_or_else(|| Error::UnknownRotor(name.clone())),
+            Wheel::Wired { wiring, turnover } => Ok(Rotor::new(*wiring, turnover)),
+        }
+    }
+
+    fn reflector(&self, model: Option<&Model>) -> Result<Reflector, Error> {
+        match self {
+            Wheel::Named(name) => match model {
+                Some(model) => model.reflector(name),
+                None => Reflector::by_name(name),
+            }
+            .ok_or_else(|| Error::UnknownReflector(name.clone())),
+            Wheel::Wired { wiring, .. } => Reflector::new(*wiring),
+        }
+    }
+
+    fn greek_wheel(&self, model: Option<&Model>) -> Result<Rotor, Error> {
+        match self {
+            Wheel::Named(name) => model
+                .and_then(|model| model.greek_wheel(name))
+                .ok_or_else(|| Error::UnknownRotor(name.clone())),
+            Wheel::Wired { wiring, .. } => Ok(Rotor::new(*wiring, &[])),
+        }
+    }
+
+    fn name(&self) -> Option<&str> {
+        match self {
+            Wheel::Named(name) => Some(name),
+            Wheel::Wired { .. } => None,
+        }
+    }
+}
+
+impl Display for Wheel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Wheel::Named(name) => f.write_str(name),
+            Wheel::Wired { wiring, turnover } if turnover.is_empty() => write!(f, "{}", wiring),
+            Wheel::Wired { wiring, turnover } => write!(f, "{}/{}", wiring, letters(turnover)),
+        }
+    }
+}
+
+/// Name, or the wiring (26 letters) with optional turnover letters after '/'.
+impl FromStr for Wheel {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if !s.contains('/') && s.chars().count() != 26 {
+            return Ok(Wheel::Named(s.into()));
+        }
+        let (wiring, turnover) = s.split_once('/').unwrap_or((s, ""));
+        Error::check_alphabet(turnover)?;
+        Ok(Wheel::Wired {
+            wiring: wiring.parse()?,
+            turnover: turnover
+                .chars()
+                .map(Letter::new)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+impl PartialEq<&str> for Wheel {
+    fn eq(&self, other: &&str) -> bool {
+        self.name() == Some(*other)
+    }
+}
+
+/// Thin wheel of M4 next to the reflector, with its ring setting and position.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct GreekWheel {
+    pub wheel: Wheel,
+    pub ring: Letter,
+    pub position: Letter,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MachineSetting {
+    // e.g. 'M3', not part of the compact form; wheels are looked up among the wheels of
+    // the model if it is given
+    pub model: Option<String>,
+    pub reflector: Wheel,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub greek_wheel: Option<GreekWheel>,
+    // left, middle and right wheel, e.g. 'III'
+    pub rotors: [Wheel; 3],
+    pub rings: [Letter; 3],
+    pub positions: [Letter; 3],
+    pub plugboard: Plugboard,
+}
+
+impl MachineSetting {
+    /// Builder with the wheels set to the ring settings and positions.
+    pub fn builder(&self) -> Result<EnigmaBuilder, Error> {
+        let model = match &self.model {
+            Some(name) => {
+                Some(Model::by_name(name).ok_or_else(|| Error::UnknownModel(name.clone()))?)
+            }
+            None => None,
+        };
+        let reflector = self.reflector.reflector(model)?;
+        let mut rotors = Vec::with_capacity(3);
+        for ((wheel, ring), position) in self
+            .rotors
+            .iter()
+            .zip(self.rings.iter())
+            .zip(self.positions.iter())
+        {
+            let mut rotor = wheel.rotor(model)?.with_ring_setting(ring.to_char())?;
+            rotor.turn_to_character(position.to_char())?;
+            rotors.push(rotor);
+        }
+        let right = rotors.pop().unwrap();
+        let middle = rotors.pop().unwrap();
+        let left = rotors.pop().unwrap();
+        let mut builder = match model {
+            Some(model) => model.builder(),
+            None => EnigmaBuilder::init(),
+        };
+        if let Some(greek) = &self.greek_wheel {
+            let rotor = greek
+                .wheel
+                .greek_wheel(model)?
+                .with_ring_setting(greek.ring.to_char())?
+                .with_position(greek.position.to_char())?;
+            builder = builder.greek_wheel(rotor);
+        }
+        Ok(builder
+            .reflector(reflector)
+            .rotor_left(left)
+            .rotor_middle(middle)
+            .rotor_right(right)
+            .plugboard(self.plugboard.clone()))
+    }
+
+    pub fn build(&self) -> Result<Enigma, Error> {
+        Ok(self.builder()?.build()?)
+    }
+
+    /// Compact form like 'B-321-AZH-ADU-AB.CD.EF', only for named wheels numbered I to IX
+    /// and without the greek wheel.
+    pub fn to_compact(&self) -> Option<String> {
+        if self.greek_wheel.is_some() {
+            return None;
+        }
+        let mut wheels = String::with_capacity(3);
+        for wheel in self.rotors.iter() {
+            let name = wheel.name()?;
+            let number = ROMAN_NUMERALS.iter().position(|n| *n == name)?;
+            wheels.push_str(&(number + 1).to_string());
+        }
+        let mut compact = format!(
+            "{}-{}-{}-{}",
+            self.reflector.name()?,
+            wheels,
+            letters(&self.rings),
+            letters(&self.positions)
+        );
+        if self.plugboard.cables() > 0 {
+            compact.push('-');
+            compact.push_str(&self.plugboard.to_string().replace(' ', "."));
+        }
+        Some(compact)
+    }
+
+    fn parse_long(s: &str) -> Result<MachineSetting, Error> {
+        let mut tokens = s.split_whitespace().peekable();
+        let model = match tokens.peek() {
+            Some(token) if !token.starts_with("UKW-") => tokens.next().map(String::from),
+            _ => None,
+        };
+        let reflector = expect_token(tokens.next(), "reflector like 'UKW-B'")?;
+        let reflector = reflector
+            .strip_prefix("UKW-")
+            .filter(|r| !r.is_empty())
+            .ok_or_else(|| invalid(reflector, "reflector like 'UKW-B'"))?;
+        let mut rotors: Vec<Wheel> = Vec::with_capacity(4);
+        for _ in 0..3 {
+            rotors.push(expect_token(tokens.next(), "wheel name like 'III'")?.parse()?);
+        }
+        // the first of four wheels is the greek wheel
+        if let Some(wheel) = tokens.next_if(|token| *token != "rings") {
+            rotors.push(wheel.parse()?);
+        }
+        expect_keyword(tokens.next(), "rings")?;
+        let mut rings = Vec::with_capacity(rotors.len());
+        for _ in 0..rotors.len() {
+            rings.push(ring_setting(expect_token(
+                tokens.next(),
+                "ring setting from 01 to 26",
+            )?)?);
+        }
+        let mut plugboard = Plugboard::identity().strict();
+        let mut token = expect_token(tokens.next(), "'stecker' or 'pos'")?;
+        if token == "stecker" {
+            let mut pairs = vec![];
+            token = loop {
+                match expect_token(tokens.next(), "'pos'")? {
+                    "pos" => break "pos",
+                    pair => pairs.push(pair),
+                }
+            };
+            plugboard = pairs.join(" ").parse()?;
+        }
+        expect_keyword(Some(token), "pos")?;
+        let positions = expect_token(tokens.next(), "rotor positions")?;
+        Error::check_length("Rotor positions", positions, rotors.len())?;
+        Error::check_alphabet(positions)?;
+        if let Some(extra) = tokens.next() {
+            return Err(invalid(extra, "end of the setting"));
+        }
+        let mut positions: Vec<Letter> = positions
+            .chars()
+            .map(Letter::new)
+            .collect::<Result<_, _>>()?;
+        let greek_wheel = if rotors.len() == 4 {
+            Some(GreekWheel {
+                wheel: rotors.remove(0),
+                ring: rings.remove(0),
+                position: positions.remove(0),
+            })
+        } else {
+            None
+        };
+        Ok(MachineSetting {
+            model,
+            reflector: reflector.parse()?,
+            greek_wheel,
+            rotors: [rotors[0].clone(), rotors[1].clone(), rotors[2].clone()],
+            rings: [rings[0], rings[1], rings[2]],
+            positions: [positions[0], positions[1], positions[2]],
+            plugboard,
+        })
+    }
+
+    fn parse_compact(s: &str) -> Result<MachineSetting, Error> {
+        let parts: Vec<&str> = s.split('-').collect();
+        if parts.len() < 4 || parts.len() > 5 {
+            return Err(invalid(s, "setting like 'B-321-AZH-ADU-AB.CD.EF'"));
+        }
+        if parts[1].chars().count() != 3 {
+            return Err(invalid(parts[1], "wheel order like '321'"));
+        }
+        let mut rotors = Vec::with_capacity(3);
+        for c in parts[1].chars() {
+            let name = c
+                .to_digit(10)
+                .filter(|d| *d > 0)
+                .map(|d| ROMAN_NUMERALS[d as usize - 1])
+                .ok_or_else(|| invalid(parts[1], "wheel order like '321'"))?;
+            rotors.push(Wheel::Named(name.into()));
+        }
+        let plugboard = match parts.get(4) {
+            Some(pairs) => pairs.replace('.', " ").parse()?,
+            None => Plugboard::identity().strict(),
+        };
+        Ok(MachineSetting {
+            model: None,
+            reflector: Wheel::Named(parts[0].into()),
+            greek_wheel: None,
+            rotors: [rotors[0].clone(), rotors[1].clone(), rotors[2].clone()],
+            rings: three_letters(parts[2])?,
+            positions: three_letters(parts[3])?,
+            plugboard,
+        })
+    }
+}
+
+/// Long form, e.g. 'M3 UKW-B III II I rings 01 26 08 stecker AB CD EF pos ADU'.
+impl Display for MachineSetting {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(model) = &self.model {
+            write!(f, "{} ", model)?;
+        }
+        write!(f, "UKW-{}", self.reflector)?;
+        let mut rings = self.rings.to_vec();
+        let mut positions = self.positions.to_vec();
+        if let Some(greek) = &self.greek_wheel {
+            write!(f, " {}", greek.wheel)?;
+            rings.insert(0, greek.ring);
+            positions.insert(0, greek.position);
+        }
+        for rotor in self.rotors.iter() {
+            write!(f, " {}", rotor)?;
+        }
+        f.write_str(" rings")?;
+        for ring in rings {
+            write!(f, " {:02}", ring.index() + 1)?;
+        }
+        if self.plugboard.cables() > 0 {
+            write!(f, " stecker {}", self.plugboard)?;
+        }
+        write!(f, " pos {}", letters(&positions))
+    }
+}
+
+/// Accepts both the long and the compact form.
+impl FromStr for MachineSetting {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.contains(char::is_whitespace) {
+            MachineSetting::parse_long(s)
+        } else {
+            MachineSetting::parse_compact(s)
+        }
+    }
+}
+
+impl FromStr for EnigmaBuilder {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse::<MachineSetting>()?.builder()
+    }
+}
+
+fn letters(letters: &[Letter]) -> String {
+    letters.iter().map(|l| l.to_char()).collect()
+}
+
+fn three_letters(s: &str) -> Result<[Letter; 3], Error> {
+    Error::check_length("Rotor positions", s, 3)?;
+    Error::check_alphabet(s)?;
+    let mut letters = [Letter::from_index(0); 3];
+    for (letter, c) in letters.iter_mut().zip(s.chars()) {
+        *letter = Letter::new(c)?;
+    }
+    Ok(letters)
+}
+
+// '01' to '26' (or the letter)
+fn ring_setting(token: &str) -> Result<Letter, Error> {
+    if let Ok(number) = token.parse::<u8>() {
+        if (1..=26).contains(&number) {
+            return Ok(Letter::from_index(number - 1));
+        }
+    }
+    let mut chars = token.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_ascii_uppercase() => Letter::new(c),
+        _ => Err(invalid(token, "ring setting from 01 to 26")),
+    }
+}
+
+fn expect_token<'a>(token: Option<&'a str>, expected: &'static str) -> Result<&'a str, Error> {
+    token.ok_or(Error::InvalidMachineSetting {
+        token: String::new(),
+        expected,
+    })
+}
+
+fn expect_keyword(token: Option<&str>, keyword: &'static str) -> Result<(), Error> {
+    match token {
+        Some(token) if token == keyword => Ok(()),
+        token => Err(invalid(token.unwrap_or_default(), keyword)),
+    }
+}
+
+fn invalid(token: &str, expected: &'static str) -> Error {
+    Error::InvalidMachineSetting {
+        token: token.into(),
+        expected,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enigma_builder::BuildError;
+
+    const LONG: &str = "M3 UKW-B III II I rings 01 26 08 stecker AB CD EF pos ADU";
+
+    #[test]
+    fn long_form_round_trips() {
+        let setting: MachineSetting = LONG.parse().unwrap();
+        assert_eq!(setting.model.as_deref(), Some("M3"));
+        assert_eq!(setting.reflector, "B");
+        assert_eq!(setting.rotors, ["III", "II", "I"]);
+        assert_eq!(letters(&setting.rings), "AZH");
+        assert_eq!(letters(&setting.positions), "ADU");
+        assert_eq!(setting.plugboard.to_string(), "AB CD EF");
+        assert_eq!(setting.to_string(), LONG);
+
+        let without_plugboard = "UKW-C V IV I rings 02 02 02 pos ZZZ";
+        assert_eq!(
+            without_plugboard
+                .parse::<MachineSetting>()
+                .unwrap()
+                .to_string(),
+            without_plugboard
+        );
+    }
+
+    #[test]
+    fn compact_form_round_trips() {
+        let setting: MachineSetting = "B-321-AZH-ADU-AB.CD.EF".parse().unwrap();
+        let long: MachineSetting = LONG.parse().unwrap();
+        assert_eq!(
+            setting,
+            MachineSetting {
+                model: None,
+                ..long
+            }
+        );
+        assert_eq!(
+            setting.to_compact().as_deref(),
+            Some("B-321-AZH-ADU-AB.CD.EF")
+        );
+        assert_eq!(
+            "C-541-BBB-QEV"
+                .parse::<MachineSetting>()
+                .unwrap()
+                .to_compact()
+                .as_deref(),
+            Some("C-541-BBB-QEV")
+        );
+    }
+
+    #[test]
+    fn custom_wiring_round_trips() {
+        let custom = "UKW-YRUHQSLDPXNGOKMIEBFZCWVJAT I II BDFHJLCPRTXVZNYEIWGAKMUSQO/V rings 01 01 01 pos AAA";
+        let setting: MachineSetting = custom.parse().unwrap();
+        assert_eq!(setting.to_string(), custom);
+        assert_eq!(setting.to_compact(), None);
+        assert_eq!(
+            setting
+                .build()
+                .unwrap()
+                .encode_for_current_rotor_setting("AAAAA"),
+            Ok("BDZGO".into())
+        );
+        assert_eq!(
+            "UKW-B I II ABC/Q rings 01 01 01 pos AAA".parse::<MachineSetting>(),
+            Err(Error::InvalidLength {
+                subject: "Wiring",
+                expected: 26,
+                actual: 3
+            })
+        );
+    }
+
+    #[test]
+    fn wheels_are_looked_up_in_model() {
+        let railway = Model::by_name("Railway").unwrap();
+        let mut expected = railway
+            .builder()
+            .rotor_left(railway.rotor("III").unwrap())
+            .rotor_middle(railway.rotor("II").unwrap())
+            .rotor_right(railway.rotor("I").unwrap())
+            .reflector(railway.reflector("UKW").unwrap())
+            .build()
+            .unwrap();
+        expected.change_rotor_setting("AAA").unwrap();
+        let mut enigma = "Railway UKW-UKW III II I rings 01 01 01 pos AAA"
+            .parse::<MachineSetting>()
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(
+            enigma.encode_for_current_rotor_setting("HELLOWORLD"),
+            expected.encode_for_current_rotor_setting("HELLOWORLD")
+        );
+    }
+
+    #[test]
+    fn builds_same_machine_as_builder() {
+        let mut from_setting: Enigma = "B-123-AAA-AAA"
+            .parse::<MachineSetting>()
+            .unwrap()
+            .build()
+            .unwrap();
+        let mut built = EnigmaBuilder::init()
+            .rotor_left(Rotor::enigma_i_wehrmacht_i())
+            .rotor_middle(Rotor::enigma_i_wehrmacht_ii())
+            .rotor_right(Rotor::enigma_i_wehrmacht_iii())
+            .reflector(Reflector::b())
+            .build()
+            .unwrap();
+        built.change_rotor_setting("AAA").unwrap();
+        let plaintext = "AAAAAAAAAA";
+        assert_eq!(
+            from_setting.encode_for_current_rotor_setting(plaintext),
+            built.encode_for_current_rotor_setting(plaintext)
+        );
+
+        let mut enigma = "UKW-B I II III rings 01 01 01 pos AAA"
+            .parse::<EnigmaBuilder>()
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(enigma.rotor_positions(), "AAA");
+        assert_eq!(
+            enigma.encode_for_current_rotor_setting("AAAAA").unwrap(),
+            "BDZGO"
+        );
+    }
+
+    #[test]
+    fn ring_settings_and_plugboard_change_encoding() {
+        // example message from the 1930 Enigma I manual
+        let mut enigma = "UKW-A II I III rings 24 13 22 stecker AM FI NV PS TU WZ pos ABL"
+            .parse::<MachineSetting>()
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(
+            enigma
+                .encode_for_current_rotor_setting("GCDSEAHUGWTQGRKVLFGXUCALXVYMIGMMNMFDXTGNVHVRMMEVOUYFZSLRHDRRXFJWCFHUHMUNZEFRDISIKBGPMYVXUZ")
+                .unwrap(),
+            "FEINDLIQEINFANTERIEKOLONNEBEOBAQTETXANFANGSUEDAUSGANGBAERWALDEXENDEDREIKMOSTWAERTSNEUSTADT"
+        );
+    }
+
+    #[test]
+    fn greek_wheel_of_m4() {
+        // message of U-534 from 1945, settings and ciphertext as published by
+        // Dirk Rijmenants, Cipher Machines and Cryptology
+        let m4 = "M4 UKW-B Beta II IV I rings 01 01 01 22 stecker AT BL DF GJ HM NW OP QY RZ VX pos VJNA";
+        let setting: MachineSetting = m4.parse().unwrap();
+        assert_eq!(setting.to_string(), m4);
+        assert_eq!(setting.to_compact(), None);
+        let greek_wheel = setting.greek_wheel.as_ref().unwrap();
+        assert_eq!(greek_wheel.wheel, "Beta");
+        assert_eq!(greek_wheel.position.to_char(), 'V');
+        assert_eq!(setting.rotors, ["II", "IV", "I"]);
+        assert_eq!(letters(&setting.positions), "JNA");
+
+        let mut enigma = setting.build().unwrap();
+        assert_eq!(
+            enigma.encode_for_current_rotor_setting("NCZWVUSXPNYMINHZXMQXSFWXWLKJAHSHNMCOCCAKUQPMKCSMHKSEINJUSBLKIOSXCKUBHMLLXCSJUSRRDVKOHULXWCCBGVLIYXEOAHXRHKKFVDREWEZLXOBAFGYUJQUKGRTVUKAMEURBVEKSUHHVOYHABCJWMAKLFKLMYFVNRIZRVVRTKOFDANJMOLBGFFLEOPRGTFLVRHOWOPBEKVWMUQFMPWPARMFHAGKXIIBG").unwrap(),
+            "VONVONJLOOKSJHFFTTTEINSEINSDREIZWOYYQNNSNEUNINHALTXXBEIANGRIFFUNTERWASSERGEDRUECKTYWABOSXLETZTERGEGNERSTANDNULACHTDREINULUHRMARQUANTONJOTANEUNACHTSEYHSDREIYZWOZWONULGRADYACHTSMYSTOSSENACHXEKNSVIERMBFAELLTYNNNNNNOOOVIERYSICHTEINSNULL"
+        );
+
+        // M4 without its greek wheel, or a greek wheel in a machine without one
+        assert_eq!(
+            "M4 UKW-B II IV I rings 01 01 01 pos AAA"
+                .parse::<MachineSetting>()
+                .unwrap()
+                .build()
+                .err(),
+            Some(Error::Build(BuildError::MissingGreekWheel))
+        );
+        assert_eq!(
+            "UKW-B Beta II IV I rings 01 01 01 01 pos AAAA"
+                .parse::<MachineSetting>()
+                .unwrap()
+                .build()
+                .err(),
+            Some(Error::UnknownRotor("Beta".into()))
+        );
+        assert_eq!(
+            "M4 UKW-B Beta II IV I rings 01 01 01 01 pos AAA".parse::<MachineSetting>(),
+            Err(Error::InvalidLength {
+                subject: "Rotor positions",
+                expected: 4,
+                actual: 3
+            })
+        );
+    }
+
+    #[test]
+    fn errors_point_at_invalid_part() {
+        assert_eq!(
+            "M3 UKW-B III II I rings 01 27 08 pos ADU".parse::<MachineSetting>(),
+            Err(Error::InvalidMachineSetting {
+                token: "27".into(),
+                expected: "ring setting from 01 to 26"
+            })
+        );
+        assert_eq!(
+            "M3 UKW-B III II I rings 01 02 08 stecker AB BC pos ADU".parse::<MachineSetting>(),
+            Err(Error::LetterAlreadyPlugged {
+                letter: 'B',
+                connected_to: 'A'
+            })
+        );
+        assert_eq!(
+            "M3 UKW-B III II I rings 01 02 08".parse::<MachineSetting>(),
+            Err(Error::InvalidMachineSetting {
+                token: "".into(),
+                expected: "'stecker' or 'pos'"
+            })
+        );
+        assert_eq!(
+            "B-3X1-AAA-AAA".parse::<MachineSetting>(),
+            Err(Error::InvalidMachineSetting {
+                token: "3X1".into(),
+                expected: "wheel order like '321'"
+            })
+        );
+        assert_eq!(
+            "UKW-Q I II III rings 01 01 01 pos AAA"
+                .parse::<MachineSetting>()
+                .unwrap()
+                .build()
+                .err(),
+            Some(Error::UnknownReflector("Q".into()))
+        );
+        assert_eq!(
+            "M3 UKW-A I II III rings 01 01 01 pos AAA"
+                .parse::<MachineSetting>()
+                .unwrap()
+                .build()
+                .err(),
+            Some(Error::UnknownReflector("A".into()))
+        );
+        assert_eq!(
+            "Z UKW-A I II III rings 01 01 01 pos AAA"
+                .parse::<MachineSetting>()
+                .unwrap()
+                .build()
+                .err(),
+            Some(Error::UnknownModel("Z".into()))
+        );
+    }
+}
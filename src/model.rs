@@ -0,0 +1,667 @@
+// Registry of historical Enigma models and the wheels shipped with them. Wirings are given
+// as the letters wired to 'A', 'B', 'C' and so on, turnovers as the letters in the window
+// when the next wheel is stepped.
+
+use std::fmt::Display;
+
+use crate::enigma_builder::EnigmaBuilder;
+use crate::entry_disk::EntryDisk;
+use crate::reflector::Reflector;
+use crate::rotors::rotor::Rotor;
+
+/// Wheel shipped with the library. `set` tells apart wheels with the same name, e.g.
+/// rotor I of Enigma I and rotor I of the Railway Enigma.
+#[derive(Debug, PartialEq, Eq)]
+pub struct WheelSpec {
+    pub set: &'static str,
+    pub name: &'static str,
+    pub kind: WheelKind,
+    pub wiring: &'static str,
+    // empty for reflectors and thin wheels, which never step
+    pub turnover: &'static str,
+}
+
+/// Slot a wheel fits in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WheelKind {
+    Rotor,
+    // greek wheel of M4, next to the reflector
+    Thin,
+    Reflector,
+    // reflector of M4, used only together with a thin wheel
+    ThinReflector,
+}
+
+impl Display for WheelSpec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.set, self.name)
+    }
+}
+
+/// How the rotors are moved on a key press.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SteppingType {
+    // pawls and notches, with the double step of the middle rotor
+    Ladder,
+    // cog wheels like an odometer (Enigma G), a rotor steps only when the one to its right
+    // leaves a notch; the reflector of G is turned by the left rotor too, which is not
+    // modelled
+    Gear,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Model {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub rotors: &'static [WheelSpec],
+    // thin wheels next to the reflector, only in Enigma M4
+    pub greek_wheels: &'static [WheelSpec],
+    pub reflectors: &'static [WheelSpec],
+    // keys wired to the contacts 'A', 'B', 'C' and so on
+    pub entry_disk: &'static str,
+    pub stepping: SteppingType,
+    pub plugboard: bool,
+    // reflector can be turned to any position (see `Reflector::with_position`)
+    pub settable_reflector: bool,
+}
+
+impl Model {
+    /// Model by its name (case is ignored), e.g. 'M3' or 'Swiss-K'.
+    pub fn by_name(name: &str) -> Option<&'static Model> {
+        MODELS.iter().find(|m| m.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn rotor(&self, name: &str) -> Option<Rotor> {
+        find(self.rotors, name).map(Rotor::from_spec)
+    }
+
+    pub fn greek_wheel(&self, name: &str) -> Option<Rotor> {
+        find(self.greek_wheels, name).map(Rotor::from_spec)
+    }
+
+    pub fn reflector(&self, name: &str) -> Option<Reflector> {
+        find(self.reflectors, name).map(Reflector::from_spec)
+    }
+
+    pub fn entry_disk(&self) -> EntryDisk {
+        EntryDisk::new(self.entry_disk).expect("built-in entry disk wiring is valid")
+    }
+
+    /// Builder with the entry disk and stepping of the model, which accepts only the wheels
+    /// of this model.
+    pub fn builder(&'static self) -> EnigmaBuilder {
+        EnigmaBuilder::init().model(self)
+    }
+}
+
+impl Display for Model {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Enigma {}", self.name)
+    }
+}
+
+pub(crate) fn find(wheels: &'static [WheelSpec], name: &str) -> Option<&'static WheelSpec> {
+    wheels.iter().find(|w| w.name == name)
+}
+
+pub static MODELS: [Model; 10] = [
+    Model {
+        name: "I",
+        description: "Army and Air Force Enigma (Heer, Luftwaffe)",
+        rotors: split_at(&WEHRMACHT_ROTORS, 5).0,
+        greek_wheels: &[],
+        reflectors: &WEHRMACHT_REFLECTORS,
+        entry_disk: ALPHABETICAL,
+        stepping: SteppingType::Ladder,
+        plugboard: true,
+        settable_reflector: false,
+    },
+    Model {
+        name: "M3",
+        description: "Navy Enigma (Kriegsmarine)",
+        rotors: &WEHRMACHT_ROTORS,
+        greek_wheels: &[],
+        reflectors: split_at(&WEHRMACHT_REFLECTORS, 1).1,
+        entry_disk: ALPHABETICAL,
+        stepping: SteppingType::Ladder,
+        plugboard: true,
+        settable_reflector: false,
+    },
+    Model {
+        name: "M4",
+        description: "Navy Enigma with the fourth, thin wheel (U-boats)",
+        rotors: &WEHRMACHT_ROTORS,
+        greek_wheels: &GREEK_WHEELS,
+        reflectors: &THIN_REFLECTORS,
+        entry_disk: ALPHABETICAL,
+        stepping: SteppingType::Ladder,
+        plugboard: true,
+        settable_reflector: false,
+    },
+    Model {
+        name: "D",
+        description: "Commercial Enigma",
+        rotors: &COMMERCIAL_ROTORS,
+        greek_wheels: &[],
+        reflectors: &COMMERCIAL_REFLECTORS,
+        entry_disk: QWERTZ,
+        stepping: SteppingType::Ladder,
+        plugboard: false,
+        settable_reflector: true,
+    },
+    Model {
+        name: "K",
+        description: "Commercial Enigma",
+        rotors: &COMMERCIAL_ROTORS,
+        greek_wheels: &[],
+        reflectors: &COMMERCIAL_REFLECTORS,
+        entry_disk: QWERTZ,
+        stepping: SteppingType::Ladder,
+        plugboard: false,
+        settable_reflector: true,
+    },
+    Model {
+        name: "G",
+        description: "Counter Enigma of the military intelligence (Abwehr), G-312",
+        rotors: &ABWEHR_ROTORS,
+        greek_wheels: &[],
+        reflectors: &ABWEHR_REFLECTORS,
+        entry_disk: QWERTZ,
+        stepping: SteppingType::Gear,
+        plugboard: false,
+        settable_reflector: true,
+    },
+    Model {
+        name: "Railway",
+        description: "Enigma K of the German railway (Reichsbahn)",
+        rotors: &RAILWAY_ROTORS,
+        greek_wheels: &[],
+        reflectors: &RAILWAY_REFLECTORS,
+        entry_disk: QWERTZ,
+        stepping: SteppingType::Ladder,
+        plugboard: false,
+        settable_reflector: true,
+    },
+    Model {
+        name: "Swiss-K",
+        description: "Enigma K of the Swiss Army and Air Force",
+        rotors: &SWISS_K_ROTORS,
+        greek_wheels: &[],
+        reflectors: &SWISS_K_REFLECTORS,
+        entry_disk: QWERTZ,
+        stepping: SteppingType::Ladder,
+        plugboard: false,
+        settable_reflector: true,
+    },
+    Model {
+        name: "T",
+        description: "Enigma K for the Japanese Navy (Tirpitz)",
+        rotors: &TIRPITZ_ROTORS,
+        greek_wheels: &[],
+        reflectors: &TIRPITZ_REFLECTORS,
+        entry_disk: TIRPITZ_ENTRY_DISK,
+        stepping: SteppingType::Ladder,
+        plugboard: false,
+        settable_reflector: true,
+    },
+    Model {
+        name: "Norway",
+        description: "Enigma I rewired for the Norwegian police after the war (Norenigma)",
+        rotors: &NORWAY_ROTORS,
+        greek_wheels: &[],
+        reflectors: &NORWAY_REFLECTORS,
+        entry_disk: ALPHABETICAL,
+        stepping: SteppingType::Ladder,
+        plugboard: true,
+        settable_reflector: false,
+    },
+];
+
+// slices of the same wheel set for models which got only some of the wheels
+const fn split_at(
+    wheels: &'static [WheelSpec],
+    mid: usize,
+) -> (&'static [WheelSpec], &'static [WheelSpec]) {
+    wheels.split_at(mid)
+}
+
+const ALPHABETICAL: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const QWERTZ: &str = "QWERTZUIOASDFGHJKPYXCVBNML";
+const TIRPITZ_ENTRY_DISK: &str = "KZROUQHYAIGBLWVSTDXFPNMCJE";
+
+const fn rotor(
+    set: &'static str,
+    name: &'static str,
+    wiring: &'static str,
+    turnover: &'static str,
+) -> WheelSpec {
+    WheelSpec {
+        set,
+        name,
+        kind: WheelKind::Rotor,
+        wiring,
+        turnover,
+    }
+}
+
+const fn reflector(set: &'static str, name: &'static str, wiring: &'static str) -> WheelSpec {
+    fixed(WheelKind::Reflector, set, name, wiring)
+}
+
+const fn thin_wheel(set: &'static str, name: &'static str, wiring: &'static str) -> WheelSpec {
+    fixed(WheelKind::Thin, set, name, wiring)
+}
+
+const fn thin_reflector(set: &'static str, name: &'static str, wiring: &'static str) -> WheelSpec {
+    fixed(WheelKind::ThinReflector, set, name, wiring)
+}
+
+// wheels which never step
+const fn fixed(
+    kind: WheelKind,
+    set: &'static str,
+    name: &'static str,
+    wiring: &'static str,
+) -> WheelSpec {
+    WheelSpec {
+        set,
+        name,
+        kind,
+        wiring,
+        turnover: "",
+    }
+}
+
+// ---- names: Heer, Enigma A, Heeres, Wehrmacht, Service Enigma, Army/GAF machine
+// rotors VI to VIII were used only by the Navy
+pub(crate) static WEHRMACHT_ROTORS: [WheelSpec; 8] = [
+    rotor("Wehrmacht", "I", "EKMFLGDQVZNTOWYHXUSPAIBRCJ", "Q"),
+    rotor("Wehrmacht", "II", "AJDKSIRUXBLHWTMCQGZNPYFVOE", "E"),
+    rotor("Wehrmacht", "III", "BDFHJLCPRTXVZNYEIWGAKMUSQO", "V"),
+    rotor("Wehrmacht", "IV", "ESOVPZJAYQUIRHXLNFTGKDCMWB", "J"),
+    rotor("Wehrmacht", "V", "VZBRGITYUPSDNHLXAWMJQOFECK", "Z"),
+    rotor("Wehrmacht", "VI", "JPGVOUMFYQBENHZRDKASXLICTW", "ZM"),
+    rotor("Wehrmacht", "VII", "NZJHGRCXMYSWBOUFAIVLPEKQDT", "ZM"),
+    rotor("Wehrmacht", "VIII", "FKQHTLXOCBJSPDZRAMEWNIUYGV", "ZM"),
+];
+pub(crate) static WEHRMACHT_REFLECTORS: [WheelSpec; 3] = [
+    reflector("Wehrmacht", "A", "EJMZALYXVBWFCRQUONTSPIKHGD"),
+    reflector("Wehrmacht", "B", "YRUHQSLDPXNGOKMIEBFZCWVJAT"),
+    reflector("Wehrmacht", "C", "FVPJIAOYEDRZXWGCTKUQSBNMHL"),
+];
+static GREEK_WHEELS: [WheelSpec; 2] = [
+    thin_wheel("M4", "Beta", "LEYJVCNIXWPBQMDRTAKZGFUHOS"),
+    thin_wheel("M4", "Gamma", "FSOKANUERHMBTIYCWLQPZXVGJD"),
+];
+static THIN_REFLECTORS: [WheelSpec; 2] = [
+    thin_reflector("M4", "B", "ENKQAUYWJICOPBLMDXZVFTHRGS"),
+    thin_reflector("M4", "C", "RDOBJNTKVEHMLFCWZAXGYIPSUQ"),
+];
+// ----
+
+static COMMERCIAL_ROTORS: [WheelSpec; 3] = [
+    rotor("Commercial", "I", "LPGSZMHAEOQKVXRFYBUTNICJDW", "Y"),
+    rotor("Commercial", "II", "SLVGBTFXJQOHEWIRZYAMKPCNDU", "E"),
+    rotor("Commercial", "III", "CJGDPSHKTURAWZXFMYNQOBVLIE", "N"),
+];
+static COMMERCIAL_REFLECTORS: [WheelSpec; 1] =
+    [reflector("Commercial", "UKW", "IMETCGFRAYSQBZXWLHKDVUPOJN")];
+
+static ABWEHR_ROTORS: [WheelSpec; 3] = [
+    rotor(
+        "Abwehr",
+        "I",
+        "DMTWSILRUYQNKFEJCAZBPGXOHV",
+        "SUVWZABCEFGIKLOPQ",
+    ),
+    rotor(
+        "Abwehr",
+        "II",
+        "HQZGPJTMOBLNCIFDYAWVEUSRKX",
+        "STVYZACDFGHKMNQ",
+    ),
+    rotor("Abwehr", "III", "UQNTLSZFMREHDPXKIBVYGJCWOA", "UWXAEFHKMNR"),
+];
+static ABWEHR_REFLECTORS: [WheelSpec; 1] =
+    [reflector("Abwehr", "UKW", "RULQMZJSYGOCETKWDAHNBXPVIF")];
+
+static RAILWAY_ROTORS: [WheelSpec; 3] = [
+    rotor("Railway", "I", "JGDQOXUSCAMIFRVTPNEWKBLZYH", "N"),
+    rotor("Railway", "II", "NTZPSFBOKMWRCJDIVLAEYUXHGQ", "E"),
+    rotor("Railway", "III", "JVIUBHTCDYAKEQZPOSGXNRMWFL", "Y"),
+];
+static RAILWAY_REFLECTORS: [WheelSpec; 1] =
+    [reflector("Railway", "UKW", "QYHOGNECVPUZTFDJAXWMKISRBL")];
+
+static SWISS_K_ROTORS: [WheelSpec; 3] = [
+    rotor("Swiss-K", "I", "PEZUOHXSCVFMTBGLRINQJWAYDK", "Y"),
+    rotor("Swiss-K", "II", "ZOUESYDKFWPCIQXHMVBLGNJRAT", "E"),
+    rotor("Swiss-K", "III", "EHRVXGAOBQUSIMZFLYNWKTPDJC", "N"),
+];
+static SWISS_K_REFLECTORS: [WheelSpec; 1] =
+    [reflector("Swiss-K", "UKW", "IMETCGFRAYSQBZXWLHKDVUPOJN")];
+
+static TIRPITZ_ROTORS: [WheelSpec; 8] = [
+    rotor("Tirpitz", "I", "KPTYUELOCVGRFQDANJMBSWHZXI", "WZEKQ"),
+    rotor("Tirpitz", "II", "UPHZLWEQMTDJXCAKSOIGVBYFNR", "WZFLR"),
+    rotor("Tirpitz", "III", "QUDLYRFEKONVZAXWHMGPJBSICT", "WZEKQ"),
+    rotor("Tirpitz", "IV", "CIWTBKXNRESPFLYDAGVHQUOJZM", "WZFLR"),
+    rotor("Tirpitz", "V", "UAXGISNJBVERDYLFZWTPCKOHMQ", "YCFKR"),
+    rotor("Tirpitz", "VI", "XFUZGALVHCNYSEWQTDMRBKPIOJ", "XEIMQ"),
+    rotor("Tirpitz", "VII", "BJVFTXPLNAYOZIKWGDQERUCHSM", "YCFKR"),
+    rotor("Tirpitz", "VIII", "YMTPNZHWKODAJXELUQVGCBISFR", "XEIMQ"),
+];
+static TIRPITZ_REFLECTORS: [WheelSpec; 1] =
+    [reflector("Tirpitz", "UKW", "GEKPBTAUMOCNILJDXZYFHWVQSR")];
+
+static NORWAY_ROTORS: [WheelSpec; 5] = [
+    rotor("Norway", "I", "WTOKASUYVRBXJHQCPZEFMDINLG", "Q"),
+    rotor("Norway", "II", "GJLPUBSWEMCTQVHXAOFZDRKYNI", "E"),
+    rotor("Norway", "III", "JWFMHNBPUSDYTIXVZGRQLAOEKC", "V"),
+    rotor("Norway", "IV", "ESOVPZJAYQUIRHXLNFTGKDCMWB", "J"),
+    rotor("Norway", "V", "HEJXQOTZBVFDASCILWPGYNMURK", "Z"),
+];
+static NORWAY_REFLECTORS: [WheelSpec; 1] =
+    [reflector("Norway", "UKW", "MOWJYPUXNDSRAIBFVLKZGQCHET")];
+
+// ---- British Typex, used by `Typex`
+/// Placeholder wirings - the original ones were never published, these are made up for
+/// exercises and only the several notches of each rotor follow the original.
+pub(crate) static TYPEX_ROTORS: [WheelSpec; 8] = [
+    rotor("Typex", "A", "FKTYWCQXGZAOIVJHPLBDMRNUES", "CLOUY"),
+    rotor("Typex", "B", "XSGFZYWIUVQKBPTDLNOHAJCREM", "AIKMO"),
+    rotor("Typex", "C", "GJZTSIFDEWRBYLUMAXVPKHOQNC", "DINRX"),
+    rotor("Typex", "D", "ISBGFQJATNVMCEODYXLKUZHRWP", "BEJOY"),
+    rotor("Typex", "E", "NVCQMEBAGOIZLFWXRPHUDYSTJK", "GIKNS"),
+    rotor("Typex", "F", "JOMKEPQIFADGCZBRVSLYTXNUWH", "FJRYZ"),
+    rotor("Typex", "G", "BYQOSDJNLWPFAGIKEHTURMVXCZ", "DLOUY"),
+    rotor("Typex", "H", "SPQYDFKJEOXWZRBUMVNCGHTIAL", "BCFMS"),
+];
+/// Placeholder wiring, see `TYPEX_ROTORS`.
+pub(crate) static TYPEX_REFLECTORS: [WheelSpec; 1] =
+    [reflector("Typex", "Standard", "ITNPVRYLAMXHJCWDSFQBZEOKGU")];
+// ----
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alphabet::Wiring;
+    use crate::error::{BuildError, Error};
+
+    #[test]
+    fn wheels_of_all_models_are_valid() {
+        for model in MODELS.iter() {
+            for wheel in model.rotors.iter().chain(model.greek_wheels.iter()) {
+                assert!(Wiring::new(wheel.wiring).is_ok(), "{}", wheel);
+                assert_eq!(Error::check_alphabet(wheel.turnover), Ok(()), "{}", wheel);
+            }
+            for wheel in model.reflectors.iter() {
+                assert!(
+                    Wiring::new(wheel.wiring).and_then(Reflector::new).is_ok(),
+                    "{}",
+                    wheel
+                );
+            }
+            assert!(Wiring::new(model.entry_disk).is_ok(), "{}", model);
+        }
+        for wheel in TYPEX_ROTORS.iter() {
+            assert!(Wiring::new(wheel.wiring).is_ok(), "{}", wheel);
+            assert_eq!(Error::check_alphabet(wheel.turnover), Ok(()), "{}", wheel);
+        }
+        assert!(Wiring::new(TYPEX_REFLECTORS[0].wiring)
+            .and_then(Reflector::new)
+            .is_ok());
+    }
+
+    #[test]
+    fn finds_models_and_their_wheels() {
+        let m3 = Model::by_name("m3").unwrap();
+        assert_eq!(m3.to_string(), "Enigma M3");
+        assert_eq!(m3.rotor("VIII"), Rotor::by_name("VIII"));
+        assert_eq!(m3.reflector("A"), None);
+        assert_eq!(Model::by_name("I").unwrap().rotor("VI"), None);
+        assert!(Model::by_name("Swiss-K")
+            .unwrap()
+            .reflector("UKW")
+            .is_some());
+        assert_eq!(Model::by_name("Z"), None);
+    }
+
+    #[test]
+    fn rejects_wheels_of_other_model() {
+        let m3 = Model::by_name("M3").unwrap();
+        let railway = Model::by_name("Railway").unwrap();
+        let built = m3
+            .builder()
+            .rotor_left(Rotor::by_name("I").unwrap())
+            .rotor_middle(railway.rotor("I").unwrap())
+            .rotor_right(Rotor::by_name("III").unwrap())
+            .reflector(Reflector::b())
+            .build();
+        assert_eq!(
+            built.err(),
+            Some(BuildError::WheelNotInModel {
+                wheel: "Railway I".into(),
+                model: "M3"
+            })
+        );
+        assert_eq!(
+            m3.builder()
+                .rotor_left(Rotor::by_name("I").unwrap())
+                .rotor_middle(Rotor::by_name("II").unwrap())
+                .rotor_right(Rotor::by_name("III").unwrap())
+                .reflector(Reflector::a())
+                .build()
+                .err(),
+            Some(BuildError::WheelNotInModel {
+                wheel: "Wehrmacht A".into(),
+                model: "M3"
+            })
+        );
+    }
+
+    #[test]
+    fn m4_with_beta_at_a_is_same_as_m3_with_reflector_b() {
+        // thin reflector B and wheel Beta in position A, ring 01 were chosen so that
+        // M4 can read M3 traffic
+        let m4 = Model::by_name("M4").unwrap();
+        let mut enigma = m4
+            .builder()
+            .greek_wheel(m4.greek_wheel("Beta").unwrap())
+            .rotor_left(m4.rotor("I").unwrap())
+            .rotor_middle(m4.rotor("II").unwrap())
+            .rotor_right(m4.rotor("III").unwrap())
+            .reflector(m4.reflector("B").unwrap())
+            .build()
+            .unwrap();
+        enigma.change_rotor_setting("AAA").unwrap();
+        assert_eq!(
+            enigma.encode_for_current_rotor_setting("AAAAA").unwrap(),
+            "BDZGO"
+        );
+        assert_eq!(
+            m4.builder()
+                .rotor_left(m4.rotor("I").unwrap())
+                .rotor_middle(m4.rotor("II").unwrap())
+                .rotor_right(m4.rotor("III").unwrap())
+                .reflector(m4.reflector("B").unwrap())
+                .build()
+                .err(),
+            Some(BuildError::MissingGreekWheel)
+        );
+    }
+
+    #[test]
+    fn greek_wheel_turned_before_building() {
+        let m4 = Model::by_name("M4").unwrap();
+        let beta_at = |position| {
+            let greek_wheel = m4.greek_wheel("Beta").unwrap().with_position(position)?;
+            let mut enigma = m4
+                .builder()
+                .greek_wheel(greek_wheel)
+                .rotor_left(m4.rotor("I").unwrap())
+                .rotor_middle(m4.rotor("II").unwrap())
+                .rotor_right(m4.rotor("III").unwrap())
+                .reflector(m4.reflector("B").unwrap())
+                .build()?;
+            enigma.change_rotor_setting("AAA")?;
+            enigma.encode_for_current_rotor_setting("AAAAA")
+        };
+        assert_eq!(beta_at('A'), Ok("BDZGO".into()));
+        assert_ne!(beta_at('B'), Ok("BDZGO".into()));
+        assert_eq!(
+            beta_at('b'),
+            Err(Error::UnsupportedCharacter {
+                character: 'b',
+                index: 0
+            })
+        );
+    }
+
+    #[test]
+    fn gear_stepping_has_no_double_step() {
+        let g = Model::by_name("G").unwrap();
+        let mut enigma = g
+            .builder()
+            .rotor_left(g.rotor("I").unwrap())
+            .rotor_middle(g.rotor("II").unwrap())
+            .rotor_right(g.rotor("III").unwrap())
+            .reflector(g.reflector("UKW").unwrap())
+            .build()
+            .unwrap();
+        // 'Q' is a notch of rotor II, but it steps only when 'V' of rotor III (not a notch)
+        // has moved on to 'W' (a notch)
+        enigma.change_rotor_setting("AQV").unwrap();
+        enigma.press('A').unwrap();
+        assert_eq!(enigma.rotor_positions(), "AQW");
+        enigma.press('A').unwrap();
+        assert_eq!(enigma.rotor_positions(), "BRX");
+        enigma.press('A').unwrap();
+        assert_eq!(enigma.rotor_positions(), "BSY");
+    }
+
+    #[test]
+    fn tirpitz_rotors_carry_five_times_per_revolution() {
+        let t = Model::by_name("T").unwrap();
+        let mut enigma = t
+            .builder()
+            .rotor_left(t.rotor("I").unwrap())
+            .rotor_middle(t.rotor("III").unwrap())
+            .rotor_right(t.rotor("II").unwrap())
+            .reflector(t.reflector("UKW").unwrap())
+            .build()
+            .unwrap();
+        enigma.change_rotor_setting("AAA").unwrap();
+        enigma.advance(26);
+        // five carries by the right rotor (at F, L, R, W and Z) and a double step when the
+        // middle rotor reached its notch at E
+        assert_eq!(enigma.rotor_positions(), "BGA");
+    }
+
+    mod vectors {
+        use super::*;
+
+        // REGRESSION-ONLY: no message enciphered on a real Railway, Swiss-K, Tirpitz or Norway
+        // machine, nor output of an independent simulator, is checked in yet. The expected
+        // outputs were produced by this implementation with the wirings above, which follow the
+        // commonly published tables, so they only guard against unintended changes.
+        fn encode(
+            model: &str,
+            rotors: [&str; 3],
+            rings: &str,
+            reflector_position: char,
+            positions: &str,
+            text: &str,
+        ) -> String {
+            let model = Model::by_name(model).unwrap();
+            let rotor = |i: usize| {
+                model
+                    .rotor(rotors[i])
+                    .unwrap()
+                    .with_ring_setting(rings.chars().nth(i).unwrap())
+                    .unwrap()
+            };
+            let reflector = model
+                .reflector(model.reflectors[0].name)
+                .unwrap()
+                .with_position(reflector_position)
+                .unwrap();
+            let mut enigma = model
+                .builder()
+                .rotor_left(rotor(0))
+                .rotor_middle(rotor(1))
+                .rotor_right(rotor(2))
+                .reflector(reflector)
+                .build()
+                .unwrap();
+            enigma.change_rotor_setting(positions).unwrap();
+            let encoded = enigma.encode_for_current_rotor_setting(text).unwrap();
+            enigma.change_rotor_setting(positions).unwrap();
+            assert_eq!(
+                enigma.encode_for_current_rotor_setting(&encoded).unwrap(),
+                text
+            );
+            encoded
+        }
+
+        #[test]
+        fn railway() {
+            assert_eq!(
+                encode(
+                    "Railway",
+                    ["III", "I", "II"],
+                    "AFQ",
+                    'G',
+                    "XVN",
+                    "DIEZUEGEFAHRENPUENKTLICH"
+                ),
+                "QCUINFUQTBCFWMWLSTLSAFOT"
+            );
+        }
+
+        #[test]
+        fn swiss_k() {
+            assert_eq!(
+                encode(
+                    "Swiss-K",
+                    ["II", "III", "I"],
+                    "BZC",
+                    'M',
+                    "HLE",
+                    "ANGRIFFIMMORGENGRAUEN"
+                ),
+                "VLAYQDMTJSAQNCGOPZBVV"
+            );
+        }
+
+        #[test]
+        fn tirpitz() {
+            assert_eq!(
+                encode(
+                    "T",
+                    ["VIII", "II", "V"],
+                    "KDA",
+                    'Y',
+                    "TEW",
+                    "KONNICHIWATOKYOKAIGUN"
+                ),
+                "MAOVFAIBDXJZTZSWTKFNV"
+            );
+        }
+
+        #[test]
+        fn norway() {
+            assert_eq!(
+                encode(
+                    "Norway",
+                    ["V", "I", "III"],
+                    "NOR",
+                    'A',
+                    "OSL",
+                    "POLITIETIOSLOMELDER"
+                ),
+                "OSFLXKFMDSVAQBWBKLV"
+            );
+        }
+    }
+}
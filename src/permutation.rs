@@ -0,0 +1,241 @@
+// Permutations of the machine alphabet, for reasoning about the machine the way Rejewski did.
+// Products are written right to left like functions, so S⁻¹P⁻¹UPS applies S first.
+
+use std::fmt::Display;
+use std::str::FromStr;
+
+use crate::alphabet::{Letter, Wiring};
+use crate::error::Error;
+
+/// Permutation of the alphabet, written as the letters 'A', 'B', 'C' and so on are mapped to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Permutation([u8; 26]);
+
+impl Permutation {
+    pub fn identity() -> Permutation {
+        Permutation::from_fn(|i| i)
+    }
+
+    /// Permutation mapping 'A' to the first letter, 'B' to the second and so on.
+    pub fn new(letters: &str) -> Result<Permutation, Error> {
+        Wiring::new(letters).map(Permutation::from)
+    }
+
+    // `f` must map the letter indexes one to one
+    pub(crate) fn from_fn<F: Fn(u8) -> u8>(f: F) -> Permutation {
+        let mut mapping = [0; 26];
+        for (i, m) in mapping.iter_mut().enumerate() {
+            *m = f(i as u8);
+        }
+        debug_assert!(Wiring::new(&Permutation(mapping).to_string()).is_ok());
+        Permutation(mapping)
+    }
+
+    pub fn apply(&self, letter: Letter) -> Letter {
+        Letter::from_index(self.0[letter.index() as usize])
+    }
+
+    /// Product `self · other` - `other` is applied first.
+    pub fn compose(&self, other: &Permutation) -> Permutation {
+        Permutation::from_fn(|i| self.0[other.0[i as usize] as usize])
+    }
+
+    pub fn inverse(&self) -> Permutation {
+        let mut inverse = [0; 26];
+        for (i, m) in self.0.iter().enumerate() {
+            inverse[*m as usize] = i as u8;
+        }
+        Permutation(inverse)
+    }
+
+    /// `by⁻¹ · self · by` - the same cycle structure, with the letters renamed by `by`.
+    pub fn conjugate(&self, by: &Permutation) -> Permutation {
+        by.inverse().compose(self).compose(by)
+    }
+
+    /// Cycles including the letters mapped to themselves, each starting with its first
+    /// letter in the alphabet and ordered by it.
+    pub fn cycles(&self) -> Vec<Vec<Letter>> {
+        let mut visited = [false; 26];
+        let mut cycles = vec![];
+        for start in 0..26u8 {
+            if visited[start as usize] {
+                continue;
+            }
+            let mut cycle = vec![];
+            let mut i = start;
+            while !visited[i as usize] {
+                visited[i as usize] = true;
+                cycle.push(Letter::from_index(i));
+                i = self.0[i as usize];
+            }
+            cycles.push(cycle);
+        }
+        cycles
+    }
+
+    /// Lengths of the cycles, longest first - the same for conjugate permutations.
+    pub fn cycle_type(&self) -> Vec<usize> {
+        let mut lengths: Vec<usize> = self.cycles().iter().map(Vec::len).collect();
+        lengths.sort_unstable_by(|a, b| b.cmp(a));
+        lengths
+    }
+
+    /// Even permutations are products of an even number of transpositions.
+    pub fn is_even(&self) -> bool {
+        (26 - self.cycles().len()).is_multiple_of(2)
+    }
+
+    /// Involutions are their own inverse, like the plugboard and the reflector.
+    pub fn is_involution(&self) -> bool {
+        self.compose(self) == Permutation::identity()
+    }
+
+    /// Cycle notation without the letters mapped to themselves, e.g. '(AB)(CDE)'.
+    pub fn to_cycle_notation(&self) -> String {
+        self.cycles()
+            .iter()
+            .filter(|cycle| cycle.len() > 1)
+            .map(|cycle| {
+                let letters: String = cycle.iter().map(|l| l.to_char()).collect();
+                format!("({})", letters)
+            })
+            .collect()
+    }
+}
+
+impl From<Wiring> for Permutation {
+    fn from(wiring: Wiring) -> Self {
+        Permutation(wiring.forward())
+    }
+}
+
+impl FromStr for Permutation {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Permutation::new(s)
+    }
+}
+
+impl Display for Permutation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for i in self.0.iter() {
+            write!(f, "{}", Letter::from_index(*i))?;
+        }
+        Ok(())
+    }
+}
+
+/// Permutations applied by the parts of Enigma to a letter typed at one rotor position.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MachinePermutations {
+    // S
+    pub plugboard: Permutation,
+    pub entry_disk: Permutation,
+    // left, middle and right rotor, shifted by their positions and rings, from the entry
+    // disk towards the reflector
+    pub rotors: [Permutation; 3],
+    // U, together with the greek wheel of M4
+    pub reflector: Permutation,
+}
+
+impl MachinePermutations {
+    /// P - from the plugboard to the reflector: entry disk, right, middle and left rotor.
+    pub fn scrambler(&self) -> Permutation {
+        let [left, middle, right] = &self.rotors;
+        left.compose(middle)
+            .compose(right)
+            .compose(&self.entry_disk)
+    }
+
+    /// S⁻¹P⁻¹UPS - the letter lit for each key.
+    pub fn machine(&self) -> Permutation {
+        self.reflector
+            .conjugate(&self.scrambler())
+            .conjugate(&self.plugboard)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn permutation(letters: &str) -> Permutation {
+        letters.parse().unwrap()
+    }
+
+    fn letter(c: char) -> Letter {
+        Letter::new(c).unwrap()
+    }
+
+    // rotor I of Enigma I
+    const ROTOR_I: &str = "EKMFLGDQVZNTOWYHXUSPAIBRCJ";
+
+    #[test]
+    fn composition_applies_right_one_first() {
+        // A -> B, then B -> C
+        let shift = permutation("BCDEFGHIJKLMNOPQRSTUVWXYZA");
+        let swap = permutation("ACBDEFGHIJKLMNOPQRSTUVWXYZ");
+        assert_eq!(swap.compose(&shift).apply(letter('A')), letter('C'));
+        assert_eq!(shift.compose(&swap).apply(letter('A')), letter('B'));
+    }
+
+    #[test]
+    fn inverse_gives_identity() {
+        let rotor = permutation(ROTOR_I);
+        assert_eq!(rotor.compose(&rotor.inverse()), Permutation::identity());
+        assert_eq!(rotor.inverse().apply(letter('E')), letter('A'));
+        assert_eq!(rotor.inverse().inverse(), rotor);
+    }
+
+    #[test]
+    fn cycles_and_parity() {
+        let rotor = permutation(ROTOR_I);
+        assert_eq!(
+            rotor.to_cycle_notation(),
+            "(AELTPHQXRU)(BKNW)(CMOY)(DFG)(IV)(JZ)"
+        );
+        assert_eq!(rotor.cycle_type(), vec![10, 4, 4, 3, 2, 2, 1]);
+        // 9 + 3 + 3 + 2 + 1 + 1 transpositions
+        assert!(!rotor.is_even());
+        assert!(Permutation::identity().is_even());
+        assert_eq!(Permutation::identity().cycles().len(), 26);
+        assert!(!permutation("BACDEFGHIJKLMNOPQRSTUVWXYZ").is_even());
+    }
+
+    #[test]
+    fn conjugation_keeps_cycle_type() {
+        let rotor = permutation(ROTOR_I);
+        let by = permutation("AJDKSIRUXBLHWTMCQGZNPYFVOE");
+        let conjugate = rotor.conjugate(&by);
+        assert_ne!(conjugate, rotor);
+        assert_eq!(conjugate.cycle_type(), rotor.cycle_type());
+        // maps by⁻¹(x) to by⁻¹(rotor(x))
+        let x = letter('A');
+        assert_eq!(
+            conjugate.apply(by.inverse().apply(x)),
+            by.inverse().apply(rotor.apply(x))
+        );
+    }
+
+    #[test]
+    fn involutions() {
+        let reflector_b = permutation("YRUHQSLDPXNGOKMIEBFZCWVJAT");
+        assert!(reflector_b.is_involution());
+        assert_eq!(reflector_b.cycle_type(), vec![2; 13]);
+        assert!(!permutation(ROTOR_I).is_involution());
+    }
+
+    #[test]
+    fn error_on_letters_not_forming_permutation() {
+        assert_eq!(
+            "ABCA".parse::<Permutation>(),
+            Err(Error::InvalidLength {
+                subject: "Wiring",
+                expected: 26,
+                actual: 4
+            })
+        );
+    }
+}
@@ -1,175 +1,253 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
 use crate::enigma::SUPPORTED_ALPHABET;
-use std::collections::BTreeMap;
+use crate::error::Error;
+
+/// Number of cables issued with the machines during the war.
+pub const HISTORICAL_MAX_CABLES: usize = 10;
 
+#[derive(Debug, Clone, PartialEq)]
 pub struct Plugboard {
-    mapping: BTreeMap<char, char>,
+    // letter (as index) each letter is connected to, itself if not connected
+    mapping: [u8; 26],
+    // reject connecting a letter which is already plugged, instead of unplugging it
+    strict: bool,
+    max_cables: Option<usize>,
 }
 
 impl Plugboard {
-    pub(crate) fn identity() -> Plugboard {
-        let mapping = SUPPORTED_ALPHABET
-            .chars()
-            .fold(BTreeMap::new(), |mut acc, c| {
-                acc.insert(c, c);
-                acc
-            });
-        Plugboard { mapping }
-    }
-
-    pub(crate) fn connect(&mut self, from: char, to: char) -> Result<(), String> {
-        if let None = SUPPORTED_ALPHABET.find(from) {
-            return Err(format!(
-                "Character '{}' is not in supported alphabet: {}",
-                from, SUPPORTED_ALPHABET
-            ));
+    pub fn identity() -> Plugboard {
+        let mut mapping = [0; 26];
+        for (i, m) in mapping.iter_mut().enumerate() {
+            *m = i as u8;
         }
-        if let None = SUPPORTED_ALPHABET.find(to) {
-            return Err(format!(
-                "Character '{}' is not in supported alphabet: {}",
-                to, SUPPORTED_ALPHABET
-            ));
+        Plugboard {
+            mapping,
+            strict: false,
+            max_cables: None,
         }
+    }
+
+    /// Connecting a letter which is already plugged fails, e.g. a letter used twice on the
+    /// key sheet.
+    pub fn strict(mut self) -> Self {
+        self.strict = true;
+        self
+    }
 
+    pub fn max_cables(mut self, max_cables: usize) -> Self {
+        self.max_cables = Some(max_cables);
+        self
+    }
+
+    /// Connects two letters with a cable. If not strict, letters already plugged are
+    /// unplugged first, e.g. connecting B-C after A-B leaves A unplugged, and connecting
+    /// a letter to itself unplugs it.
+    pub fn connect(&mut self, from: char, to: char) -> Result<(), Error> {
         if from.eq(&to) {
-            if let Err(e) = self.disconnect(from) {
-                return Err(e);
+            if self.strict {
+                Plugboard::index(from, 0)?;
+                return Err(Error::InvalidPlugboardPair(format!("{}{}", from, to)));
             }
-            return Ok(());
+            return self.disconnect(from);
         }
 
-        let disconnected_1 = self.mapping.insert(from, to);
-        if let Some(c) = disconnected_1 {
-            if !c.eq(&from) && !c.eq(&to) {
-                *self.mapping.get_mut(&c).unwrap() = c;
+        let from_i = Plugboard::index(from, 0)?;
+        let to_i = Plugboard::index(to, 1)?;
+        if self.strict {
+            for (letter, i) in [(from, from_i), (to, to_i)].iter() {
+                let connected = self.mapping[*i] as usize;
+                if connected != *i {
+                    return Err(Error::LetterAlreadyPlugged {
+                        letter: *letter,
+                        connected_to: (b'A' + connected as u8) as char,
+                    });
+                }
             }
         }
 
-        let disconnected_2 = self.mapping.insert(to, from);
-        if !disconnected_1.eq(&disconnected_2) {
-            if let Some(c) = disconnected_2 {
-                if !c.eq(&from) && !c.eq(&to) {
-                    *self.mapping.get_mut(&c).unwrap() = c;
-                }
+        let mut connected = self.clone();
+        connected.disconnect_index(from_i);
+        connected.disconnect_index(to_i);
+        connected.mapping[from_i] = to_i as u8;
+        connected.mapping[to_i] = from_i as u8;
+        if let Some(max) = self.max_cables {
+            let cables = connected.cables();
+            if cables > max {
+                return Err(Error::TooManyPlugboardPairs {
+                    max,
+                    actual: cables,
+                });
             }
         }
+        *self = connected;
         Ok(())
     }
 
-    pub(crate) fn disconnect(&mut self, char_to_disconnect: char) -> Result<(), String> {
-        if let None = SUPPORTED_ALPHABET.find(char_to_disconnect) {
-            return Err(format!(
-                "Character '{}' is not in supported alphabet: {}",
-                char_to_disconnect, SUPPORTED_ALPHABET
-            ));
-        }
-        let disconnected_value = self.mapping.insert(char_to_disconnect, char_to_disconnect);
-
-        if let Some(c) = disconnected_value {
-            if c.eq(&char_to_disconnect) {
-                return Ok(());
-            }
-            *self.mapping.get_mut(&c).unwrap() = c;
-        }
+    pub fn disconnect(&mut self, char_to_disconnect: char) -> Result<(), Error> {
+        let i = Plugboard::index(char_to_disconnect, 0)?;
+        self.disconnect_index(i);
         Ok(())
     }
 
-    pub(crate) fn encode_from_right(&self, letter: char) -> u8 {
-        let encoded = self.mapping.get(&letter).expect(&format!(
-            "Plugboard does not support '{}' character",
-            letter
-        ));
-        SUPPORTED_ALPHABET.find(*encoded).unwrap() as u8
+    /// Connected letters, each pair in alphabetical order.
+    pub fn pairs(&self) -> Vec<(char, char)> {
+        self.mapping
+            .iter()
+            .enumerate()
+            .filter(|(i, connected)| *i < **connected as usize)
+            .map(|(i, connected)| ((b'A' + i as u8) as char, (b'A' + connected) as char))
+            .collect()
+    }
+
+    pub fn cables(&self) -> usize {
+        self.pairs().len()
+    }
+
+    fn disconnect_index(&mut self, i: usize) {
+        let connected = self.mapping[i] as usize;
+        self.mapping[connected] = connected as u8;
+        self.mapping[i] = i as u8;
+    }
+
+    // `index` is the position of the character in the arguments, reported on error
+    fn index(c: char, index: usize) -> Result<usize, Error> {
+        SUPPORTED_ALPHABET
+            .find(c)
+            .ok_or(Error::UnsupportedCharacter {
+                character: c,
+                index,
+            })
+    }
+
+    pub(crate) fn encode_from_right(&self, i: u8) -> u8 {
+        self.mapping[i as usize]
+    }
+
+    pub(crate) fn encode_from_left(&self, i: u8) -> u8 {
+        self.mapping[i as usize]
     }
+}
 
-    pub(crate) fn encode_from_left(&self, i: u8) -> char {
-        let encoded = SUPPORTED_ALPHABET.chars().nth(i as usize).unwrap();
-        *self.mapping.get(&encoded).unwrap()
+// pairs as on the key sheets, e.g. 'AB CD EF'
+impl Display for Plugboard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let pairs: Vec<String> = self
+            .pairs()
+            .iter()
+            .map(|(a, b)| format!("{}{}", a, b))
+            .collect();
+        f.write_str(&pairs.join(" "))
     }
 }
 
-#[derive(Debug, PartialEq)]
+/// Parses pairs like 'AB CD EF' into a strict plugboard, so a letter used twice is an error.
+impl FromStr for Plugboard {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut plugboard = Plugboard::identity().strict();
+        for pair in s.split_whitespace() {
+            let connection = PlugboardConnection::create(pair)?;
+            plugboard.connect(connection.left, connection.right)?;
+        }
+        Ok(plugboard)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct PlugboardConnection {
     pub(crate) left: char,
     pub(crate) right: char,
 }
 
 impl PlugboardConnection {
-    pub fn create(character_pair: &str) -> Result<PlugboardConnection, String> {
+    pub fn create(character_pair: &str) -> Result<PlugboardConnection, Error> {
         if character_pair.chars().count() != 2 {
-            return Err(format!(
-                "Expected only pairs (2 values) split by comma character (,), but found: {}",
-                character_pair
-            ));
+            return Err(Error::InvalidPlugboardPair(character_pair.into()));
         }
+        Error::check_alphabet(character_pair)?;
 
         let p0 = character_pair.chars().nth(0).unwrap();
         let p1 = character_pair.chars().nth(1).unwrap();
-        if !SUPPORTED_ALPHABET.contains(p0) {
-            return Err(format!(
-                "Value '{}' is not allowed as part of plugboard. Allowed: {}",
-                p0, SUPPORTED_ALPHABET
-            ));
-        }
-        if !SUPPORTED_ALPHABET.contains(p1) {
-            return Err(format!(
-                "Value '{}' is not allowed as part of plugboard. Allowed: {}",
-                p1, SUPPORTED_ALPHABET
-            ));
-        }
 
         Ok(PlugboardConnection {
             left: p0,
             right: p1,
         })
     }
+
+    pub fn left(&self) -> char {
+        self.left
+    }
+
+    pub fn right(&self) -> char {
+        self.right
+    }
+}
+
+// both letters, e.g. 'AB', as accepted by `PlugboardConnection::create`
+impl Display for PlugboardConnection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", self.left, self.right)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::collections::BTreeMap;
+
+    fn mapping(plugboard: &Plugboard) -> BTreeMap<char, char> {
+        SUPPORTED_ALPHABET
+            .chars()
+            .zip(plugboard.mapping.iter().map(|i| (b'A' + i) as char))
+            .collect()
+    }
 
     #[test]
     fn test_plugboard() {
         let mut plugboard = Plugboard::identity();
-        let mut expected_mapping = plugboard.mapping.clone();
+        let mut expected_mapping = mapping(&plugboard);
 
         assert_eq!(plugboard.connect('A', 'B'), Ok(()));
         expected_mapping.insert('A', 'B');
         expected_mapping.insert('B', 'A');
-        assert_eq!(expected_mapping, plugboard.mapping);
+        assert_eq!(expected_mapping, mapping(&plugboard));
 
         assert_eq!(plugboard.connect('C', 'D'), Ok(()));
         expected_mapping.insert('C', 'D');
         expected_mapping.insert('D', 'C');
-        assert_eq!(expected_mapping, plugboard.mapping);
+        assert_eq!(expected_mapping, mapping(&plugboard));
 
         assert_eq!(plugboard.connect('B', 'C'), Ok(()));
         expected_mapping.insert('A', 'A');
         expected_mapping.insert('B', 'C');
         expected_mapping.insert('C', 'B');
         expected_mapping.insert('D', 'D');
-        assert_eq!(expected_mapping, plugboard.mapping);
+        assert_eq!(expected_mapping, mapping(&plugboard));
 
         assert_eq!(plugboard.disconnect('B'), Ok(()));
         expected_mapping.insert('B', 'B');
         expected_mapping.insert('C', 'C');
-        assert_eq!(expected_mapping, plugboard.mapping);
+        assert_eq!(expected_mapping, mapping(&plugboard));
     }
 
     #[test]
     fn expect_disconnect_executed_by_connecting_same_character() {
         let mut plugboard = Plugboard::identity();
-        let mut expected_mapping = plugboard.mapping.clone();
+        let mut expected_mapping = mapping(&plugboard);
 
         assert_eq!(plugboard.connect('A', 'B'), Ok(()));
         expected_mapping.insert('A', 'B');
         expected_mapping.insert('B', 'A');
-        assert_eq!(expected_mapping, plugboard.mapping);
+        assert_eq!(expected_mapping, mapping(&plugboard));
 
         assert_eq!(plugboard.connect('B', 'B'), Ok(()));
         expected_mapping.insert('A', 'A');
         expected_mapping.insert('B', 'B');
-        assert_eq!(expected_mapping, plugboard.mapping);
+        assert_eq!(expected_mapping, mapping(&plugboard));
     }
 
     #[test]
@@ -177,7 +255,10 @@ mod tests {
         let mut plugboard = Plugboard::identity();
         assert_eq!(
             plugboard.connect('1', 'A'),
-            Err("Character '1' is not in supported alphabet: ABCDEFGHIJKLMNOPQRSTUVWXYZ".into())
+            Err(Error::UnsupportedCharacter {
+                character: '1',
+                index: 0
+            })
         );
     }
 
@@ -186,7 +267,10 @@ mod tests {
         let mut plugboard = Plugboard::identity();
         assert_eq!(
             plugboard.connect('A', '2'),
-            Err("Character '2' is not in supported alphabet: ABCDEFGHIJKLMNOPQRSTUVWXYZ".into())
+            Err(Error::UnsupportedCharacter {
+                character: '2',
+                index: 1
+            })
         );
     }
 
@@ -195,7 +279,106 @@ mod tests {
         let mut plugboard = Plugboard::identity();
         assert_eq!(
             plugboard.disconnect('3'),
-            Err("Character '3' is not in supported alphabet: ABCDEFGHIJKLMNOPQRSTUVWXYZ".into())
+            Err(Error::UnsupportedCharacter {
+                character: '3',
+                index: 0
+            })
+        );
+    }
+
+    #[test]
+    fn error_on_invalid_connection() {
+        assert_eq!(
+            PlugboardConnection::create("ABC"),
+            Err(Error::InvalidPlugboardPair("ABC".into()))
+        );
+        assert_eq!(
+            PlugboardConnection::create("A1"),
+            Err(Error::UnsupportedCharacter {
+                character: '1',
+                index: 1
+            })
+        );
+    }
+
+    #[test]
+    fn strict_plugboard_rejects_letter_used_twice() {
+        let mut plugboard = Plugboard::identity().strict();
+        assert_eq!(plugboard.connect('A', 'B'), Ok(()));
+        assert_eq!(
+            plugboard.connect('B', 'C'),
+            Err(Error::LetterAlreadyPlugged {
+                letter: 'B',
+                connected_to: 'A'
+            })
+        );
+        assert_eq!(plugboard.pairs(), vec![('A', 'B')]);
+
+        assert_eq!(plugboard.disconnect('A'), Ok(()));
+        assert_eq!(plugboard.connect('B', 'C'), Ok(()));
+        assert_eq!(plugboard.pairs(), vec![('B', 'C')]);
+    }
+
+    #[test]
+    fn connection_shows_its_letters() {
+        let connection = PlugboardConnection::create("QE").unwrap();
+        assert_eq!((connection.left(), connection.right()), ('Q', 'E'));
+        assert_eq!(connection.to_string(), "QE");
+    }
+
+    #[test]
+    fn strict_plugboard_rejects_letter_plugged_to_itself() {
+        let mut plugboard = Plugboard::identity().strict();
+        assert_eq!(plugboard.connect('A', 'B'), Ok(()));
+        assert_eq!(
+            plugboard.connect('A', 'A'),
+            Err(Error::InvalidPlugboardPair("AA".into()))
+        );
+        assert_eq!(plugboard.pairs(), vec![('A', 'B')]);
+        assert_eq!(
+            "AB CC".parse::<Plugboard>().unwrap_err().to_string(),
+            "Expected pairs of 2 different letters, but found: CC"
+        );
+        // not strict, the letter is unplugged
+        let mut plugboard = Plugboard::identity();
+        plugboard.connect('A', 'B').unwrap();
+        assert_eq!(plugboard.connect('A', 'A'), Ok(()));
+        assert!(plugboard.pairs().is_empty());
+    }
+
+    #[test]
+    fn error_on_too_many_cables() {
+        let mut plugboard = Plugboard::identity().max_cables(2);
+        assert_eq!(plugboard.connect('A', 'B'), Ok(()));
+        assert_eq!(plugboard.connect('C', 'D'), Ok(()));
+        assert_eq!(
+            plugboard.connect('E', 'F'),
+            Err(Error::TooManyPlugboardPairs { max: 2, actual: 3 })
+        );
+        // replacing a cable does not need another one
+        assert_eq!(plugboard.connect('D', 'E'), Ok(()));
+        assert_eq!(plugboard.pairs(), vec![('A', 'B'), ('D', 'E')]);
+        assert_eq!(plugboard.cables(), 2);
+    }
+
+    #[test]
+    fn key_sheet_notation() {
+        let plugboard: Plugboard = "QA ZU  CB".parse().unwrap();
+        assert_eq!(plugboard.pairs(), vec![('A', 'Q'), ('B', 'C'), ('U', 'Z')]);
+        assert_eq!(plugboard.to_string(), "AQ BC UZ");
+        assert_eq!(plugboard.to_string().parse(), Ok(plugboard));
+        assert_eq!("".parse::<Plugboard>().unwrap().to_string(), "");
+
+        assert_eq!(
+            "AB CA".parse::<Plugboard>(),
+            Err(Error::LetterAlreadyPlugged {
+                letter: 'A',
+                connected_to: 'B'
+            })
+        );
+        assert_eq!(
+            "AB CDE".parse::<Plugboard>(),
+            Err(Error::InvalidPlugboardPair("CDE".into()))
         );
     }
 }
@@ -1,48 +1,156 @@
-use crate::enigma::SUPPORTED_ALPHABET;
+use crate::alphabet::{Letter, Wiring};
+use crate::error::Error;
+use crate::model::{WheelSpec, WEHRMACHT_REFLECTORS};
+use crate::rotors::rotor::Rotor;
 use log::debug;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Reflector {
-    setting: &'static str,
+    // already turned to `position`
+    wiring: [u8; 26],
+    // reflectors of the commercial models can be turned like a rotor (but never step),
+    // 0 for 'A'
+    position: u8,
+    // none for reflectors with custom wiring
+    spec: Option<&'static WheelSpec>,
 }
 
 impl Reflector {
     #[allow(dead_code)]
     pub fn a() -> Reflector {
-        Reflector::new(REFLECTOR_A)
+        Reflector::from_spec(&WEHRMACHT_REFLECTORS[0])
     }
     #[allow(dead_code)]
     pub fn b() -> Reflector {
-        Reflector::new(REFLECTOR_B)
+        Reflector::from_spec(&WEHRMACHT_REFLECTORS[1])
     }
     #[allow(dead_code)]
     pub fn c() -> Reflector {
-        Reflector::new(REFLECTOR_C)
+        Reflector::from_spec(&WEHRMACHT_REFLECTORS[2])
     }
 
-    fn new(setting: &'static str) -> Reflector {
-        if setting.len() != SUPPORTED_ALPHABET.len() {
-            panic!(
-                "Reflector alphabet must be of same length and contain same characters as '{}'",
-                SUPPORTED_ALPHABET
-            );
+    /// Reflector by its name, 'A', 'B' or 'C'.
+    pub fn by_name(name: &str) -> Option<Reflector> {
+        match name {
+            "A" => Some(Reflector::a()),
+            "B" => Some(Reflector::b()),
+            "C" => Some(Reflector::c()),
+            _ => None,
         }
-        Reflector { setting }
+    }
+
+    /// Reflector with the given wiring, which must connect the letters in pairs.
+    pub fn new(wiring: Wiring) -> Result<Reflector, Error> {
+        let wiring = wiring.forward();
+        for (index, wired) in wiring.iter().enumerate() {
+            if *wired as usize == index || wiring[*wired as usize] as usize != index {
+                return Err(Error::ReflectorNotReciprocal {
+                    letter: (b'A' + *wired) as char,
+                    index,
+                });
+            }
+        }
+        Ok(Reflector {
+            wiring,
+            position: 0,
+            spec: None,
+        })
+    }
+
+    // wirings shipped with the library are checked by tests
+    pub(crate) fn from_spec(spec: &'static WheelSpec) -> Reflector {
+        let reflector = Wiring::new(spec.wiring)
+            .and_then(Reflector::new)
+            .expect("built-in reflector wiring is valid");
+        Reflector {
+            spec: Some(spec),
+            ..reflector
+        }
+    }
+
+    /// Reflector of M4 together with the thin wheel next to it, which never steps - the
+    /// signal passes the wheel before and after the reflector.
+    pub(crate) fn with_greek_wheel(&self, greek_wheel: &Rotor) -> Reflector {
+        let mut wiring = [0; 26];
+        for (i, wired) in wiring.iter_mut().enumerate() {
+            let e = greek_wheel.encode_from_right(i as u8);
+            *wired = greek_wheel.encode_from_left(self.wiring[e as usize]);
+        }
+        Reflector {
+            wiring,
+            position: 0,
+            spec: None,
+        }
+    }
+
+    /// Reflector turned to `position` - possible only in models with a settable reflector,
+    /// which the builder checks.
+    pub fn with_position(mut self, position: char) -> Result<Reflector, Error> {
+        let position = Letter::new(position)?.index();
+        let by = (position + 26 - self.position) % 26;
+        let turned = self.wiring;
+        for (i, wired) in self.wiring.iter_mut().enumerate() {
+            let contact = turned[(i + by as usize) % 26];
+            *wired = (contact + 26 - by) % 26;
+        }
+        self.position = position;
+        Ok(self)
+    }
+
+    pub fn position(&self) -> char {
+        Letter::from_index(self.position).to_char()
+    }
+
+    /// Wheel the reflector was made from, if it is one shipped with the library.
+    pub fn spec(&self) -> Option<&'static WheelSpec> {
+        self.spec
+    }
+
+    pub(crate) fn wiring(&self) -> [u8; 26] {
+        self.wiring
     }
 
     pub(crate) fn encode(&self, i: u8) -> u8 {
-        let c = self.setting.chars().nth(i as usize).unwrap();
-        let idx = SUPPORTED_ALPHABET.find(c).unwrap();
-        debug!("   --- reflector: {}", c);
-        idx as u8
+        let idx = self.wiring[i as usize];
+        debug!("   --- reflector: {}", (b'A' + idx) as char);
+        idx
     }
 }
 
-// ---- names: Heer, Enigma A, Heeres, Wehrmacht, Service Enigma, Army/GAF machine
-#[allow(dead_code)]
-const REFLECTOR_A: &str = "EJMZALYXVBWFCRQUONTSPIKHGD";
-#[allow(dead_code)]
-const REFLECTOR_B: &str = "YRUHQSLDPXNGOKMIEBFZCWVJAT";
-#[allow(dead_code)]
-const REFLECTOR_C: &str = "FVPJIAOYEDRZXWGCTKUQSBNMHL";
-// ----
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn turned_reflector_still_connects_pairs() {
+        let turned = Reflector::b().with_position('C').unwrap();
+        assert_eq!(turned.position(), 'C');
+        // contact 'A' is where 'C' was, wired to 'U' ('S' after turning back by 2)
+        assert_eq!(turned.encode(0), 18);
+        let wiring: String = turned.wiring().iter().map(|i| (b'A' + i) as char).collect();
+        assert!(Wiring::new(&wiring).and_then(Reflector::new).is_ok());
+        assert_eq!(turned.with_position('A').unwrap(), Reflector::b());
+    }
+
+    #[test]
+    fn error_on_wiring_not_connecting_pairs() {
+        // rotor I wiring, 'A' goes to 'E' but 'E' goes to 'L'
+        let wiring = Wiring::new("EKMFLGDQVZNTOWYHXUSPAIBRCJ").unwrap();
+        assert_eq!(
+            Reflector::new(wiring),
+            Err(Error::ReflectorNotReciprocal {
+                letter: 'E',
+                index: 0
+            })
+        );
+        // 'A' wired to itself
+        let wiring = Wiring::new("ABCDEFGHIJKLMNOPQRSTUVWXYZ").unwrap();
+        assert_eq!(
+            Reflector::new(wiring),
+            Err(Error::ReflectorNotReciprocal {
+                letter: 'A',
+                index: 0
+            })
+        );
+    }
+}
@@ -1,88 +1,156 @@
+use crate::alphabet::{Letter, Wiring};
 use crate::enigma::SUPPORTED_ALPHABET;
+use crate::error::Error;
+use crate::model::{WheelSpec, WEHRMACHT_ROTORS};
 use log::debug;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Rotor {
     current_offset: u8,
-    alphabet: &'static str,
+    // Ringstellung - how far the wiring core is turned against the letter ring, 0 for 'A' (01)
+    ring_offset: u8,
+    // contact on the left side wired to each contact on the right side, and the other way round
+    forward: [u8; 26],
+    backward: [u8; 26],
     turnover_offsets: Vec<u8>,
+    // wiring core inserted the other way round
+    reversed: bool,
+    // none for rotors with custom wiring
+    spec: Option<&'static WheelSpec>,
 }
 
 impl Rotor {
     pub fn enigma_i_wehrmacht_i() -> Rotor {
-        Rotor::new(ENIGMA_I_WEHRMACHT_I_ROTOR, ENIGMA_I_WEHRMACHT_I_TURNOVER)
+        Rotor::from_spec(&WEHRMACHT_ROTORS[0])
     }
     pub fn enigma_i_wehrmacht_ii() -> Rotor {
-        Rotor::new(ENIGMA_I_WEHRMACHT_II_ROTOR, ENIGMA_I_WEHRMACHT_II_TURNOVER)
+        Rotor::from_spec(&WEHRMACHT_ROTORS[1])
     }
     pub fn enigma_i_wehrmacht_iii() -> Rotor {
-        Rotor::new(
-            ENIGMA_I_WEHRMACHT_III_ROTOR,
-            ENIGMA_I_WEHRMACHT_III_TURNOVER,
-        )
+        Rotor::from_spec(&WEHRMACHT_ROTORS[2])
     }
     pub fn m3_wehrmacht_iv() -> Rotor {
-        Rotor::new(M3_WEHRMACHT_IV_ROTOR, M3_WEHRMACHT_IV_TURNOVER)
+        Rotor::from_spec(&WEHRMACHT_ROTORS[3])
     }
     pub fn m3_wehrmacht_v() -> Rotor {
-        Rotor::new(M3_WEHRMACHT_V_ROTOR, M3_WEHRMACHT_V_TURNOVER)
+        Rotor::from_spec(&WEHRMACHT_ROTORS[4])
     }
 
-    fn new(alphabet: &'static str, turnover: &'static str) -> Rotor {
-        for c in alphabet.chars() {
-            if !SUPPORTED_ALPHABET.contains(c) {
-                panic!(
-                    "Alphabet error: '{}' must be a letter from set '{}'",
-                    c, SUPPORTED_ALPHABET
-                );
-            }
-        }
+    /// Rotor of Enigma I and M3 by its name on the wheel, 'I' to 'VIII'. Wheels of other
+    /// models are found with `Model::rotor`.
+    pub fn by_name(name: &str) -> Option<Rotor> {
+        WEHRMACHT_ROTORS
+            .iter()
+            .find(|spec| spec.name == name)
+            .map(Rotor::from_spec)
+    }
 
-        let mut turnover_offsets: Vec<u8> = Vec::with_capacity(turnover.chars().count());
-        for c in turnover.chars() {
-            let i = SUPPORTED_ALPHABET.find(c).expect(&format!(
-                "Turnover error: '{}' must be a letter from set '{}'",
-                c, SUPPORTED_ALPHABET
-            )) as u8;
-            turnover_offsets.push(i);
+    /// Rotor with the given wiring, stepping the next rotor when leaving any of `turnover`
+    /// positions.
+    pub fn new(wiring: Wiring, turnover: &[Letter]) -> Rotor {
+        Rotor {
+            current_offset: 0,
+            ring_offset: 0,
+            forward: wiring.forward(),
+            backward: wiring.inverse(),
+            turnover_offsets: turnover.iter().map(|l| l.index()).collect(),
+            reversed: false,
+            spec: None,
         }
+    }
 
+    // wirings shipped with the library are checked by tests
+    pub(crate) fn from_spec(spec: &'static WheelSpec) -> Rotor {
         Rotor {
-            current_offset: 0,
-            alphabet,
-            turnover_offsets,
+            spec: Some(spec),
+            ..Rotor::built_in(spec.wiring, spec.turnover)
         }
     }
 
+    fn built_in(wiring: &'static str, turnover: &'static str) -> Rotor {
+        let wiring = Wiring::new(wiring).expect("built-in rotor wiring is valid");
+        let turnover: Vec<Letter> = turnover
+            .chars()
+            .map(|c| Letter::new(c).expect("built-in rotor turnover is valid"))
+            .collect();
+        Rotor::new(wiring, &turnover)
+    }
+
+    pub fn with_ring_setting(mut self, ring_setting: char) -> Result<Rotor, Error> {
+        match SUPPORTED_ALPHABET.find(ring_setting) {
+            None => Err(Error::UnsupportedCharacter {
+                character: ring_setting,
+                index: 0,
+            }),
+            Some(position) => {
+                self.ring_offset = position as u8;
+                Ok(self)
+            }
+        }
+    }
+
+    /// Rotor turned to `position` in the window, e.g. the greek wheel of M4, which is never
+    /// turned by the machine.
+    pub fn with_position(mut self, position: char) -> Result<Rotor, Error> {
+        self.turn_to_character(position)?;
+        Ok(self)
+    }
+
+    pub(crate) fn ring_setting(&self) -> char {
+        SUPPORTED_ALPHABET.as_bytes()[self.ring_offset as usize] as char
+    }
+
+    /// Rotor with its wiring core inserted the other way round, like the slugs of Typex - the
+    /// wiring is mirrored and inverted, the ring and the notches stay where they were.
+    /// `Typex` takes reversed rotors as they are, but Enigma could not hold them, so its
+    /// builder rejects them with `BuildError::ReversedWheel` unless it is `permissive()`.
+    pub fn reversed(mut self) -> Rotor {
+        let mirror = |i: u8| (26 - i) % 26;
+        let (forward, backward) = (self.forward, self.backward);
+        for (i, (f, b)) in self
+            .forward
+            .iter_mut()
+            .zip(self.backward.iter_mut())
+            .enumerate()
+        {
+            *f = mirror(backward[mirror(i as u8) as usize]);
+            *b = mirror(forward[mirror(i as u8) as usize]);
+        }
+        self.reversed = !self.reversed;
+        self
+    }
+
+    pub fn is_reversed(&self) -> bool {
+        self.reversed
+    }
+
+    /// Wheel the rotor was made from, if it is one shipped with the library.
+    pub fn spec(&self) -> Option<&'static WheelSpec> {
+        self.spec
+    }
+
     pub(crate) fn encode_from_right(&self, i: u8) -> u8 {
-        let offseted_i = Rotor::offset_positively(i, self.current_offset);
-        let next_encoded = self.alphabet.chars().nth(offseted_i as usize).unwrap();
-        debug!("   --- rotor_r: {}", next_encoded);
-        let next_i = SUPPORTED_ALPHABET.find(next_encoded).unwrap();
-        let next_i = Rotor::offset_negatively(next_i as u8, self.current_offset);
-        next_i
+        let offseted_i = Rotor::offset_positively(i, self.core_offset());
+        let next_i = self.forward[offseted_i as usize];
+        debug!("   --- rotor_r: {}", (b'A' + next_i) as char);
+        Rotor::offset_negatively(next_i, self.core_offset())
     }
 
     pub(crate) fn encode_from_left(&self, i: u8) -> u8 {
-        let offseted_i = Rotor::offset_positively(i, self.current_offset);
-        let next_encoded = SUPPORTED_ALPHABET.chars().nth(offseted_i as usize).unwrap();
-        let next_i = self.alphabet.find(next_encoded).unwrap();
-        debug!(
-            "   --- rotor_l: {}",
-            SUPPORTED_ALPHABET.chars().nth(next_i).unwrap()
-        );
-        let next_i = Rotor::offset_negatively(next_i as u8, self.current_offset);
-        next_i
+        let offseted_i = Rotor::offset_positively(i, self.core_offset());
+        let next_i = self.backward[offseted_i as usize];
+        debug!("   --- rotor_l: {}", (b'A' + next_i) as char);
+        Rotor::offset_negatively(next_i, self.core_offset())
+    }
+
+    // wiring is shifted by the position in the window, but the ring turns it back
+    fn core_offset(&self) -> u8 {
+        Rotor::offset_negatively(self.current_offset, self.ring_offset)
     }
 
-    pub(crate) fn turn_to_character(&mut self, character: char) {
-        self.current_offset = match SUPPORTED_ALPHABET.find(character) {
-            None => panic!(
-                "Character '{}' is not in supported alphabet: {}",
-                character, SUPPORTED_ALPHABET
-            ),
-            Some(position) => position,
-        } as u8;
+    pub(crate) fn turn_to_character(&mut self, character: char) -> Result<(), Error> {
+        self.current_offset = Letter::new(character)?.index();
+        Ok(())
     }
 
     pub(crate) fn rotate(&mut self) -> bool {
@@ -105,21 +173,41 @@ impl Rotor {
         should_rotate_next
     }
 
+    // contact on the left side wired to each contact on the right side
+    pub(crate) fn wiring(&self) -> [u8; 26] {
+        self.forward
+    }
+
+    pub(crate) fn turnover_offsets(&self) -> &[u8] {
+        &self.turnover_offsets
+    }
+
     pub(crate) fn is_in_turnover_position(&self) -> bool {
         self.turnover_offsets.contains(&self.current_offset)
     }
 
+    pub(in crate::rotors) fn is_turnover_offset(&self, offset: u8) -> bool {
+        self.turnover_offsets.contains(&offset)
+    }
+
+    pub(in crate::rotors) fn offset(&self) -> u8 {
+        self.current_offset
+    }
+
+    pub(in crate::rotors) fn set_offset(&mut self, offset: u8) {
+        self.current_offset = offset % 26;
+    }
+
     #[allow(dead_code)] // used in tests
     fn offset_by(&mut self, offset: i8) {
         self.current_offset = if offset.is_positive() {
             Rotor::offset_positively(self.current_offset, offset as u8)
         } else {
-            Rotor::offset_negatively(self.current_offset, (offset * -1) as u8)
+            Rotor::offset_negatively(self.current_offset, (-offset) as u8)
         };
     }
 
-    #[allow(dead_code)] // used in tests
-    pub(in crate::rotors) fn get_offset_character(&self) -> char {
+    pub(crate) fn get_offset_character(&self) -> char {
         SUPPORTED_ALPHABET
             .chars()
             .nth(self.current_offset as usize)
@@ -127,46 +215,37 @@ impl Rotor {
     }
 
     fn offset_positively(offset_source: u8, offset_by: u8) -> u8 {
-        // this function is complicated just because I had a goal in life
-        // to make operations on offset never go out of range 0..25
-        let o = offset_by % 26;
-        if o == 0 {
-            return offset_source;
-        }
-        let allowed_max_offset_before_limit = 25 - offset_source;
-        let will_offset_over_limit = o > allowed_max_offset_before_limit;
-        if will_offset_over_limit {
-            if offset_source == 25 {
-                o - 1
-            } else {
-                o - allowed_max_offset_before_limit - 1
-            }
-        } else {
-            offset_source + o
-        }
+        ((offset_source as u16 + offset_by as u16) % 26) as u8
     }
 
     fn offset_negatively(offset_source: u8, offset_by: u8) -> u8 {
-        // this function is complicated just because I had a goal in life
-        // to make operations on offset never go out of range 0..25
-        let o = offset_by % 26;
-        if o == 0 {
-            offset_source
-        } else if offset_source >= o {
-            offset_source - o
-        } else {
-            26 - (o - offset_source)
-        }
+        ((offset_source as u16 + 26 - (offset_by % 26) as u16) % 26) as u8
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn custom_rotor_from_validated_wiring() {
+        let wiring = Wiring::new("EKMFLGDQVZNTOWYHXUSPAIBRCJ").unwrap();
+        let mut r = Rotor::new(wiring, &[Letter::new('Q').unwrap()]);
+        assert_eq!(
+            r,
+            Rotor {
+                spec: None,
+                ..Rotor::enigma_i_wehrmacht_i()
+            }
+        );
+        r.turn_to_character('Q').unwrap();
+        assert!(r.rotate());
+    }
+
     #[test]
     fn test_rotation() {
-        let mut r = Rotor::new(SUPPORTED_ALPHABET, "Q");
+        let mut r = Rotor::built_in(SUPPORTED_ALPHABET, "Q");
         assert_eq!(r.rotate(), false);
         assert_eq!(r.rotate(), false);
         assert_eq!(r.rotate(), false);
@@ -192,7 +271,7 @@ mod tests {
 
     #[test]
     fn rotor_will_cause_turnover_on_expected_points() {
-        let mut r = Rotor::new(SUPPORTED_ALPHABET, "DGIKW");
+        let mut r = Rotor::built_in(SUPPORTED_ALPHABET, "DGIKW");
 
         for _ in 1..=5 {
             assert_eq!(r.rotate(), false); // A
@@ -253,21 +332,91 @@ mod tests {
         use super::*;
 
         #[test]
-        #[should_panic(expected = "Character 'a' is not in supported alphabet")]
         fn test_1() {
             let mut r = Rotor::enigma_i_wehrmacht_i();
-            r.turn_to_character('a');
+            assert_eq!(
+                r.turn_to_character('a'),
+                Err(Error::UnsupportedCharacter {
+                    character: 'a',
+                    index: 0
+                })
+            );
         }
 
         #[test]
         fn test_2() {
-            let mut r = Rotor::new("ABCDEF", "E");
+            let mut r = Rotor::built_in(SUPPORTED_ALPHABET, "E");
             assert_eq!(r.rotate(), false);
-            r.turn_to_character('E');
+            assert_eq!(r.turn_to_character('E'), Ok(()));
             assert_eq!(r.rotate(), true);
         }
     }
 
+    mod with_ring_setting {
+        use super::*;
+
+        #[test]
+        fn ring_setting_shifts_wiring_but_not_turnover() {
+            let r = Rotor::enigma_i_wehrmacht_i();
+            // 'A' enters on contact 'A' and leaves on 'E'
+            assert_eq!(r.encode_from_right(0), 4);
+
+            let mut r = Rotor::enigma_i_wehrmacht_i()
+                .with_ring_setting('B')
+                .unwrap();
+            // 'A' enters on contact 'Z' which is wired to 'J', which leaves on 'K'
+            assert_eq!(r.encode_from_right(0), 10);
+            assert_eq!(r.encode_from_left(10), 0);
+
+            r.turn_to_character('Q').unwrap();
+            assert!(r.is_in_turnover_position());
+        }
+
+        #[test]
+        fn error_on_unsupported_character() {
+            assert_eq!(
+                Rotor::enigma_i_wehrmacht_i().with_ring_setting('1'),
+                Err(Error::UnsupportedCharacter {
+                    character: '1',
+                    index: 0
+                })
+            );
+        }
+    }
+
+    mod reversed {
+        use super::*;
+
+        #[test]
+        fn mirrors_and_inverts_wiring() {
+            let r = Rotor::enigma_i_wehrmacht_i().reversed();
+            assert!(r.is_reversed());
+            // 'U' is wired to 'A', seen from the other side contact 'G' is wired to 'A'
+            assert_eq!(r.encode_from_right(0), 6);
+            assert_eq!(r.encode_from_left(6), 0);
+            for i in 0..26 {
+                assert_eq!(r.encode_from_left(r.encode_from_right(i)), i);
+            }
+        }
+
+        #[test]
+        fn keeps_turnover_and_ring() {
+            let mut r = Rotor::enigma_i_wehrmacht_i()
+                .with_ring_setting('C')
+                .unwrap()
+                .reversed();
+            assert_eq!(r.ring_offset, 2);
+            r.turn_to_character('Q').unwrap();
+            assert!(r.rotate());
+        }
+
+        #[test]
+        fn reversing_twice_restores_rotor() {
+            let r = Rotor::m3_wehrmacht_iv();
+            assert_eq!(r.clone().reversed().reversed(), r);
+        }
+    }
+
     mod offset_by {
         use super::*;
 
@@ -304,30 +453,3 @@ mod tests {
         }
     }
 }
-
-// ---- names: Heer, Enigma A, Heeres, Wehrmacht, Service Enigma, Army/GAF machine
-#[allow(dead_code)]
-const ENIGMA_I_WEHRMACHT_I_ROTOR: &str = "EKMFLGDQVZNTOWYHXUSPAIBRCJ";
-#[allow(dead_code)]
-const ENIGMA_I_WEHRMACHT_I_TURNOVER: &str = "Q";
-
-#[allow(dead_code)]
-const ENIGMA_I_WEHRMACHT_II_ROTOR: &str = "AJDKSIRUXBLHWTMCQGZNPYFVOE";
-#[allow(dead_code)]
-const ENIGMA_I_WEHRMACHT_II_TURNOVER: &str = "E";
-
-#[allow(dead_code)]
-const ENIGMA_I_WEHRMACHT_III_ROTOR: &str = "BDFHJLCPRTXVZNYEIWGAKMUSQO";
-#[allow(dead_code)]
-const ENIGMA_I_WEHRMACHT_III_TURNOVER: &str = "V";
-
-#[allow(dead_code)]
-const M3_WEHRMACHT_IV_ROTOR: &str = "ESOVPZJAYQUIRHXLNFTGKDCMWB";
-#[allow(dead_code)]
-const M3_WEHRMACHT_IV_TURNOVER: &str = "J";
-
-#[allow(dead_code)]
-const M3_WEHRMACHT_V_ROTOR: &str = "VZBRGITYUPSDNHLXAWMJQOFECK";
-#[allow(dead_code)]
-const M3_WEHRMACHT_V_TURNOVER: &str = "Z";
-// ----
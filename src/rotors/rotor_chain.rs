@@ -1,11 +1,17 @@
-use crate::enigma::SUPPORTED_ALPHABET;
+use std::fmt::Display;
+
+use crate::enigma_builder::RotorPlacement;
+use crate::error::Error;
+use crate::model::SteppingType;
 use crate::rotors::rotor::Rotor;
 use log::debug;
 
+#[derive(Clone)]
 pub struct RotorChain {
     left: Rotor,
     middle: Rotor,
     right: Rotor,
+    stepping_type: SteppingType,
 }
 
 impl RotorChain {
@@ -14,63 +20,307 @@ impl RotorChain {
             left,
             middle,
             right,
+            stepping_type: SteppingType::Ladder,
         }
     }
 
-    pub(crate) fn change_setting<S: AsRef<str>>(&mut self, new_setting: S) -> Result<(), String> {
-        let new_setting_ref = new_setting.as_ref();
-        if new_setting_ref.len() != 3 {
-            return Err(format!(
-                "New setting for rotor chain is unsupported. Required 3 characters, got {}.",
-                new_setting_ref.len()
-            ));
-        }
+    pub(crate) fn with_stepping_type(mut self, stepping_type: SteppingType) -> RotorChain {
+        self.stepping_type = stepping_type;
+        self
+    }
 
-        for c in new_setting_ref.chars() {
-            if let None = SUPPORTED_ALPHABET.find(c) {
-                return Err(format!(
-                    "Character '{}' is not in supported alphabet: {}.",
-                    c, SUPPORTED_ALPHABET
-                ));
-            }
-        }
+    pub(crate) fn change_setting<S: AsRef<str>>(&mut self, new_setting: S) -> Result<(), Error> {
+        let new_setting_ref = new_setting.as_ref();
+        Error::check_length("Rotor positions", new_setting_ref, 3)?;
+        Error::check_alphabet(new_setting_ref)?;
 
         self.left
-            .turn_to_character(new_setting_ref.chars().nth(0).unwrap());
+            .turn_to_character(new_setting_ref.chars().nth(0).unwrap())?;
         self.middle
-            .turn_to_character(new_setting_ref.chars().nth(1).unwrap());
+            .turn_to_character(new_setting_ref.chars().nth(1).unwrap())?;
         self.right
-            .turn_to_character(new_setting_ref.chars().nth(2).unwrap());
+            .turn_to_character(new_setting_ref.chars().nth(2).unwrap())?;
 
         Ok(())
     }
 
+    // window letters stay, the wiring cores turn against the letter rings
+    pub(crate) fn change_ring_setting(&mut self, rings: &str) -> Result<(), Error> {
+        Error::check_length("Ring settings", rings, 3)?;
+        Error::check_alphabet(rings)?;
+        let mut rings = rings.chars();
+        for rotor in [&mut self.left, &mut self.middle, &mut self.right].iter_mut() {
+            **rotor = rotor.clone().with_ring_setting(rings.next().unwrap())?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn ring_settings(&self) -> String {
+        self.rotors().iter().map(|r| r.ring_setting()).collect()
+    }
+
     pub(crate) fn encode_from_right(&self, encoded: u8) -> u8 {
+        self.encode_from_right_traced(encoded, |_, _| {})
+    }
+
+    pub(crate) fn encode_from_left(&self, encoded: u8) -> u8 {
+        self.encode_from_left_traced(encoded, |_, _| {})
+    }
+
+    // `record` is called with the letter leaving each rotor
+    pub(crate) fn encode_from_right_traced<F>(&self, encoded: u8, mut record: F) -> u8
+    where
+        F: FnMut(RotorPlacement, u8),
+    {
         let mut e = self.right.encode_from_right(encoded);
+        record(RotorPlacement::Right, e);
         e = self.middle.encode_from_right(e);
+        record(RotorPlacement::Middle, e);
         e = self.left.encode_from_right(e);
+        record(RotorPlacement::Left, e);
         e
     }
 
-    pub(crate) fn encode_from_left(&self, encoded: u8) -> u8 {
+    pub(crate) fn encode_from_left_traced<F>(&self, encoded: u8, mut record: F) -> u8
+    where
+        F: FnMut(RotorPlacement, u8),
+    {
         let mut e = self.left.encode_from_left(encoded);
+        record(RotorPlacement::Left, e);
         e = self.middle.encode_from_left(e);
+        record(RotorPlacement::Middle, e);
         e = self.right.encode_from_left(e);
+        record(RotorPlacement::Right, e);
         e
     }
 
-    pub(crate) fn rotate(&mut self) {
+    // left, middle and right
+    pub(crate) fn rotors(&self) -> [&Rotor; 3] {
+        [&self.left, &self.middle, &self.right]
+    }
+
+    /// Window letters of left, middle and right rotor.
+    pub(crate) fn positions(&self) -> String {
+        [&self.left, &self.middle, &self.right]
+            .iter()
+            .map(|r| r.get_offset_character())
+            .collect()
+    }
+
+    pub(crate) fn rotate(&mut self) -> Stepping {
+        if self.stepping_type == SteppingType::Gear {
+            return self.rotate_gear();
+        }
         let will_rotate_middle = self.right.rotate();
-        if will_rotate_middle || self.middle.is_in_turnover_position() {
+        let middle_in_turnover_position = self.middle.is_in_turnover_position();
+        let mut stepping = Stepping {
+            left: false,
+            middle: false,
+            right: true,
+            double_step: false,
+        };
+        if will_rotate_middle || middle_in_turnover_position {
             debug!(
                 "Will force next rotor rotation due to double step? {}",
-                self.middle.is_in_turnover_position()
+                middle_in_turnover_position
             );
+            stepping.middle = true;
+            stepping.double_step = !will_rotate_middle;
             let will_rotate_left = self.middle.rotate();
             if will_rotate_left {
                 self.left.rotate();
+                stepping.left = true;
+            }
+        }
+        stepping
+    }
+
+    // like an odometer, each rotor is carried only by the one to its right
+    fn rotate_gear(&mut self) -> Stepping {
+        let mut stepping = Stepping {
+            left: false,
+            middle: false,
+            right: true,
+            double_step: false,
+        };
+        if self.right.rotate() {
+            stepping.middle = true;
+            if self.middle.rotate() {
+                self.left.rotate();
+                stepping.left = true;
             }
         }
+        stepping
+    }
+
+    /// Exact inverse of `rotate` for the positions `rotate` can lead to. Positions reached by
+    /// a double step can also be reached without it when the middle rotor was set on its notch
+    /// by hand - the double step is assumed then only if the right rotor is where the regular
+    /// stepping would have left it.
+    pub(crate) fn rotate_back(&mut self) {
+        if self.stepping_type == SteppingType::Gear {
+            return self.rotate_gear_back();
+        }
+        let (l, m, r) = (
+            self.left.offset(),
+            self.middle.offset(),
+            self.right.offset(),
+        );
+        let previous = |o: u8| (o + 25) % 26;
+        let r0 = previous(r);
+        let m0 = previous(m);
+
+        let without_middle_step =
+            !self.right.is_turnover_offset(r0) && !self.middle.is_turnover_offset(m);
+        let with_double_step = self.middle.is_turnover_offset(m0);
+        let carried_by_right = self.right.is_turnover_offset(r0);
+
+        self.right.set_offset(r0);
+        if with_double_step && (!without_middle_step || self.right.is_turnover_offset(previous(r0)))
+        {
+            self.middle.set_offset(m0);
+            self.left.set_offset(previous(l));
+        } else if carried_by_right {
+            self.middle.set_offset(m0);
+        }
+    }
+
+    /// Same as `rotate_back` called `key_presses` times. The positions repeat within 26^3
+    /// steps, so at most one cycle of them is walked.
+    pub(crate) fn rewind(&mut self, key_presses: u64) {
+        const UNSEEN: u64 = u64::MAX;
+        let mut first_seen = vec![UNSEEN; 26 * 26 * 26];
+        // offsets after i steps back
+        let mut path: Vec<[u8; 3]> = vec![];
+        for i in 0..key_presses {
+            let offsets = [
+                self.left.offset(),
+                self.middle.offset(),
+                self.right.offset(),
+            ];
+            let seen = &mut first_seen[offsets.iter().fold(0, |n, &o| n * 26 + o as usize)];
+            if *seen != UNSEEN {
+                let [l, m, r] = path[(*seen + (key_presses - i) % (i - *seen)) as usize];
+                self.left.set_offset(l);
+                self.middle.set_offset(m);
+                self.right.set_offset(r);
+                return;
+            }
+            *seen = i;
+            path.push(offsets);
+            self.rotate_back();
+        }
+    }
+
+    fn rotate_gear_back(&mut self) {
+        let previous = |o: u8| (o + 25) % 26;
+        let r0 = previous(self.right.offset());
+        self.right.set_offset(r0);
+        if self.right.is_turnover_offset(r0) {
+            let m0 = previous(self.middle.offset());
+            self.middle.set_offset(m0);
+            if self.middle.is_turnover_offset(m0) {
+                self.left.set_offset(previous(self.left.offset()));
+            }
+        }
+    }
+
+    /// Positions after `key_presses` without stepping one by one. Middle and right rotor step
+    /// independently of the left one, so their positions start to repeat within 676 key
+    /// presses, and the left rotor steps every time the middle one is on its notch. With gear
+    /// stepping the carries of each rotor are counted directly.
+    pub(crate) fn advance(&mut self, key_presses: u64) {
+        if self.stepping_type == SteppingType::Gear {
+            // like an odometer, each rotor turns by the carries of the one to its right
+            let middle_steps = RotorChain::turn(&mut self.right, key_presses);
+            let left_steps = RotorChain::turn(&mut self.middle, middle_steps);
+            RotorChain::turn(&mut self.left, left_steps);
+            return;
+        }
+        const UNSEEN: usize = usize::MAX;
+        let mut first_seen = [UNSEEN; 26 * 26];
+        // middle and right offsets after i key presses
+        let mut path: Vec<(u8, u8)> = vec![];
+        // left rotor steps after i key presses
+        let mut left_steps: Vec<u64> = vec![0];
+
+        let (mut m, mut r) = (self.middle.offset(), self.right.offset());
+        let mut cycle_start = None;
+        while (path.len() as u64) < key_presses {
+            let seen = &mut first_seen[m as usize * 26 + r as usize];
+            if *seen != UNSEEN {
+                cycle_start = Some(*seen);
+                break;
+            }
+            *seen = path.len();
+            path.push((m, r));
+
+            let middle_at_turnover = self.middle.is_turnover_offset(m);
+            if middle_at_turnover || self.right.is_turnover_offset(r) {
+                m = (m + 1) % 26;
+            }
+            r = (r + 1) % 26;
+            left_steps.push(left_steps[left_steps.len() - 1] + middle_at_turnover as u64);
+        }
+
+        let left_stepped = match cycle_start {
+            None => left_steps[path.len()],
+            Some(start) => {
+                let period = (path.len() - start) as u64;
+                let cycles = (key_presses - start as u64) / period;
+                let rest = ((key_presses - start as u64) % period) as usize;
+                let left_per_cycle = left_steps[path.len()] - left_steps[start];
+                m = path[start + rest].0;
+                r = path[start + rest].1;
+                left_steps[start + rest] + cycles * left_per_cycle
+            }
+        };
+        let l = (self.left.offset() as u64 + left_stepped % 26) as u8;
+        self.left.set_offset(l);
+        self.middle.set_offset(m);
+        self.right.set_offset(r);
+    }
+
+    // turns the rotor by `steps` and returns how many times it passed a notch
+    fn turn(rotor: &mut Rotor, steps: u64) -> u64 {
+        let start = rotor.offset() as u64;
+        let notches_within = |n: u64| {
+            (0..n)
+                .filter(|i| rotor.is_turnover_offset(((start + i) % 26) as u8))
+                .count()
+        };
+        let carries = steps / 26 * notches_within(26) as u64 + notches_within(steps % 26) as u64;
+        rotor.set_offset(((start + steps % 26) % 26) as u8);
+        carries
+    }
+}
+
+/// Rotors which stepped on a key press.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stepping {
+    pub left: bool,
+    pub middle: bool,
+    pub right: bool,
+    // middle rotor stepped because of its own notch, not carried by the right one
+    pub double_step: bool,
+}
+
+impl Display for Stepping {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let stepped: Vec<&str> = [
+            (self.left, "left"),
+            (self.middle, "middle"),
+            (self.right, "right"),
+        ]
+        .iter()
+        .filter(|(stepped, _)| *stepped)
+        .map(|(_, name)| *name)
+        .collect();
+        write!(f, "{} stepped", stepped.join(", "))?;
+        if self.double_step {
+            write!(f, " (double step)")?;
+        }
+        Ok(())
     }
 }
 
@@ -90,10 +340,11 @@ mod tests {
             let mut chain = RotorChain::new(r1, r2, r3);
             assert_eq!(
                 chain.change_setting("X"),
-                Err(
-                    "New setting for rotor chain is unsupported. Required 3 characters, got 1."
-                        .into()
-                )
+                Err(Error::InvalidLength {
+                    subject: "Rotor positions",
+                    expected: 3,
+                    actual: 1
+                })
             );
         }
 
@@ -106,10 +357,11 @@ mod tests {
             let mut chain = RotorChain::new(r1, r2, r3);
             assert_eq!(
                 chain.change_setting("XXXXXX"),
-                Err(
-                    "New setting for rotor chain is unsupported. Required 3 characters, got 6."
-                        .into()
-                )
+                Err(Error::InvalidLength {
+                    subject: "Rotor positions",
+                    expected: 3,
+                    actual: 6
+                })
             );
         }
 
@@ -122,21 +374,21 @@ mod tests {
             let mut chain = RotorChain::new(r1, r2, r3);
             assert_eq!(
                 chain.change_setting("XX1"),
-                Err(
-                    "Character '1' is not in supported alphabet: ABCDEFGHIJKLMNOPQRSTUVWXYZ."
-                        .into()
-                )
+                Err(Error::UnsupportedCharacter {
+                    character: '1',
+                    index: 2
+                })
             );
         }
 
         #[test]
         fn sets_new_setting_for_rotors_in_chain() {
             let mut r1 = Rotor::enigma_i_wehrmacht_i();
-            r1.turn_to_character('A');
+            r1.turn_to_character('A').unwrap();
             let mut r2 = Rotor::enigma_i_wehrmacht_ii();
-            r2.turn_to_character('A');
+            r2.turn_to_character('A').unwrap();
             let mut r3 = Rotor::enigma_i_wehrmacht_iii();
-            r3.turn_to_character('A');
+            r3.turn_to_character('A').unwrap();
 
             let mut chain = RotorChain::new(r1, r2, r3);
 
@@ -186,12 +438,190 @@ mod tests {
         assert_eq!("BFY", get_offsets_string_for_chain(&chain));
     }
 
+    mod rotate_back {
+        use super::*;
+
+        #[test]
+        fn inverts_rotation_including_double_steps() {
+            let mut chain = enigma_i_chain();
+            assert_eq!(chain.change_setting("ADS"), Ok(()));
+            let mut positions = vec![];
+            for _ in 0..2000 {
+                positions.push(get_offsets_string_for_chain(&chain));
+                chain.rotate();
+            }
+            for expected in positions.iter().rev() {
+                chain.rotate_back();
+                assert_eq!(&get_offsets_string_for_chain(&chain), expected);
+            }
+        }
+
+        #[test]
+        fn steps_back_through_double_step() {
+            let mut chain = enigma_i_chain();
+            assert_eq!(chain.change_setting("BFX"), Ok(()));
+            chain.rotate_back();
+            assert_eq!("AEW", get_offsets_string_for_chain(&chain));
+            chain.rotate_back();
+            assert_eq!("ADV", get_offsets_string_for_chain(&chain));
+            chain.rotate_back();
+            assert_eq!("ADU", get_offsets_string_for_chain(&chain));
+        }
+
+        #[test]
+        fn assumes_regular_stepping_when_both_are_possible() {
+            let mut chain = enigma_i_chain();
+            // middle rotor set on its notch by hand steps together with the left one
+            assert_eq!(chain.change_setting("AEA"), Ok(()));
+            chain.rotate();
+            assert_eq!("BFB", get_offsets_string_for_chain(&chain));
+            chain.rotate_back();
+            assert_eq!("BFA", get_offsets_string_for_chain(&chain));
+        }
+    }
+
+    mod advance {
+        use super::*;
+
+        #[test]
+        fn same_as_rotating_one_by_one() {
+            for setting in ["AAA", "ADU", "AEA", "ZZZ", "QEV"].iter() {
+                let mut rotated = enigma_i_chain();
+                assert_eq!(rotated.change_setting(setting), Ok(()));
+                let mut advanced = enigma_i_chain();
+                for n in 0..1000 {
+                    assert_eq!(advanced.change_setting(setting), Ok(()));
+                    advanced.advance(n);
+                    assert_eq!(
+                        get_offsets_string_for_chain(&advanced),
+                        get_offsets_string_for_chain(&rotated),
+                        "{} advanced by {}",
+                        setting,
+                        n
+                    );
+                    rotated.rotate();
+                }
+            }
+        }
+
+        #[test]
+        fn advances_by_many_periods() {
+            // rotors I, II and III return to the same positions every 26 * 25 * 26 key presses
+            let mut chain = enigma_i_chain();
+            assert_eq!(chain.change_setting("QEV"), Ok(()));
+            chain.advance(16900 * 1_000_000_000 + 1234);
+            let mut expected = enigma_i_chain();
+            assert_eq!(expected.change_setting("QEV"), Ok(()));
+            for _ in 0..1234 {
+                expected.rotate();
+            }
+            assert_eq!(
+                get_offsets_string_for_chain(&chain),
+                get_offsets_string_for_chain(&expected)
+            );
+        }
+    }
+
+    #[test]
+    fn rewinds_like_rotating_back() {
+        for setting in ["AAA", "ADU", "AEA", "QEV"].iter() {
+            let mut rotated = enigma_i_chain();
+            assert_eq!(rotated.change_setting(setting), Ok(()));
+            let mut positions = vec![];
+            for _ in 0..20_000 {
+                positions.push(get_offsets_string_for_chain(&rotated));
+                rotated.rotate_back();
+            }
+            // beyond the cycle of rotors I, II and III too
+            for n in (0..100).chain([675, 676, 16_900, 16_901, 17_576, 19_999].iter().copied()) {
+                let mut rewound = enigma_i_chain();
+                assert_eq!(rewound.change_setting(setting), Ok(()));
+                rewound.rewind(n as u64);
+                assert_eq!(
+                    get_offsets_string_for_chain(&rewound),
+                    positions[n],
+                    "{} rewound by {}",
+                    setting,
+                    n
+                );
+            }
+        }
+        let mut chain = enigma_i_chain();
+        chain.rewind(u64::MAX);
+    }
+
+    mod gear {
+        use super::*;
+        use crate::model::Model;
+
+        fn enigma_g_chain() -> RotorChain {
+            let g = Model::by_name("G").unwrap();
+            RotorChain::new(
+                g.rotor("I").unwrap(),
+                g.rotor("II").unwrap(),
+                g.rotor("III").unwrap(),
+            )
+            .with_stepping_type(SteppingType::Gear)
+        }
+
+        #[test]
+        fn rotates_back_and_advances_like_rotating() {
+            let mut chain = enigma_g_chain();
+            assert_eq!(chain.change_setting("ZQV"), Ok(()));
+            let mut positions = vec![];
+            for _ in 0..2000 {
+                positions.push(get_offsets_string_for_chain(&chain));
+                chain.rotate();
+            }
+            let mut advanced = enigma_g_chain();
+            assert_eq!(advanced.change_setting("ZQV"), Ok(()));
+            advanced.advance(26 * 26 * 26 * 1_000_000 + 2000);
+            assert_eq!(
+                get_offsets_string_for_chain(&advanced),
+                get_offsets_string_for_chain(&chain)
+            );
+            for expected in positions.iter().rev() {
+                chain.rotate_back();
+                assert_eq!(&get_offsets_string_for_chain(&chain), expected);
+            }
+        }
+
+        #[test]
+        fn advances_through_all_positions_like_rotating() {
+            for setting in ["AAA", "ZQV", "QEV"].iter() {
+                let mut rotated = enigma_g_chain();
+                assert_eq!(rotated.change_setting(setting), Ok(()));
+                let mut advanced = enigma_g_chain();
+                for n in 0..26 * 26 * 26 + 100 {
+                    assert_eq!(advanced.change_setting(setting), Ok(()));
+                    advanced.advance(n);
+                    assert_eq!(
+                        get_offsets_string_for_chain(&advanced),
+                        get_offsets_string_for_chain(&rotated),
+                        "{} advanced by {}",
+                        setting,
+                        n
+                    );
+                    rotated.rotate();
+                }
+            }
+        }
+    }
+
+    fn enigma_i_chain() -> RotorChain {
+        RotorChain::new(
+            Rotor::enigma_i_wehrmacht_i(),
+            Rotor::enigma_i_wehrmacht_ii(),
+            Rotor::enigma_i_wehrmacht_iii(),
+        )
+    }
+
     fn get_offsets_string_for_chain(chain: &RotorChain) -> String {
-        return format!(
+        format!(
             "{}{}{}",
             chain.left.get_offset_character(),
             chain.middle.get_offset_character(),
             chain.right.get_offset_character()
-        );
+        )
     }
 }
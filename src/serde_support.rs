@@ -0,0 +1,110 @@
+// Values which already have a text form are (de)serialized as that text, so a saved setup
+// reads like the notation used everywhere else, e.g. "AB CD EF" for the plugboard.
+
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::alphabet::{Letter, Wiring};
+use crate::enigma::MachineState;
+use crate::plugboard::Plugboard;
+
+macro_rules! serde_as_text {
+    ($($t:ty),*) => {
+        $(
+            impl Serialize for $t {
+                fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    serializer.collect_str(self)
+                }
+            }
+
+            impl<'de> Deserialize<'de> for $t {
+                fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                    String::deserialize(deserializer)?
+                        .parse()
+                        .map_err(D::Error::custom)
+                }
+            }
+        )*
+    };
+}
+
+serde_as_text!(Letter, Wiring, Plugboard, MachineState);
+
+#[cfg(test)]
+mod tests {
+    use crate::machine_setting::{MachineSetting, Wheel};
+    use crate::Error;
+
+    use super::*;
+
+    #[test]
+    fn machine_setting_round_trips() {
+        let setting: MachineSetting = "M3 UKW-B III II I rings 01 26 08 stecker AB CD EF pos ADU"
+            .parse()
+            .unwrap();
+        let json = serde_json::to_string(&setting).unwrap();
+        assert_eq!(
+            json,
+            r#"{"model":"M3","reflector":"B","rotors":["III","II","I"],"rings":["A","Z","H"],"positions":["A","D","U"],"plugboard":"AB CD EF"}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<MachineSetting>(&json).unwrap(),
+            setting
+        );
+    }
+
+    #[test]
+    fn greek_wheel_is_written_only_for_m4() {
+        let setting: MachineSetting = "M4 UKW-B Beta II IV I rings 01 01 01 22 pos VJNA"
+            .parse()
+            .unwrap();
+        let json = serde_json::to_string(&setting).unwrap();
+        assert_eq!(
+            json,
+            r#"{"model":"M4","reflector":"B","greek_wheel":{"wheel":"Beta","ring":"A","position":"V"},"rotors":["II","IV","I"],"rings":["A","A","V"],"positions":["J","N","A"],"plugboard":""}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<MachineSetting>(&json).unwrap(),
+            setting
+        );
+    }
+
+    #[test]
+    fn custom_wiring_is_an_object() {
+        let json = r#"{"model":null,"reflector":{"wiring":"YRUHQSLDPXNGOKMIEBFZCWVJAT"},
+            "rotors":["I","II",{"wiring":"BDFHJLCPRTXVZNYEIWGAKMUSQO","turnover":["V"]}],
+            "rings":["A","A","A"],"positions":["A","A","A"],"plugboard":""}"#;
+        let setting: MachineSetting = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            setting.rotors[2],
+            Wheel::Wired {
+                wiring: "BDFHJLCPRTXVZNYEIWGAKMUSQO".parse().unwrap(),
+                turnover: vec![Letter::new('V').unwrap()]
+            }
+        );
+        // same wheels as 'B-123-AAA-AAA'
+        let mut enigma = setting.build().unwrap();
+        assert_eq!(
+            enigma.encode_for_current_rotor_setting("AAAAA").unwrap(),
+            "BDZGO"
+        );
+    }
+
+    #[test]
+    fn state_and_invalid_values() {
+        let state = MachineState::new("ADU", 3).unwrap();
+        let json = serde_json::to_string(&state).unwrap();
+        assert_eq!(json, r#""ADU:3""#);
+        assert_eq!(serde_json::from_str::<MachineState>(&json).unwrap(), state);
+
+        let error = serde_json::from_str::<Plugboard>(r#""AB BC""#).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            Error::LetterAlreadyPlugged {
+                letter: 'B',
+                connected_to: 'A'
+            }
+            .to_string()
+        );
+    }
+}
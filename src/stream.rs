@@ -0,0 +1,268 @@
+// Encoding of streams of any length - the machine keeps stepping between the chunks, so the
+// result is the same as if the whole input was encoded at once.
+
+use std::io::{self, Read, Write};
+use std::str::FromStr;
+
+use crate::error::Error;
+use crate::machine::Machine;
+
+/// What happens with characters which are not letters, e.g. spaces and punctuation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NonLetterPolicy {
+    // dropped from the output
+    Skip,
+    // copied to the output unchanged, rotors do not step
+    PassThrough,
+    // stream fails with `io::ErrorKind::InvalidData`
+    Fail,
+}
+
+impl FromStr for NonLetterPolicy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "skip" => Ok(NonLetterPolicy::Skip),
+            "pass-through" => Ok(NonLetterPolicy::PassThrough),
+            "fail" => Ok(NonLetterPolicy::Fail),
+            _ => Err(Error::UnsupportedNonLetterPolicy(s.into())),
+        }
+    }
+}
+
+/// Writer encoding all the letters written to it (lowercase ones are uppercased) before
+/// passing them to the inner writer.
+pub struct EncodingWriter<'a, W: Write, M: Machine + ?Sized> {
+    machine: &'a mut M,
+    inner: W,
+    policy: NonLetterPolicy,
+    buffer: Vec<u8>,
+    // bytes accepted so far
+    position: u64,
+}
+
+impl<'a, W: Write, M: Machine + ?Sized> EncodingWriter<'a, W, M> {
+    pub fn new(machine: &'a mut M, inner: W, policy: NonLetterPolicy) -> Self {
+        EncodingWriter {
+            machine,
+            inner,
+            policy,
+            buffer: vec![],
+            position: 0,
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<'a, W: Write, M: Machine + ?Sized> Write for EncodingWriter<'a, W, M> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.clear();
+        // failing byte and the error
+        let mut error = None;
+        for (i, b) in buf.iter().enumerate() {
+            if b.is_ascii_alphabetic() {
+                let key = b.to_ascii_uppercase() as char;
+                match self.machine.press(key) {
+                    Ok(encoded) => self.buffer.push(encoded as u8),
+                    Err(err) => {
+                        error = Some((i, io::Error::new(io::ErrorKind::InvalidData, err)));
+                        break;
+                    }
+                }
+            } else {
+                match self.policy {
+                    NonLetterPolicy::Skip => {}
+                    NonLetterPolicy::PassThrough => self.buffer.push(*b),
+                    NonLetterPolicy::Fail => {
+                        let character = if b.is_ascii() {
+                            format!("'{}'", (*b as char).escape_default())
+                        } else {
+                            format!("byte 0x{:02X}", b)
+                        };
+                        let message = format!(
+                            "Unsupported character {} at position {}",
+                            character,
+                            self.position + i as u64
+                        );
+                        error = Some((i, io::Error::new(io::ErrorKind::InvalidData, message)));
+                        break;
+                    }
+                }
+            }
+        }
+        // the buffer holds only the bytes before the failing one
+        let accepted = match &error {
+            Some((i, _)) => *i,
+            None => buf.len(),
+        };
+        self.inner.write_all(&self.buffer)?;
+        self.position += accepted as u64;
+        match error {
+            Some((_, error)) if accepted == 0 => Err(error),
+            _ => Ok(accepted),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Encodes everything read from `input` into `output`, returns the number of bytes read.
+pub fn encode_stream<R: Read, W: Write, M: Machine + ?Sized>(
+    machine: &mut M,
+    mut input: R,
+    output: W,
+    policy: NonLetterPolicy,
+) -> io::Result<u64> {
+    let mut writer = EncodingWriter::new(machine, output, policy);
+    let read = io::copy(&mut input, &mut writer)?;
+    writer.flush()?;
+    Ok(read)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enigma::{Enigma, MachineState};
+    use crate::enigma_builder::EnigmaBuilder;
+    use crate::reflector::Reflector;
+    use crate::rotors::rotor::Rotor;
+
+    fn enigma() -> Enigma {
+        let mut enigma = EnigmaBuilder::init()
+            .rotor_left(Rotor::enigma_i_wehrmacht_i())
+            .rotor_middle(Rotor::enigma_i_wehrmacht_ii())
+            .rotor_right(Rotor::enigma_i_wehrmacht_iii())
+            .reflector(Reflector::b())
+            .build()
+            .unwrap();
+        enigma.change_rotor_setting("AAA").unwrap();
+        enigma
+    }
+
+    fn encode(input: &str, policy: NonLetterPolicy) -> io::Result<String> {
+        let mut output = vec![];
+        encode_stream(&mut enigma(), input.as_bytes(), &mut output, policy)?;
+        Ok(String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn encodes_same_as_whole_message() {
+        assert_eq!(encode("AAAAA", NonLetterPolicy::Fail).unwrap(), "BDZGO");
+        assert_eq!(encode("aaAaa", NonLetterPolicy::Fail).unwrap(), "BDZGO");
+    }
+
+    #[test]
+    fn keeps_machine_state_between_chunks() {
+        let mut enigma = enigma();
+        let mut output = vec![];
+        {
+            let mut writer = EncodingWriter::new(&mut enigma, &mut output, NonLetterPolicy::Fail);
+            writer.write_all(b"AA").unwrap();
+            writer.write_all(b"A").unwrap();
+            writer.write_all(b"AA").unwrap();
+        }
+        assert_eq!(output, b"BDZGO");
+
+        // machine is left where the stream ended
+        output.clear();
+        encode_stream(
+            &mut enigma,
+            &b"AAAAA"[..],
+            &mut output,
+            NonLetterPolicy::Fail,
+        )
+        .unwrap();
+        assert_eq!(output, b"WCXLT");
+    }
+
+    #[test]
+    fn non_letter_policies() {
+        assert_eq!(
+            encode("AA A-\nAA", NonLetterPolicy::PassThrough).unwrap(),
+            "BD Z-\nGO"
+        );
+        assert_eq!(encode("AA A-\nAA", NonLetterPolicy::Skip).unwrap(), "BDZGO");
+        let error = encode("AA A", NonLetterPolicy::Fail).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(error.to_string(), "Unsupported character ' ' at position 2");
+    }
+
+    #[test]
+    fn passes_through_non_ascii_bytes() {
+        assert_eq!(encode("AÄA", NonLetterPolicy::PassThrough).unwrap(), "BÄD");
+        assert_eq!(
+            encode("AÄA", NonLetterPolicy::Fail)
+                .unwrap_err()
+                .to_string(),
+            "Unsupported character byte 0xC3 at position 1"
+        );
+    }
+
+    // any machine implemented outside the library may reject letters
+    struct WithoutJ(Enigma);
+
+    impl Machine for WithoutJ {
+        fn press(&mut self, key: char) -> Result<char, Error> {
+            if key == 'J' {
+                return Err(Error::UnsupportedCharacter {
+                    character: key,
+                    index: 0,
+                });
+            }
+            self.0.press(key)
+        }
+        fn advance(&mut self, key_presses: u64) {
+            self.0.advance(key_presses)
+        }
+        fn step_back(&mut self) {
+            self.0.step_back()
+        }
+        fn rotor_positions(&self) -> String {
+            self.0.rotor_positions()
+        }
+        fn change_rotor_setting(&mut self, setting: &str) -> Result<(), Error> {
+            self.0.change_rotor_setting(setting)
+        }
+        fn snapshot(&self) -> MachineState {
+            self.0.snapshot()
+        }
+        fn restore(&mut self, state: &MachineState) -> Result<(), Error> {
+            self.0.restore(state)
+        }
+        fn reset(&mut self) {
+            self.0.reset()
+        }
+    }
+
+    #[test]
+    fn letter_rejected_by_machine_is_invalid_data() {
+        let mut machine = WithoutJ(enigma());
+        let mut output = vec![];
+        let error = encode_stream(
+            &mut machine,
+            &b"AAJA"[..],
+            &mut output,
+            NonLetterPolicy::PassThrough,
+        )
+        .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(output, b"BD");
+    }
+
+    #[test]
+    fn non_letter_policy_from_str() {
+        assert_eq!("skip".parse(), Ok(NonLetterPolicy::Skip));
+        assert_eq!("pass-through".parse(), Ok(NonLetterPolicy::PassThrough));
+        assert_eq!("fail".parse(), Ok(NonLetterPolicy::Fail));
+        assert_eq!(
+            "keep".parse::<NonLetterPolicy>(),
+            Err(Error::UnsupportedNonLetterPolicy("keep".into()))
+        );
+    }
+}